@@ -1,111 +1,377 @@
-use std::collections::LinkedList;
-use std::fs::File;
 use std::io;
 use std::io::Write;
+use std::mem;
 
-use colored::Colorize;
+use crate::asm_generator::asm::{peephole, printer, AsmInstr, Syntax};
+use crate::asm_generator::asm_instructions::{X86Backend, I386_LINUX, X86_64_LINUX};
+use crate::asm_generator::backend::Backend;
+use crate::asm_generator::c_backend::CBackend;
+use crate::asm_generator::js_backend::JsBackend;
+use crate::asm_generator::llvm_backend::LlvmBackend;
+use crate::asm_generator::rust_backend::RustBackend;
+use crate::asm_generator::wat_backend::WatBackend;
+use crate::grammar::{Expression, Span, Token};
 
-use crate::asm_generator::asm_instructions::*;
-use crate::grammar::{Expression, Token};
-
-mod array_list_asm;
+pub mod asm;
 mod asm_instructions;
+pub mod backend;
+mod c_backend;
+mod cell_cache;
+mod debug_info;
+pub mod elf_writer;
+mod js_backend;
+mod llvm_backend;
+mod rust_backend;
+mod wat_backend;
+pub mod x86_64_encoder;
+
+/// Architectures and operating systems assembly can be generated for.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Target {
+    /// 32-bit x86 Linux using `int $0x80`
+    I386Linux,
+    /// 32-bit x86 Linux using `int 0x80`, in Intel syntax for NASM
+    I386LinuxNasm,
+    /// 64-bit x86 Linux using `syscall`
+    X86_64Linux,
+    /// 64-bit x86 Linux executable written without an assembler or a linker
+    X86_64LinuxElf,
+    /// Portable C source
+    C,
+    /// Standalone Rust source with a `main` function
+    Rust,
+    /// Rust source of a `run` function to vendor into other crates
+    RustFn,
+    /// Textual LLVM IR
+    Llvm,
+    /// WebAssembly text running on WASI
+    Wat,
+    /// JavaScript ES module for browsers and Node
+    Js,
+}
+
+impl Target {
+    /// Every target that can be picked
+    pub const ALL: [Target; 10] = [
+        Target::I386Linux,
+        Target::I386LinuxNasm,
+        Target::X86_64Linux,
+        Target::X86_64LinuxElf,
+        Target::C,
+        Target::Rust,
+        Target::RustFn,
+        Target::Llvm,
+        Target::Wat,
+        Target::Js,
+    ];
+
+    /// Gives the name used for the target on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::I386Linux => "i386-linux",
+            Target::I386LinuxNasm => "i386-linux-nasm",
+            Target::X86_64Linux => "x86_64-linux",
+            Target::X86_64LinuxElf => "x86_64-linux-elf",
+            Target::C => "c",
+            Target::Rust => "rust",
+            Target::RustFn => "rust-fn",
+            Target::Llvm => "llvm",
+            Target::Wat => "wat",
+            Target::Js => "js",
+        }
+    }
+
+    /// Finds the target with the given command line name.
+    pub fn from_name(name: &str) -> Option<Target> {
+        Self::ALL.into_iter().find(|target| target.name() == name)
+    }
+
+    /// Whether the generated program can dump its tape on a signal, which takes a
+    /// handler installed with Linux syscalls.
+    pub fn handles_signals(&self) -> bool {
+        matches!(
+            self,
+            Target::I386Linux | Target::I386LinuxNasm | Target::X86_64Linux | Target::X86_64LinuxElf
+        )
+    }
+
+    /// Whether `.loc` directives and DWARF sections can be put in the generated code, which
+    /// takes the GNU assembler.
+    pub fn has_debug_info(&self) -> bool {
+        matches!(self, Target::I386Linux | Target::X86_64Linux)
+    }
+
+    /// Whether the target is an executable written by the
+    /// [elf_writer](elf_writer::write_executable) instead of source code.
+    pub fn is_executable(&self) -> bool {
+        *self == Target::X86_64LinuxElf
+    }
+
+    /// Creates the code generator for the target.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Runtime behaviour of the generated program.
+    pub fn backend(&self, options: CodegenOptions) -> Box<dyn Backend> {
+        match self {
+            Target::I386Linux => Box::new(X86Backend::new(&I386_LINUX, Syntax::Att, options)),
+            Target::I386LinuxNasm => Box::new(X86Backend::new(&I386_LINUX, Syntax::Intel, options)),
+            Target::X86_64Linux => Box::new(X86Backend::new(&X86_64_LINUX, Syntax::Att, options)),
+            Target::X86_64LinuxElf => {
+                unreachable!("Executables are encoded without a code generator")
+            }
+            Target::C => Box::new(CBackend::new(options)),
+            Target::Rust => Box::new(RustBackend::new(true, options)),
+            Target::RustFn => Box::new(RustBackend::new(false, options)),
+            Target::Llvm => Box::new(LlvmBackend::new(options)),
+            Target::Wat => Box::new(WatBackend::new(options)),
+            Target::Js => Box::new(JsBackend::new(options)),
+        }
+    }
+
+    /// Gives the target matching the machine the compiler runs on, if it is an x86 one.
+    pub fn host() -> Option<Target> {
+        if cfg!(target_arch = "x86_64") {
+            Some(Target::X86_64Linux)
+        } else if cfg!(target_arch = "x86") {
+            Some(Target::I386Linux)
+        } else {
+            None
+        }
+    }
+}
+
+/// What the status the generated program exits with is.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExitCode {
+    /// Always 0
+    Zero,
+    /// The value of the current cell, so programs can act as predicates in shell scripts
+    Cell,
+}
+
+impl ExitCode {
+    /// Every exit code that can be picked
+    pub const ALL: [ExitCode; 2] = [ExitCode::Zero, ExitCode::Cell];
+
+    /// Gives the name used for the exit code on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExitCode::Zero => "zero",
+            ExitCode::Cell => "cell",
+        }
+    }
+
+    /// Finds the exit code with the given command line name.
+    pub fn from_name(name: &str) -> Option<ExitCode> {
+        Self::ALL.into_iter().find(|exit_code| exit_code.name() == name)
+    }
+}
+
+/// When the generated program writes a hex dump of its tape to stderr.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TapeDump {
+    /// Never
+    Off,
+    /// When the program exits
+    Exit,
+    /// When the program exits and whenever it gets `SIGUSR1`
+    Signal,
+}
+
+impl TapeDump {
+    /// Every choice that can be picked with a name, leaving out [TapeDump::Off]
+    pub const ALL: [TapeDump; 2] = [TapeDump::Exit, TapeDump::Signal];
+
+    /// Gives the name used for the choice on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TapeDump::Off => "off",
+            TapeDump::Exit => "exit",
+            TapeDump::Signal => "signal",
+        }
+    }
+
+    /// Finds the choice with the given command line name.
+    pub fn from_name(name: &str) -> Option<TapeDump> {
+        Self::ALL.into_iter().find(|tape_dump| tape_dump.name() == name)
+    }
+}
+
+/// Runtime behaviour of the generated program picked on the command line
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CodegenOptions {
+    /// What the program exits with
+    pub exit_code: ExitCode,
+    /// When the program dumps its tape
+    pub tape_dump: TapeDump,
+    /// Whether the code carries debug information mapping it to the source
+    pub debug_info: bool,
+}
+
+impl CodegenOptions {
+    /// Whether every change to a cell has to reach memory right away, as something
+    /// else reads the tape while the program runs, Eg = the signal handler or `gdb`.
+    pub fn keeps_tape_in_memory(&self) -> bool {
+        self.tape_dump == TapeDump::Signal || self.debug_info
+    }
+}
 
 /// Represents the context for generating assembly code from Brainfuck expressions.
-pub struct AsmContext<'a> {
+///
+/// The code is written to `W` in order as the syntax tree is walked, so only the
+/// block of code the [peephole] optimizer is looking at is kept in memory.
+pub struct AsmContext<'a, W: Write> {
     /// Tree containing instructions to be executed
     syntax_tree: &'a [Expression],
-    /// File to write into
-    asm_file: File,
-    /// The main function of the program
-    main_func: LinkedList<String>,
-    /// All Loops used in the program
-    loop_func: LinkedList<String>,
+    /// Where the code gets written
+    out: W,
+    /// Instructions since the last label, waiting for the peephole optimizer
+    pending: Vec<AsmInstr>,
     /// Unique id's to be given to the loops
     loop_uuid: usize,
-    /// Used to check if the program requires dealing the console
-    used_stdin: bool,
-    used_stdout: bool,
+    /// Code generator of the target
+    backend: Box<dyn Backend>,
+    /// Whether to remove redundant instructions from the generated code
+    peephole: bool,
+    /// Path of the Brain FK program when the code carries debug information about it
+    source_path: Option<&'a str>,
+    /// Lines of the Brain FK program when the code is annotated with them
+    source_lines: Option<Vec<&'a str>>,
 }
 
-impl<'a> AsmContext<'a> {
-    /// Creates a new `AsmContext` with the provided syntax tree and file path.
-    pub fn new(syntax_tree: &'a [Expression], file_path: &str) -> Self {
-        let asm_file = match File::create(file_path) {
-            Ok(file) => file,
-            Err(error) => {
-                eprintln!("{:?}", error);
-                std::process::exit(404);
-            }
-        };
-
+impl<'a, W: Write> AsmContext<'a, W> {
+    /// Creates a new `AsmContext` writing the code for the target to `out`, running the
+    /// [peephole] optimizer on it when `peephole` is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `syntax_tree` - The optimized program.
+    /// * `out` - Where the code gets written, Eg = a file, stdout or a `Vec<u8>`.
+    /// * `target` - The target to generate code for.
+    /// * `peephole` - Whether to remove redundant instructions from the code.
+    /// * `options` - Runtime behaviour of the generated program.
+    pub fn new(
+        syntax_tree: &'a [Expression],
+        out: W,
+        target: Target,
+        peephole: bool,
+        options: CodegenOptions,
+    ) -> Self {
         AsmContext {
             syntax_tree,
-            asm_file,
-            main_func: LinkedList::new(),
-            loop_func: LinkedList::new(),
+            out,
+            pending: vec![],
             loop_uuid: 1,
-            used_stdin: false,
-            used_stdout: false,
+            backend: target.backend(options),
+            peephole,
+            source_path: None,
+            source_lines: None,
         }
     }
 
-    /// Generates the assembly code from the syntax tree and writes it to the file.
-    pub fn generate_asm(&mut self) {
-        self.generate_asm_(self.syntax_tree, false, 0, 0);
-
-        // Adds init functions and other helper functions depending on what the program
-        // requires as recorded in its context
-        self.inject_helper_functions();
+    /// Annotates every part of the generated code with the line of the source it comes
+    /// from, carets marking the tokens it was made of, and names the loops after where
+    /// they start in the source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The Brain FK program the syntax tree was parsed from.
+    pub fn with_annotations(mut self, source: &'a str) -> Self {
+        self.source_lines = Some(source.lines().collect());
+        self
+    }
 
-        match self.write_to_file() {
-            Ok(_) => {
-                println!("{}", "The ASM code was successfully generated".green());
-            }
-            Err(error) => {
-                eprintln!("{}", "Could not generate the ASM file".red());
-                eprintln!("{}", error)
-            }
-        };
+    /// Marks every part of the generated code with the place in the source it comes
+    /// from and describes the program for debuggers, on targets that support it.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_path` - The path of the Brain FK program, as the debugger will open it.
+    pub fn with_debug_info(mut self, source_path: &'a str) -> Self {
+        self.source_path = Some(source_path);
+        self
     }
 
-    /// Recursively generates assembly code for the given expressions.
-    fn generate_asm_(&mut self,
-                     expression: &[Expression],
-                     in_expanded_loop: bool,
-                     parent_loop_id: usize,
-                     loop_depth: usize,
-    ) {
-        // Instructions for current level useful for loops call evaluations
-        let mut instructions: Vec<String> = vec![];
+    /// Generates the code from the syntax tree and writes the whole program out, from
+    /// the declarations to the exit.
+    pub fn generate_asm(&mut self) -> io::Result<()> {
+        // The data section comes first so what the program needs is found up front
+        let mut used_stdin = false;
+        let mut precomputed_output = vec![];
+        scan_program(self.syntax_tree, &mut used_stdin, &mut precomputed_output);
+
+        // Declarations needed by everything else
+        let code = self.backend.header();
+        self.emit(code)?;
+        if let Some(path) = self.source_path {
+            let code = self.backend.source_file(path);
+            self.emit(code)?;
+        }
+        let code = self.backend.data_section(used_stdin, &precomputed_output);
+        self.emit(code)?;
+
+        // Main entry, setting up the tape before the first line runs
+        self.mark_source(1, 0)?;
+        let code = self.backend.prologue();
+        self.emit(code)?;
+
+        self.generate_asm_(self.syntax_tree, 0)?;
+
+        // Exit the program, dumping the tape first if asked to
+        if let Some(last) = self.syntax_tree.last() {
+            let span = last.span();
+            self.mark_source(span.end_line, span.end_column - 1)?;
+        }
+        let code = self.backend.epilogue();
+        self.emit(code)?;
+
+        if let Some(path) = self.source_path {
+            let code = self.backend.debug_info(path);
+            self.emit(code)?;
+        }
 
+        self.write_pending()?;
+        self.out.flush()
+    }
+
+    /// Recursively generates and writes the assembly code for the given expressions.
+    fn generate_asm_(&mut self, expression: &[Expression], loop_depth: usize) -> io::Result<()> {
         for expr in expression.iter() {
             match expr {
-                Expression::Loop(_loop) => {
+                Expression::Loop(_loop, span) => {
                     let loop_id = self.assign_loop_uuid();
-                    // Push the loop call and return position
-                    instructions.push(asm_loop_call(loop_depth, loop_id));
-
-                    self.generate_asm_(_loop, true, loop_id, loop_depth + 1)
+                    let name = match self.source_lines {
+                        Some(_) => format!("AT_{}_{}", span.line, span.column + 1),
+                        None => format!("L{}_C{}", loop_depth, loop_id),
+                    };
+                    let end = Span::char(span.end_line, span.end_column - 1);
+                    self.annotate(Span::char(span.line, span.column))?;
+                    self.mark_source(span.line, span.column)?;
+                    let code = self.backend.loop_begin(&name);
+                    self.emit(code)?;
+                    self.generate_asm_(_loop, loop_depth + 1)?;
+                    self.annotate(end)?;
+                    self.mark_source(end.line, end.column)?;
+                    let code = self.backend.loop_end(&name);
+                    self.emit(code)?;
                 }
                 Expression::Operator(_op) => {
-                    instructions.push(format!(
-                        "\n# Token::{:?} | Count:{}",
-                        _op.type_name, _op.count
+                    self.annotate(_op.span)?;
+                    self.mark_source(_op.span.line, _op.span.column)?;
+                    let code = self.backend.comment(&format!(
+                        "Token::{:?} | Count:{} | Offset:{}",
+                        _op.type_name, _op.count, _op.offset
                     ));
-                    instructions.push(match _op.type_name {
-                        Token::MoveBack => asm_cell_ptr_decrement(_op.count),
-                        Token::MoveForward => asm_cell_ptr_increment(_op.count),
-                        Token::Add => asm_cell_increment(_op.count),
-                        Token::Sub => asm_cell_decrement(_op.count),
-                        Token::StdOut => {
-                            self.used_stdout = true;
-                            asm_print_cell()
-                        }
-                        Token::StdIn => {
-                            self.used_stdin = true;
-                            asm_read_to_cell()
-                        }
+                    self.emit(code)?;
+                    let count = _op.count as isize;
+                    let code = match _op.type_name {
+                        Token::MoveBack => self.backend.move_ptr(-count),
+                        Token::MoveForward => self.backend.move_ptr(count),
+                        Token::Add => self.backend.add(count, _op.offset),
+                        Token::Sub => self.backend.add(-count, _op.offset),
+                        Token::StdOut => self.backend.output(),
+                        Token::StdIn => self.backend.input(),
                         _ => {
                             eprintln!(
                                 "Unexpected Token: {:?} when generating assembly ",
@@ -113,78 +379,380 @@ impl<'a> AsmContext<'a> {
                             );
                             std::process::exit(123);
                         }
-                    });
+                    };
+                    self.emit(code)?;
+                }
+                Expression::Snapshot(snapshot) => {
+                    self.annotate(snapshot.span)?;
+                    self.mark_source(snapshot.span.line, snapshot.span.column)?;
+                    let code = self.backend.restore_snapshot(&snapshot.tape, snapshot.cell_ptr);
+                    self.emit(code)?;
+                    if !snapshot.output.is_empty() {
+                        let code = self.backend.print_precomputed_output(&snapshot.output);
+                        self.emit(code)?;
+                    }
                 }
             }
         }
 
-        // Prepare for loop return if applicable
-        let list = if in_expanded_loop {
-            // --------------- [ If only in loop ] ------------ \\
+        Ok(())
+    }
+
+    /// Writes the line of the source a part of the program starts on as comments, with
+    /// carets under the part, when the code is annotated.
+    /// Eg = '   3 | ++[->+<]' and '     |   ^' for the loop of the line
+    ///
+    /// # Arguments
+    ///
+    /// * `span` - The source of the part of the program.
+    fn annotate(&mut self, span: Span) -> io::Result<()> {
+        let Some(lines) = &self.source_lines else {
+            return Ok(());
+        };
+        let line = lines.get(span.line - 1).copied().unwrap_or_default();
+        let gutter = lines.len().to_string().len();
 
-            // Give the curr loop a name
-            instructions.insert(0, asm_loop_name(loop_depth - 1, parent_loop_id));
-            // End the loop
-            instructions.push(asm_loop_end(loop_depth - 1, parent_loop_id));
-            &mut self.loop_func
-        } else {
-            &mut self.main_func
+        // Tabs are kept so the carets line up with the source whatever their width
+        let mut carets: String = line
+            .chars()
+            .take(span.column)
+            .map(|char| if char == '\t' { '\t' } else { ' ' })
+            .collect();
+        let end = match span.end_line == span.line {
+            true => span.end_column,
+            false => line.chars().count(),
         };
+        carets.push_str(&"^".repeat(end.saturating_sub(span.column).max(1)));
+        if span.end_line != span.line {
+            carets.push_str(&format!(" up to line {}", span.end_line));
+        }
 
-        instructions
-            .iter()
-            .rev()
-            .for_each(|elem| list.push_front(elem.to_string()))
+        let code = [
+            self.backend.comment(&format!("{:>gutter$} | {}", span.line, line)),
+            self.backend.comment(&format!("{:>gutter$} | {}", "", carets)),
+        ]
+        .concat();
+        self.emit(code)
     }
 
-    /// Writes the generated assembly code to the file.
-    fn write_to_file(&self) -> Result<(), io::Error> {
-        let mut file = &self.asm_file;
-
-        // Write the main instructions to file
-        for instruction in &self.main_func {
-            file.write_all(instruction.as_bytes())?;
+    /// Marks the code that follows as coming from a character of the source when the
+    /// code carries debug information.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line of the character, counting from 1.
+    /// * `column` - The column of the character, counting from 0.
+    fn mark_source(&mut self, line: usize, column: usize) -> io::Result<()> {
+        match self.source_path {
+            Some(_) => {
+                let code = self.backend.source_line(line, column + 1);
+                self.emit(code)
+            }
+            None => Ok(()),
         }
+    }
 
-        // Write the loop func instructions to file
-        for functions in &self.loop_func {
-            file.write_all(functions.as_bytes())?;
+    /// Writes instructions out, holding them back while the peephole optimizer may
+    /// still remove some of them.
+    fn emit(&mut self, instructions: Vec<AsmInstr>) -> io::Result<()> {
+        if !self.peephole {
+            return instructions
+                .iter()
+                .try_for_each(|instr| self.write_instr(instr));
         }
 
-        // File read successfully
+        for instr in instructions {
+            if peephole::starts_block(&instr) {
+                self.write_pending()?;
+            }
+            self.pending.push(instr);
+        }
         Ok(())
     }
 
+    /// Optimizes the instructions held back and writes them out.
+    fn write_pending(&mut self) -> io::Result<()> {
+        peephole::optimize(mem::take(&mut self.pending))
+            .iter()
+            .try_for_each(|instr| self.write_instr(instr))
+    }
 
-    /// Adds init functions and other helper functions depending on what the program
-    //  requires as recorded in its context
-    /// Inits push must be backwards {push_front()}
-    fn inject_helper_functions(&mut self) {
-        // ------------- [ Top Part ] ------------- \\
+    /// Writes an instruction in the syntax of the target.
+    fn write_instr(&mut self, instr: &AsmInstr) -> io::Result<()> {
+        self.out
+            .write_all(printer::print(instr, self.backend.syntax()).as_bytes())
+    }
 
-        // Main entry
-        self.main_func.push_front(asm_main_init());
+    /// Assigns a unique loop UUID.
+    fn assign_loop_uuid(&mut self) -> usize {
+        self.loop_uuid += 1;
+        self.loop_uuid - 1
+    }
+}
 
-        // Input for user
-        if self.used_stdin {
-            self.main_func.push_front(asm_stdin_init());
+/// Finds what the program needs before any of it is written.
+///
+/// # Arguments
+///
+/// * `expressions` - The program or the body of a loop.
+/// * `used_stdin` - Set when the program reads input.
+/// * `precomputed_output` - Collects the output computed at compile time.
+fn scan_program(expressions: &[Expression], used_stdin: &mut bool, precomputed_output: &mut Vec<u8>) {
+    for expr in expressions {
+        match expr {
+            Expression::Loop(body, _) => scan_program(body, used_stdin, precomputed_output),
+            Expression::Operator(op) => *used_stdin |= op.type_name == Token::StdIn,
+            Expression::Snapshot(snapshot) => precomputed_output.extend_from_slice(&snapshot.output),
         }
+    }
+}
 
-        // Data section
-        self.main_func.push_front(asm_data_init());
+/// Options of the programs generated by the tests
+#[cfg(test)]
+const TEST_OPTIONS: CodegenOptions = CodegenOptions {
+    exit_code: ExitCode::Zero,
+    tape_dump: TapeDump::Off,
+    debug_info: false,
+};
 
-        // ---------- [ Top Part END ] ---------- \\
+/// Generates the code of a program without running any optimization pass over it.
+#[cfg(test)]
+fn generate(program: &str, target: Target) -> String {
+    let syntax_tree = crate::optimizer::parse(program);
+    let mut out = vec![];
+    AsmContext::new(&syntax_tree, &mut out, target, false, TEST_OPTIONS)
+        .generate_asm()
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
 
-        // Print out the array used by the program
-        // self.main_func.push_back(asm_debug_memory());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm_generator::asm::{BinaryOp, Cond, Memory, Operand, Register, Size};
+    use crate::optimizer::{parse, PassManager};
+    use crate::parser::Parser;
 
-        // Exit the program
-        self.main_func.push_back(asm_exit());
+    /// Lowers a program for x86-64 without the helper functions around it.
+    ///
+    /// # Returns
+    ///
+    /// The lines of code written for the program, without comments.
+    fn lower(program: &str) -> Vec<String> {
+        let syntax_tree = parse(program);
+        let mut out = vec![];
+        let mut context = AsmContext::new(&syntax_tree, &mut out, Target::X86_64Linux, false, TEST_OPTIONS);
+        context.generate_asm_(&syntax_tree, 0).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .map(String::from)
+            .collect()
     }
 
-    /// Assigns a unique loop UUID.
-    fn assign_loop_uuid(&mut self) -> usize {
-        self.loop_uuid += 1;
-        self.loop_uuid - 1
+    /// Prints the instructions the way the x86-64 target writes them.
+    fn lines(code: &[AsmInstr]) -> Vec<String> {
+        code.iter()
+            .flat_map(|instr| printer::print(instr, Syntax::Att).lines().map(String::from).collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// The current cell
+    fn cell() -> Memory {
+        Memory::base(Register::Rbx)
+    }
+
+    /// Loads the current cell into the cache register
+    fn load_cell() -> AsmInstr {
+        AsmInstr::binary(BinaryOp::Mov, Size::Byte, Operand::Reg(Register::Al), Operand::Mem(cell()))
+    }
+
+    /// Stores the cache register back to the current cell
+    fn store_cell() -> AsmInstr {
+        AsmInstr::binary(BinaryOp::Mov, Size::Byte, Operand::Mem(cell()), Operand::Reg(Register::Al))
+    }
+
+    /// Adds to or subtracts from the cached cell
+    fn modify_cell(op: BinaryOp, amount: i64) -> AsmInstr {
+        AsmInstr::binary(op, Size::Byte, Operand::Reg(Register::Al), Operand::Imm(amount))
+    }
+
+    /// Moves the cell pointer
+    fn move_ptr(op: BinaryOp, amount: i64) -> AsmInstr {
+        AsmInstr::binary(op, Size::Qword, Operand::Reg(Register::Rbx), Operand::Imm(amount))
+    }
+
+    /// Compares the current cell with zero
+    fn test_cell() -> AsmInstr {
+        AsmInstr::binary(BinaryOp::Cmp, Size::Byte, Operand::Mem(cell()), Operand::Imm(0))
+    }
+
+    /// Sets a 32-bit register
+    fn mov32(dst: Register, value: i64) -> AsmInstr {
+        AsmInstr::binary(BinaryOp::Mov, Size::Dword, Operand::Reg(dst), Operand::Imm(value))
+    }
+
+    #[test]
+    fn lowers_cell_changes_and_loops() {
+        assert_eq!(
+            lower("+>-<[-]"),
+            lines(&[
+                load_cell(),
+                modify_cell(BinaryOp::Add, 1),
+                store_cell(),
+                move_ptr(BinaryOp::Add, 1),
+                load_cell(),
+                modify_cell(BinaryOp::Sub, 1),
+                store_cell(),
+                move_ptr(BinaryOp::Sub, 1),
+                test_cell(),
+                AsmInstr::Jcc {
+                    cond: Cond::Equal,
+                    label: "LOOP_L0_C1_END".to_string(),
+                },
+                AsmInstr::Label("LOOP_L0_C1".to_string()),
+                load_cell(),
+                modify_cell(BinaryOp::Sub, 1),
+                store_cell(),
+                test_cell(),
+                AsmInstr::Jcc {
+                    cond: Cond::NotEqual,
+                    label: "LOOP_L0_C1".to_string(),
+                },
+                AsmInstr::Label("LOOP_L0_C1_END".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn lowers_input_and_output_to_syscalls() {
+        let cell_address = AsmInstr::binary(
+            BinaryOp::Mov,
+            Size::Qword,
+            Operand::Reg(Register::Rsi),
+            Operand::Reg(Register::Rbx),
+        );
+
+        assert_eq!(
+            lower(",."),
+            lines(&[
+                // Prompt
+                AsmInstr::Lea {
+                    dst: Register::Rsi,
+                    src: Memory::rip("input_prompt"),
+                },
+                mov32(Register::Eax, 1),
+                mov32(Register::Edi, 1),
+                mov32(Register::Edx, 19),
+                AsmInstr::Syscall,
+                // Read
+                cell_address.clone(),
+                mov32(Register::Eax, 0),
+                mov32(Register::Edi, 0),
+                mov32(Register::Edx, 1),
+                AsmInstr::Syscall,
+                // Write
+                cell_address,
+                mov32(Register::Eax, 1),
+                mov32(Register::Edi, 1),
+                mov32(Register::Edx, 1),
+                AsmInstr::Syscall,
+            ])
+        );
+    }
+
+    #[test]
+    fn marks_the_code_with_the_source_lines() {
+        let syntax_tree = parse("+>\n[-].");
+        let mut out = vec![];
+        let options = CodegenOptions {
+            debug_info: true,
+            ..TEST_OPTIONS
+        };
+        AsmContext::new(&syntax_tree, &mut out, Target::X86_64Linux, false, options)
+            .with_debug_info("two_lines.bf")
+            .generate_asm()
+            .unwrap();
+        let code = String::from_utf8(out).unwrap();
+        let marks: Vec<&str> = code
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with(".file") || line.starts_with(".loc"))
+            .collect();
+
+        // The prologue and the first `+`, the loop and its body, then the exit
+        assert_eq!(
+            marks,
+            [
+                ".file 1 \"two_lines.bf\"",
+                ".loc 1 1 1",
+                ".loc 1 1 1",
+                ".loc 1 1 2",
+                ".loc 1 2 1",
+                ".loc 1 2 2",
+                ".loc 1 2 3",
+                ".loc 1 2 4",
+                ".loc 1 2 4",
+            ]
+        );
+        assert!(code.contains(".section .debug_info"));
+        assert!(code.contains(".section .debug_abbrev"));
+    }
+
+    #[test]
+    fn leaves_the_source_lines_out_of_text_targets() {
+        let syntax_tree = parse("+>\n[-].");
+        let mut out = vec![];
+        AsmContext::new(&syntax_tree, &mut out, Target::C, false, TEST_OPTIONS)
+            .with_debug_info("two_lines.bf")
+            .generate_asm()
+            .unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(!code.contains(".file"));
+        assert!(!code.contains(".loc"));
+    }
+
+    #[test]
+    fn annotates_the_code_with_the_source() {
+        let source = "+++>\n+[-].";
+        let mut parser = Parser::new(source.to_string(), PassManager::with_level(1));
+        parser.generate_syntax_tree();
+        let syntax_tree = parser.get_ast().unwrap().clone();
+        let mut out = vec![];
+        AsmContext::new(&syntax_tree, &mut out, Target::X86_64Linux, false, TEST_OPTIONS)
+            .with_annotations(source)
+            .generate_asm()
+            .unwrap();
+        let code = String::from_utf8(out).unwrap();
+        let annotations: Vec<&str> = code
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("LOOP") || line.contains(" | ") && !line.contains("Token::"))
+            .collect();
+
+        // The carets cover every token an instruction was made of, loops being named
+        // after the line and column of their `[`
+        assert_eq!(
+            annotations,
+            [
+                "# 1 | +++>",
+                "#   | ^^^",
+                "# 1 | +++>",
+                "#   |    ^",
+                "# 2 | +[-].",
+                "#   | ^",
+                "# 2 | +[-].",
+                "#   |  ^",
+                "LOOP_AT_2_2:",
+                "# 2 | +[-].",
+                "#   |   ^",
+                "# 2 | +[-].",
+                "#   |    ^",
+                "LOOP_AT_2_2_END:",
+                "# 2 | +[-].",
+                "#   |     ^",
+            ]
+        );
     }
 }
@@ -1 +0,0 @@
-pub fn init() {}
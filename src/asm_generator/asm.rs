@@ -0,0 +1,250 @@
+pub mod peephole;
+pub mod printer;
+
+/// Flavours of assembly the instructions can be printed in
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Syntax {
+    /// AT&T syntax for GNU `as`
+    Att,
+    /// Intel syntax for NASM
+    Intel,
+}
+
+/// Registers used by the backends
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Register {
+    Al,
+    Eax,
+    Ebx,
+    Ecx,
+    Edx,
+    Esi,
+    Edi,
+    Esp,
+    Rax,
+    Rbx,
+    Rcx,
+    Rdx,
+    Rsi,
+    Rdi,
+    /// Fourth argument of 64-bit syscalls
+    R10,
+    /// Instruction pointer, only as the base of labels in 64-bit code
+    Rip,
+}
+
+impl Register {
+    /// Gives the 32-bit register holding the low half of a 64-bit one, others staying the same
+    /// Eg = 'Rdi' is 'Edi'
+    pub fn dword(self) -> Self {
+        match self {
+            Register::Rax => Register::Eax,
+            Register::Rbx => Register::Ebx,
+            Register::Rcx => Register::Ecx,
+            Register::Rdx => Register::Edx,
+            Register::Rsi => Register::Esi,
+            Register::Rdi => Register::Edi,
+            register => register,
+        }
+    }
+}
+
+/// Number of bytes an instruction works on
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Size {
+    Byte,
+    Dword,
+    Qword,
+}
+
+/// Memory at `label + disp + base + index`, every part being optional
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Memory {
+    pub label: Option<String>,
+    pub disp: isize,
+    pub base: Option<Register>,
+    pub index: Option<Register>,
+}
+
+impl Memory {
+    /// Memory at a label
+    pub fn label(label: &str) -> Self {
+        Memory {
+            label: Some(label.to_string()),
+            ..Memory::default()
+        }
+    }
+
+    /// Memory at a label addressed relative to the instruction pointer
+    pub fn rip(label: &str) -> Self {
+        Memory {
+            base: Some(Register::Rip),
+            ..Memory::label(label)
+        }
+    }
+
+    /// Memory at the address held by a register
+    pub fn base(base: Register) -> Self {
+        Memory {
+            base: Some(base),
+            ..Memory::default()
+        }
+    }
+
+    /// Moves the memory by `disp` bytes.
+    pub fn disp(self, disp: isize) -> Self {
+        Memory { disp, ..self }
+    }
+
+    /// Adds a register to the address.
+    pub fn index(self, index: Register) -> Self {
+        Memory {
+            index: Some(index),
+            ..self
+        }
+    }
+}
+
+/// Operand of an instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Reg(Register),
+    Imm(i64),
+    Mem(Memory),
+}
+
+/// Instructions taking a destination and a source
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BinaryOp {
+    Mov,
+    Add,
+    Sub,
+    Cmp,
+    Xor,
+    And,
+    /// Logical shift right by an immediate
+    Shr,
+}
+
+/// Conditions of the conditional jumps
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Cond {
+    Equal,
+    NotEqual,
+    Less,
+}
+
+/// Sections of the program
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Section {
+    Text,
+    Data,
+    /// Zero filled memory
+    Bss,
+    /// DWARF description of the program
+    DebugInfo,
+    /// DWARF layouts of the entries of [Section::DebugInfo]
+    DebugAbbrev,
+    /// DWARF line table, filled in by the assembler from [AsmInstr::Loc]
+    DebugLine,
+}
+
+/// Contents of a named piece of memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    /// Zeroed bytes, only in [Section::Bss]
+    Space(usize),
+    /// A string ending with a zero byte
+    Asciz(String),
+    Bytes(Vec<u8>),
+    /// A number of the given size worked out by the assembler, Eg = the address of a
+    /// label or the distance between two
+    Expr(Size, String),
+}
+
+/// A line of assembly.
+///
+/// Backends build these instead of text so the generated code can be inspected and
+/// rewritten before the [printer] turns it into AT&T or Intel syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmInstr {
+    Section(Section),
+    /// Makes a label visible to the linker
+    Global(String),
+    Label(String),
+    Data { label: String, data: Data },
+    Comment(String),
+    /// Names the source file the [AsmInstr::Loc] directives refer to
+    SourceFile(String),
+    /// Marks the code that follows as coming from a line and column of the source file,
+    /// both counting from 1
+    Loc { line: usize, column: usize },
+    Binary { op: BinaryOp, size: Size, dst: Operand, src: Operand },
+    Lea { dst: Register, src: Memory },
+    /// Loads a byte into a 32-bit register, zeroing the bits above it
+    Movzx { dst: Register, src: Memory },
+    Jcc { cond: Cond, label: String },
+    Jmp(String),
+    Call(String),
+    Ret,
+    Push(Register),
+    Pop(Register),
+    /// Software interrupt, `0x80` being the 32-bit Linux syscall
+    Int(u8),
+    Syscall,
+    /// Source code of targets that are not assembly, printed as is
+    Text(String),
+}
+
+impl AsmInstr {
+    /// Shorthand for an instruction taking a destination and a source.
+    pub fn binary(op: BinaryOp, size: Size, dst: Operand, src: Operand) -> Self {
+        AsmInstr::Binary { op, size, dst, src }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_memory() {
+        assert_eq!(
+            Memory::label("array").index(Register::Edx).disp(-2),
+            Memory {
+                label: Some("array".to_string()),
+                disp: -2,
+                base: None,
+                index: Some(Register::Edx),
+            }
+        );
+        assert_eq!(
+            Memory::rip("array"),
+            Memory {
+                label: Some("array".to_string()),
+                disp: 0,
+                base: Some(Register::Rip),
+                index: None,
+            }
+        );
+        assert_eq!(Memory::base(Register::Rbx).disp(1).label, None);
+    }
+
+    #[test]
+    fn builds_binary() {
+        assert_eq!(
+            AsmInstr::binary(
+                BinaryOp::Add,
+                Size::Byte,
+                Operand::Mem(Memory::base(Register::Rbx)),
+                Operand::Imm(1),
+            ),
+            AsmInstr::Binary {
+                op: BinaryOp::Add,
+                size: Size::Byte,
+                dst: Operand::Mem(Memory::base(Register::Rbx)),
+                src: Operand::Imm(1),
+            }
+        );
+    }
+}
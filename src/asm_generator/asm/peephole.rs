@@ -0,0 +1,455 @@
+use crate::asm_generator::asm::{AsmInstr, BinaryOp, Memory, Operand, Register, Size};
+
+/// What a register is known to hold at a point of the program
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    /// The address computed by a `lea`
+    Address(Memory),
+    /// The byte at the memory, in the low byte of the register
+    Byte(Memory),
+    /// The same value as another register of the same width
+    Copy(Register),
+}
+
+/// How an instruction uses the registers
+#[derive(Debug, Default)]
+struct Effects {
+    /// Registers the instruction reads
+    reads: Vec<Register>,
+    /// Registers the instruction changes
+    writes: Vec<Register>,
+    /// Registers whose whole value gets replaced, a subset of `writes`
+    replaces: Vec<Register>,
+    /// Whether the instruction may read or write any register, Eg = jumps and syscalls
+    barrier: bool,
+}
+
+/// Removes redundant instructions from the assembly of a program.
+///
+/// Repeats two rewrites until neither finds anything left to remove: a forward one
+/// dropping address computations, byte loads and register copies whose result is
+/// already in the destination, and a backward one dropping register writes that get
+/// replaced before being read. Labels, jumps and syscalls end what is known about the
+/// registers and [AsmInstr::Text] is left alone.
+///
+/// # Arguments
+///
+/// * `code` - The instructions to optimize.
+///
+/// # Returns
+///
+/// The instructions that are left.
+pub fn optimize(mut code: Vec<AsmInstr>) -> Vec<AsmInstr> {
+    loop {
+        let len = code.len();
+        code = remove_redundant_instructions(code);
+        code = remove_dead_writes(code);
+        if code.len() == len {
+            return code;
+        }
+    }
+}
+
+/// Whether the optimizer forgets everything it knows at the instruction, so the code
+/// before it can be optimized on its own.
+pub fn starts_block(instr: &AsmInstr) -> bool {
+    matches!(
+        instr,
+        AsmInstr::Label(_)
+            | AsmInstr::Section(_)
+            | AsmInstr::Global(_)
+            | AsmInstr::Data { .. }
+            | AsmInstr::Text(_)
+    )
+}
+
+/// Gives the register holding the whole value of a register
+/// Eg = '%al' -> '%rax', '%ebx' -> '%rbx'
+fn full(reg: Register) -> Register {
+    match reg {
+        Register::Al | Register::Eax | Register::Rax => Register::Rax,
+        Register::Ebx | Register::Rbx => Register::Rbx,
+        Register::Ecx | Register::Rcx => Register::Rcx,
+        Register::Edx | Register::Rdx => Register::Rdx,
+        Register::Esi | Register::Rsi => Register::Rsi,
+        Register::Edi | Register::Rdi => Register::Rdi,
+        reg => reg,
+    }
+}
+
+/// Gives the number of bytes of a register
+fn width(reg: Register) -> Size {
+    match reg {
+        Register::Al => Size::Byte,
+        Register::Eax
+        | Register::Ebx
+        | Register::Ecx
+        | Register::Edx
+        | Register::Esi
+        | Register::Edi => Size::Dword,
+        _ => Size::Qword,
+    }
+}
+
+/// Registers the address of a memory operand is made of
+fn address_registers(mem: &Memory) -> Vec<Register> {
+    [mem.base, mem.index]
+        .into_iter()
+        .flatten()
+        .filter(|&reg| reg != Register::Rip)
+        .map(full)
+        .collect()
+}
+
+/// Whether a register is part of the address of a memory operand
+fn uses(mem: &Memory, reg: Register) -> bool {
+    address_registers(mem).contains(&full(reg))
+}
+
+/// Registers an operand reads
+fn operand_reads(operand: &Operand) -> Vec<Register> {
+    match operand {
+        Operand::Reg(reg) => vec![full(*reg)],
+        Operand::Mem(mem) => address_registers(mem),
+        Operand::Imm(_) => vec![],
+    }
+}
+
+/// Gives how an instruction uses the registers.
+fn effects(instr: &AsmInstr) -> Effects {
+    match instr {
+        AsmInstr::Comment(_) | AsmInstr::Loc { .. } => Effects::default(),
+        // Writing the low 32 bits of a register replaces all of it
+        AsmInstr::Binary {
+            op: BinaryOp::Mov,
+            size: Size::Dword | Size::Qword,
+            dst: Operand::Reg(dst),
+            src,
+        } => Effects {
+            reads: operand_reads(src),
+            writes: vec![full(*dst)],
+            replaces: vec![full(*dst)],
+            barrier: false,
+        },
+        AsmInstr::Binary {
+            op: BinaryOp::Xor,
+            size: Size::Dword | Size::Qword,
+            dst: Operand::Reg(dst),
+            src: Operand::Reg(src),
+        } if dst == src => Effects {
+            reads: vec![],
+            writes: vec![full(*dst)],
+            replaces: vec![full(*dst)],
+            barrier: false,
+        },
+        AsmInstr::Binary { op, dst, src, .. } => {
+            let mut reads = operand_reads(dst);
+            reads.extend(operand_reads(src));
+            let writes = match (op, dst) {
+                (BinaryOp::Cmp, _) => vec![],
+                (_, Operand::Reg(dst)) => vec![full(*dst)],
+                _ => vec![],
+            };
+            Effects {
+                reads,
+                writes,
+                ..Effects::default()
+            }
+        }
+        AsmInstr::Lea { dst, src } => Effects {
+            reads: address_registers(src),
+            writes: vec![full(*dst)],
+            replaces: vec![full(*dst)],
+            barrier: false,
+        },
+        AsmInstr::Movzx { dst, src } => Effects {
+            reads: address_registers(src),
+            writes: vec![full(*dst)],
+            replaces: vec![full(*dst)],
+            barrier: false,
+        },
+        _ => Effects {
+            barrier: true,
+            ..Effects::default()
+        },
+    }
+}
+
+/// Knowledge about the values the registers hold
+#[derive(Debug, Default)]
+struct Known {
+    values: Vec<(Register, Value)>,
+}
+
+impl Known {
+    /// Gives what a register is known to hold
+    fn get(&self, reg: Register) -> Option<&Value> {
+        self.values
+            .iter()
+            .find(|(holder, _)| *holder == full(reg))
+            .map(|(_, value)| value)
+    }
+
+    /// Records what a register holds, unless the value depends on the register itself.
+    fn set(&mut self, reg: Register, value: Value) {
+        self.forget_register(reg);
+        let depends_on_itself = match &value {
+            Value::Address(mem) | Value::Byte(mem) => uses(mem, reg),
+            Value::Copy(other) => full(*other) == full(reg),
+        };
+        if !depends_on_itself {
+            self.values.push((full(reg), value));
+        }
+    }
+
+    /// Whether two registers of the same width are known to hold the same value
+    fn same_value(&self, a: Register, b: Register) -> bool {
+        full(a) == full(b)
+            || self.get(a) == Some(&Value::Copy(b))
+            || self.get(b) == Some(&Value::Copy(a))
+    }
+
+    /// Forgets the value of a register and every value computed from it.
+    fn forget_register(&mut self, reg: Register) {
+        let reg = full(reg);
+        self.values.retain(|(holder, value)| {
+            *holder != reg
+                && match value {
+                    Value::Address(mem) | Value::Byte(mem) => !uses(mem, reg),
+                    Value::Copy(other) => full(*other) != reg,
+                }
+        });
+    }
+
+    /// Forgets every byte loaded from memory, as memory got written.
+    fn forget_memory(&mut self) {
+        self.values
+            .retain(|(_, value)| !matches!(value, Value::Byte(_)));
+    }
+}
+
+/// Removes instructions that put a value into a register that already holds it.
+fn remove_redundant_instructions(code: Vec<AsmInstr>) -> Vec<AsmInstr> {
+    let mut known = Known::default();
+    let mut optimized = Vec::with_capacity(code.len());
+
+    for instr in code {
+        match &instr {
+            AsmInstr::Lea { dst, src } => {
+                if known.get(*dst) == Some(&Value::Address(src.clone())) {
+                    continue;
+                }
+                known.set(*dst, Value::Address(src.clone()));
+            }
+            AsmInstr::Binary {
+                op: BinaryOp::Mov,
+                size: Size::Byte,
+                dst: Operand::Reg(dst),
+                src: Operand::Mem(src),
+            } => {
+                if known.get(*dst) == Some(&Value::Byte(src.clone())) {
+                    continue;
+                }
+                known.set(*dst, Value::Byte(src.clone()));
+            }
+            AsmInstr::Binary {
+                op: BinaryOp::Mov,
+                size: Size::Dword | Size::Qword,
+                dst: Operand::Reg(dst),
+                src: Operand::Reg(src),
+            } => {
+                if width(*dst) != width(*src) {
+                    known.forget_register(*dst);
+                } else if known.same_value(*dst, *src) {
+                    continue;
+                } else {
+                    // A 32-bit copy zeroes the top of a 64-bit register, which then only
+                    // matches the low half of the source
+                    known.set(*dst, Value::Copy(*src));
+                }
+            }
+            // Once a byte is stored the register holds what is in memory
+            AsmInstr::Binary {
+                op: BinaryOp::Mov,
+                size: Size::Byte,
+                dst: Operand::Mem(mem),
+                src: Operand::Reg(src),
+            } => {
+                known.forget_memory();
+                known.set(*src, Value::Byte(mem.clone()));
+            }
+            // Syscalls give back a value in the accumulator and may write memory, the
+            // 64-bit one also clobbering the counter
+            AsmInstr::Int(_) | AsmInstr::Syscall => {
+                known.forget_memory();
+                known.forget_register(Register::Eax);
+                known.forget_register(Register::Ecx);
+            }
+            // A conditional jump that is not taken changes nothing
+            AsmInstr::Jcc { .. } | AsmInstr::Comment(_) | AsmInstr::Loc { .. } => {}
+            AsmInstr::Binary { .. } | AsmInstr::Movzx { .. } => {
+                if matches!(instr, AsmInstr::Binary { dst: Operand::Mem(_), .. }) {
+                    known.forget_memory();
+                }
+                effects(&instr)
+                    .writes
+                    .into_iter()
+                    .for_each(|reg| known.forget_register(reg));
+            }
+            // Labels can be reached from anywhere
+            _ => known.values.clear(),
+        }
+        optimized.push(instr);
+    }
+
+    optimized
+}
+
+/// Whether the register written by an instruction gets replaced before anything reads it.
+fn is_dead_write(reg: Register, following: &[AsmInstr]) -> bool {
+    for instr in following {
+        let effects = effects(instr);
+        if effects.barrier || effects.reads.contains(&reg) {
+            return false;
+        }
+        if effects.replaces.contains(&reg) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Removes the register writes that get replaced before anything reads them.
+fn remove_dead_writes(code: Vec<AsmInstr>) -> Vec<AsmInstr> {
+    let is_dead = |idx: usize| match &code[idx] {
+        AsmInstr::Binary {
+            op: BinaryOp::Mov,
+            size: Size::Dword | Size::Qword,
+            dst: Operand::Reg(dst),
+            ..
+        }
+        | AsmInstr::Lea { dst, .. } => is_dead_write(full(*dst), &code[idx + 1..]),
+        _ => false,
+    };
+    let dead: Vec<bool> = (0..code.len()).map(is_dead).collect();
+
+    code.into_iter()
+        .zip(dead)
+        .filter(|(_, dead)| !dead)
+        .map(|(instr, _)| instr)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shorthand for a move between registers
+    fn mov(size: Size, dst: Register, src: Register) -> AsmInstr {
+        AsmInstr::binary(BinaryOp::Mov, size, Operand::Reg(dst), Operand::Reg(src))
+    }
+
+    /// Shorthand for a move of an immediate into a register
+    fn mov_imm(dst: Register, value: i64) -> AsmInstr {
+        AsmInstr::binary(BinaryOp::Mov, Size::Dword, Operand::Reg(dst), Operand::Imm(value))
+    }
+
+    /// Shorthand for storing the low byte of the accumulator in the current cell
+    fn store_cell() -> AsmInstr {
+        AsmInstr::binary(
+            BinaryOp::Mov,
+            Size::Byte,
+            Operand::Mem(Memory::label("array").index(Register::Edx)),
+            Operand::Reg(Register::Al),
+        )
+    }
+
+    /// Shorthand for loading the current cell into the low byte of the accumulator
+    fn load_cell() -> AsmInstr {
+        AsmInstr::binary(
+            BinaryOp::Mov,
+            Size::Byte,
+            Operand::Reg(Register::Al),
+            Operand::Mem(Memory::label("array").index(Register::Edx)),
+        )
+    }
+
+    #[test]
+    fn removes_redundant_loads() {
+        let lea = AsmInstr::Lea {
+            dst: Register::Ecx,
+            src: Memory::label("array").index(Register::Edx),
+        };
+        let code = vec![lea.clone(), store_cell(), lea.clone(), load_cell()];
+        assert_eq!(remove_redundant_instructions(code), vec![lea, store_cell()]);
+    }
+
+    #[test]
+    fn keeps_loads_after_labels() {
+        let code = vec![
+            store_cell(),
+            AsmInstr::Label("LOOP_L0_C1".to_string()),
+            load_cell(),
+        ];
+        assert_eq!(remove_redundant_instructions(code.clone()), code);
+    }
+
+    #[test]
+    fn removes_copies_of_the_same_width() {
+        let copy = mov(Size::Qword, Register::Rsi, Register::Rbx);
+        let code = vec![copy.clone(), copy.clone()];
+        assert_eq!(remove_redundant_instructions(code), vec![copy]);
+    }
+
+    #[test]
+    fn keeps_copies_of_another_width() {
+        // The 32-bit copy leaves the top of the 64-bit one to be copied
+        let code = vec![
+            mov(Size::Dword, Register::Esi, Register::Ebx),
+            mov(Size::Qword, Register::Rsi, Register::Rbx),
+        ];
+        assert_eq!(remove_redundant_instructions(code.clone()), code);
+    }
+
+    #[test]
+    fn removes_dead_writes() {
+        let code = vec![
+            mov_imm(Register::Eax, 3),
+            mov_imm(Register::Eax, 4),
+            AsmInstr::Int(0x80),
+        ];
+        assert_eq!(
+            remove_dead_writes(code),
+            vec![mov_imm(Register::Eax, 4), AsmInstr::Int(0x80)]
+        );
+    }
+
+    #[test]
+    fn keeps_writes_read_across_labels_and_syscalls() {
+        let across_label = vec![
+            mov_imm(Register::Eax, 3),
+            AsmInstr::Label("LOOP_L0_C1".to_string()),
+            mov_imm(Register::Eax, 4),
+        ];
+        assert_eq!(remove_dead_writes(across_label.clone()), across_label);
+
+        let across_syscall = vec![
+            mov_imm(Register::Eax, 3),
+            AsmInstr::Syscall,
+            mov_imm(Register::Eax, 4),
+        ];
+        assert_eq!(remove_dead_writes(across_syscall.clone()), across_syscall);
+    }
+
+    #[test]
+    fn keeps_stores_before_labels_and_io() {
+        for next in [
+            AsmInstr::Label("LOOP_L0_C1".to_string()),
+            AsmInstr::Syscall,
+            AsmInstr::Int(0x80),
+        ] {
+            let code = vec![store_cell(), next, load_cell()];
+            assert_eq!(optimize(code.clone()), code);
+        }
+    }
+}
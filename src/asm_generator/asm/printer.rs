@@ -0,0 +1,412 @@
+use crate::asm_generator::asm::{
+    AsmInstr, BinaryOp, Cond, Data, Memory, Operand, Register, Section, Size, Syntax,
+};
+
+/// Number of bytes per line of [Data::Bytes]
+const BYTES_PER_LINE: usize = 16;
+
+/// Prints an instruction in the given syntax, with the line break it needs.
+///
+/// # Arguments
+///
+/// * `instr` - The instruction to print.
+/// * `syntax` - The flavour of assembly to print it in.
+///
+/// # Returns
+///
+/// The text of the instruction.
+pub fn print(instr: &AsmInstr, syntax: Syntax) -> String {
+    match instr {
+        AsmInstr::Section(section) => format!("\n{}\n", section_name(*section, syntax)),
+        AsmInstr::Global(label) => match syntax {
+            Syntax::Att => format!(".globl {}\n", label),
+            Syntax::Intel => format!("global {}\n", label),
+        },
+        AsmInstr::Label(label) => format!("\n{}:\n", label),
+        AsmInstr::Data { label, data } => print_data(label, data, syntax),
+        AsmInstr::Comment(text) => match syntax {
+            Syntax::Att => format!("    # {}\n", text),
+            Syntax::Intel => format!("    ; {}\n", text),
+        },
+        // NASM has no DWARF line directives, so they are only kept as comments
+        AsmInstr::SourceFile(path) => match syntax {
+            Syntax::Att => format!(".file 1 \"{}\"\n", escape(path)),
+            Syntax::Intel => format!("; file {}\n", path),
+        },
+        AsmInstr::Loc { line, column } => match syntax {
+            Syntax::Att => format!("    .loc 1 {} {}\n", line, column),
+            Syntax::Intel => format!("    ; line {} column {}\n", line, column),
+        },
+        AsmInstr::Binary { op, size, dst, src } => {
+            let mnemonic = match op {
+                BinaryOp::Mov => "mov",
+                BinaryOp::Add => "add",
+                BinaryOp::Sub => "sub",
+                BinaryOp::Cmp => "cmp",
+                BinaryOp::Xor => "xor",
+                BinaryOp::And => "and",
+                BinaryOp::Shr => "shr",
+            };
+            print_instr(mnemonic, *size, &[dst, src], syntax)
+        }
+        AsmInstr::Lea { dst, src } => match syntax {
+            Syntax::Att => line("lea", &format!("{}, {}", memory(src, syntax), register(*dst, syntax))),
+            Syntax::Intel => line("lea", &format!("{}, {}", register(*dst, syntax), memory(src, syntax))),
+        },
+        AsmInstr::Movzx { dst, src } => match syntax {
+            Syntax::Att => line("movzbl", &format!("{}, {}", memory(src, syntax), register(*dst, syntax))),
+            Syntax::Intel => line("movzx", &format!("{}, byte {}", register(*dst, syntax), memory(src, syntax))),
+        },
+        AsmInstr::Jcc { cond, label } => {
+            let mnemonic = match cond {
+                Cond::Equal => "je",
+                Cond::NotEqual => "jne",
+                Cond::Less => "jl",
+            };
+            line(mnemonic, label)
+        }
+        AsmInstr::Jmp(label) => line("jmp", label),
+        AsmInstr::Call(label) => line("call", label),
+        AsmInstr::Ret => line("ret", ""),
+        AsmInstr::Push(reg) => line("push", &register(*reg, syntax)),
+        AsmInstr::Pop(reg) => line("pop", &register(*reg, syntax)),
+        AsmInstr::Int(vector) => match syntax {
+            Syntax::Att => line("int", &format!("${:#x}", vector)),
+            Syntax::Intel => line("int", &format!("{:#x}", vector)),
+        },
+        AsmInstr::Syscall => line("syscall", ""),
+        AsmInstr::Text(text) => text.clone(),
+    }
+}
+
+/// Prints an instruction with its mnemonic padded so the operands line up
+fn line(mnemonic: &str, operands: &str) -> String {
+    format!("    {:<8}{}", mnemonic, operands).trim_end().to_string() + "\n"
+}
+
+/// Prints an instruction whose operands are given destination first.
+///
+/// AT&T puts the size on the mnemonic and the operands backwards while Intel only
+/// names the size when no register gives it away.
+fn print_instr(mnemonic: &str, size: Size, operands: &[&Operand], syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Att => {
+            let suffix = match size {
+                Size::Byte => "b",
+                Size::Dword => "l",
+                Size::Qword => "q",
+            };
+            let operands: Vec<String> =
+                operands.iter().rev().map(|op| operand(op, syntax)).collect();
+            line(&format!("{}{}", mnemonic, suffix), &operands.join(", "))
+        }
+        Syntax::Intel => {
+            let needs_size = !operands.iter().any(|op| matches!(op, Operand::Reg(_)));
+            let operands: Vec<String> = operands
+                .iter()
+                .map(|op| match op {
+                    Operand::Mem(_) if needs_size => {
+                        format!("{} {}", size_name(size), operand(op, syntax))
+                    }
+                    _ => operand(op, syntax),
+                })
+                .collect();
+            line(mnemonic, &operands.join(", "))
+        }
+    }
+}
+
+/// Prints a piece of memory, named unless the label is empty
+fn print_data(label: &str, data: &Data, syntax: Syntax) -> String {
+    let name = match label.is_empty() {
+        true => String::from("    "),
+        false => format!("{}: ", label),
+    };
+    match (data, syntax) {
+        (Data::Space(len), Syntax::Att) => format!("{}.space {}\n", name, len),
+        (Data::Space(len), Syntax::Intel) => format!("{}resb {}\n", name, len),
+        (Data::Expr(size, expr), _) => {
+            format!("{}{} {}\n", name, int_directive(*size, syntax), expr)
+        }
+        (Data::Asciz(text), Syntax::Att) => format!("{}.asciz \"{}\"\n", name, text),
+        (Data::Asciz(text), Syntax::Intel) => format!("{}db \"{}\", 0\n", name, text),
+        (Data::Bytes(bytes), _) => {
+            let lines = bytes
+                .chunks(BYTES_PER_LINE)
+                .map(|chunk| {
+                    let chunk: Vec<String> = chunk.iter().map(|byte| byte.to_string()).collect();
+                    format!(
+                        "    {} {}\n",
+                        int_directive(Size::Byte, syntax),
+                        chunk.join(", ")
+                    )
+                })
+                .collect::<String>();
+            match label.is_empty() {
+                true => lines,
+                false => format!("{}:\n{}", label, lines),
+            }
+        }
+    }
+}
+
+/// Gives the directive defining a number of the given size
+fn int_directive(size: Size, syntax: Syntax) -> &'static str {
+    match (size, syntax) {
+        (Size::Byte, Syntax::Att) => ".byte",
+        (Size::Dword, Syntax::Att) => ".long",
+        (Size::Qword, Syntax::Att) => ".quad",
+        (Size::Byte, Syntax::Intel) => "db",
+        (Size::Dword, Syntax::Intel) => "dd",
+        (Size::Qword, Syntax::Intel) => "dq",
+    }
+}
+
+/// Gives the name of a section
+fn section_name(section: Section, syntax: Syntax) -> &'static str {
+    match (section, syntax) {
+        (Section::Text, Syntax::Att) => ".text",
+        (Section::Data, Syntax::Att) => ".data",
+        (Section::Bss, Syntax::Att) => ".bss",
+        (Section::DebugInfo, Syntax::Att) => ".section .debug_info,\"\",@progbits",
+        (Section::DebugAbbrev, Syntax::Att) => ".section .debug_abbrev,\"\",@progbits",
+        (Section::DebugLine, Syntax::Att) => ".section .debug_line,\"\",@progbits",
+        (Section::Text, Syntax::Intel) => "section .text",
+        (Section::Data, Syntax::Intel) => "section .data",
+        (Section::Bss, Syntax::Intel) => "section .bss",
+        (Section::DebugInfo, Syntax::Intel) => "section .debug_info",
+        (Section::DebugAbbrev, Syntax::Intel) => "section .debug_abbrev",
+        (Section::DebugLine, Syntax::Intel) => "section .debug_line",
+    }
+}
+
+/// Escapes the quotes and backslashes of a string put between quotes
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Gives the Intel keyword of a size
+fn size_name(size: Size) -> &'static str {
+    match size {
+        Size::Byte => "byte",
+        Size::Dword => "dword",
+        Size::Qword => "qword",
+    }
+}
+
+/// Prints an operand
+fn operand(operand: &Operand, syntax: Syntax) -> String {
+    match (operand, syntax) {
+        (Operand::Reg(reg), _) => register(*reg, syntax),
+        (Operand::Imm(value), Syntax::Att) => format!("${}", value),
+        (Operand::Imm(value), Syntax::Intel) => value.to_string(),
+        (Operand::Mem(mem), _) => memory(mem, syntax),
+    }
+}
+
+/// Prints a register
+fn register(reg: Register, syntax: Syntax) -> String {
+    let name = match reg {
+        Register::Al => "al",
+        Register::Eax => "eax",
+        Register::Ebx => "ebx",
+        Register::Ecx => "ecx",
+        Register::Edx => "edx",
+        Register::Esi => "esi",
+        Register::Edi => "edi",
+        Register::Esp => "esp",
+        Register::Rax => "rax",
+        Register::Rbx => "rbx",
+        Register::Rcx => "rcx",
+        Register::Rdx => "rdx",
+        Register::Rsi => "rsi",
+        Register::Rdi => "rdi",
+        Register::R10 => "r10",
+        Register::Rip => "rip",
+    };
+    match syntax {
+        Syntax::Att => format!("%{}", name),
+        Syntax::Intel => name.to_string(),
+    }
+}
+
+/// Prints a memory operand
+/// Eg = 'array+2(%edx)', '-1(%rbx)' or '[array+edx+2]', '[rbx-1]'
+fn memory(mem: &Memory, syntax: Syntax) -> String {
+    let disp = match (&mem.label, mem.disp) {
+        (_, 0) => String::new(),
+        (Some(_), disp) => format!("{:+}", disp),
+        (None, disp) => disp.to_string(),
+    };
+
+    match syntax {
+        Syntax::Att => {
+            let registers: Vec<String> = [mem.base, mem.index]
+                .into_iter()
+                .flatten()
+                .map(|reg| register(reg, syntax))
+                .collect();
+            let registers = match registers.is_empty() {
+                true => String::new(),
+                false => format!("({})", registers.join(",")),
+            };
+            format!("{}{}{}", mem.label.as_deref().unwrap_or(""), disp, registers)
+        }
+        Syntax::Intel if mem.base == Some(Register::Rip) => {
+            format!("[rel {}{}]", mem.label.as_deref().unwrap_or(""), disp)
+        }
+        Syntax::Intel => {
+            let parts: Vec<String> = mem
+                .label
+                .iter()
+                .cloned()
+                .chain(
+                    [mem.base, mem.index]
+                        .into_iter()
+                        .flatten()
+                        .map(|reg| register(reg, syntax)),
+                )
+                .collect();
+            match (parts.is_empty(), mem.disp) {
+                (true, disp) => format!("[{}]", disp),
+                (false, 0) => format!("[{}]", parts.join("+")),
+                (false, disp) => format!("[{}{:+}]", parts.join("+"), disp),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prints an instruction in both syntaxes
+    fn print_both(instr: AsmInstr) -> (String, String) {
+        (print(&instr, Syntax::Att), print(&instr, Syntax::Intel))
+    }
+
+    #[test]
+    fn prints_mov() {
+        let load = AsmInstr::binary(
+            BinaryOp::Mov,
+            Size::Dword,
+            Operand::Reg(Register::Ecx),
+            Operand::Mem(Memory::label("array").index(Register::Edx).disp(2)),
+        );
+        assert_eq!(
+            print_both(load),
+            (
+                "    movl    array+2(%edx), %ecx\n".to_string(),
+                "    mov     ecx, [array+edx+2]\n".to_string()
+            )
+        );
+
+        // Without a register Intel has to be told the size
+        let store = AsmInstr::binary(
+            BinaryOp::Mov,
+            Size::Byte,
+            Operand::Mem(Memory::rip("array")),
+            Operand::Imm(0),
+        );
+        assert_eq!(
+            print_both(store),
+            (
+                "    movb    $0, array(%rip)\n".to_string(),
+                "    mov     byte [rel array], 0\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn prints_add() {
+        let add = AsmInstr::binary(
+            BinaryOp::Add,
+            Size::Byte,
+            Operand::Mem(Memory::base(Register::Rbx).disp(-1)),
+            Operand::Imm(3),
+        );
+        assert_eq!(
+            print_both(add),
+            (
+                "    addb    $3, -1(%rbx)\n".to_string(),
+                "    add     byte [rbx-1], 3\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn prints_cmp() {
+        let cmp = AsmInstr::binary(
+            BinaryOp::Cmp,
+            Size::Byte,
+            Operand::Reg(Register::Al),
+            Operand::Imm(0),
+        );
+        assert_eq!(
+            print_both(cmp),
+            (
+                "    cmpb    $0, %al\n".to_string(),
+                "    cmp     al, 0\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn prints_jumps() {
+        let jcc = AsmInstr::Jcc {
+            cond: Cond::NotEqual,
+            label: "LOOP_L0_C1".to_string(),
+        };
+        assert_eq!(
+            print_both(jcc),
+            (
+                "    jne     LOOP_L0_C1\n".to_string(),
+                "    jne     LOOP_L0_C1\n".to_string()
+            )
+        );
+        assert_eq!(
+            print_both(AsmInstr::Jmp("DUMP_TAPE_NEXT".to_string())),
+            (
+                "    jmp     DUMP_TAPE_NEXT\n".to_string(),
+                "    jmp     DUMP_TAPE_NEXT\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn prints_calls_and_the_stack() {
+        assert_eq!(
+            print_both(AsmInstr::Call("DUMP_TAPE".to_string())),
+            (
+                "    call    DUMP_TAPE\n".to_string(),
+                "    call    DUMP_TAPE\n".to_string()
+            )
+        );
+        assert_eq!(
+            print_both(AsmInstr::Ret),
+            ("    ret\n".to_string(), "    ret\n".to_string())
+        );
+        assert_eq!(
+            print_both(AsmInstr::Push(Register::Rbx)),
+            ("    push    %rbx\n".to_string(), "    push    rbx\n".to_string())
+        );
+        assert_eq!(
+            print_both(AsmInstr::Pop(Register::Rbx)),
+            ("    pop     %rbx\n".to_string(), "    pop     rbx\n".to_string())
+        );
+    }
+
+    #[test]
+    fn splits_long_bytes() {
+        let data = AsmInstr::Data {
+            label: "output".to_string(),
+            data: Data::Bytes((0..17).collect()),
+        };
+        let (att, intel) = print_both(data);
+        assert_eq!(
+            att,
+            "output:\n    .byte 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15\n    .byte 16\n"
+        );
+        assert_eq!(intel, att.replace(".byte", "db"));
+    }
+}
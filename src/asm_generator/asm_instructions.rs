@@ -1,58 +1,200 @@
 // ------------------- [ REGISTERS ] ------------------- \\
-// NOTE IF CHANGES TO THE REGISTERS 'e[a-d]x' ARE MADE AN EVALUATION OF ALL SYS CALLS ARE TO BE CHECKED
+// NOTE THE SYSCALLS TAKE THEIR ARGUMENTS IN THE REGISTERS OF THE [Abi] ('%ebx' '%ecx' '%edx' '%esi'
+// WITH 'int $0x80', '%rdi' '%rsi' '%rdx' '%r10' WITH 'syscall' WHICH ALSO CLOBBERS '%rcx' & '%r11')
+// SO THE CACHED CELL IS FLUSHED BEFORE THEM AND THE CELL PTR STAYS OUT OF THEM OR IS SAVED
 
-static REG_TEMP: &str = "%eax";
-static REG_TEMP_NOT_PUBLIC: &str = "%ebx";
-static REG_CELL_LEN: &str = "%ecx";
-static REG_CELL_PTR: &str = "%edx";
-static REG_TEMP_SAVE_LEN: &str = "%esi";
-static REG_TEMP_SAVE_PTR: &str = "%edi";
+use crate::asm_generator::asm::{
+    AsmInstr, BinaryOp, Cond, Data, Memory, Operand, Register, Section, Size, Syntax,
+};
+use crate::asm_generator::backend::Backend;
+use crate::asm_generator::cell_cache::CellCache;
+use crate::asm_generator::debug_info::debug_sections;
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump};
+use crate::grammar::{PROMPT, TAPE_LEN};
+use crate::tape::{
+    CELLS_PER_LINE, CELL_DIGITS, DUMP_HEADER, HEX_DIGITS, INDEX_DIGITS, MAX_DUMP_LEN,
+};
+
+const REG_TEMP_BYTE: Register = Register::Al;
 
 // ------------------- [ REGISTERS END ] ------------------- \\
 
 static CELL_MEMORY: &str = "array";
-static CELL_MEMORY_LEN: &str = "array_len";
 static PROMPT_MEMORY: &str = "input_prompt";
-static PROMPT: &str = "Enter a character: ";
+static PRECOMPUTED_OUTPUT_MEMORY: &str = "precomputed_output";
 static LOOP: &str = "LOOP";
+static DUMP_HEADER_MEMORY: &str = "dump_header";
+static DUMP_HEX_MEMORY: &str = "dump_hex";
+static DUMP_BUFFER_MEMORY: &str = "dump_buffer";
+static DUMP_SIGACTION_MEMORY: &str = "dump_sigaction";
+static DUMP_TAPE: &str = "DUMP_TAPE";
+static DUMP_SIGNAL_HANDLER: &str = "DUMP_SIGNAL_HANDLER";
+static DUMP_SIGNAL_RESTORER: &str = "DUMP_SIGNAL_RESTORER";
+
+/// Signal dumping the tape
+pub const SIGUSR1: i64 = 10;
+/// Flags of the signal handler, `SA_SIGINFO | SA_RESTORER | SA_RESTART`, so I/O carries on after a dump
+pub const SIGACTION_FLAGS: i64 = 0x1400_0004;
+
+/// Numbers of the syscalls of a target
+pub struct SysCalls {
+    pub read: i64,
+    pub write: i64,
+    pub exit: i64,
+    pub rt_sigaction: i64,
+    pub rt_sigreturn: i64,
+}
 
-enum SysCall {
-    Write = 4,
-    Read = 3,
+/// How a target calls the kernel and lays out the structures of the signal handler
+pub struct Abi {
+    /// Size of the pointers
+    word: Size,
+    /// Register holding the address of the current cell
+    cell_ptr: Register,
+    /// Registers of the first four arguments of a syscall, the number going in 'eax'
+    sys_call_args: [Register; 4],
+    /// Instruction making a syscall
+    sys_call: AsmInstr,
+    /// Numbers of the syscalls
+    pub sys_calls: SysCalls,
+    /// Whether labels are addressed relative to the instruction pointer
+    rip_relative: bool,
+    /// Register keeping the cell_ptr while a syscall takes its register, with its offset
+    /// in the `ucontext_t` handed to signal handlers
+    saved_cell_ptr: Option<(Register, isize)>,
+    /// Where signal handlers find the address of their `ucontext_t`
+    ucontext: Operand,
+    /// Offset of the saved cell_ptr in the `ucontext_t`
+    pub ucontext_cell_ptr: isize,
+    /// Size of the `struct sigaction` of the kernel
+    pub sigaction_len: usize,
+    /// Offsets of the flags and of the restorer in the `struct sigaction`, the handler
+    /// coming first
+    pub sigaction_flags_at: isize,
+    pub sigaction_restorer_at: isize,
 }
 
-enum FileDescriptor {
+/// 32-bit Linux, calling the kernel with `int $0x80`
+pub static I386_LINUX: Abi = Abi {
+    word: Size::Dword,
+    cell_ptr: Register::Edx,
+    sys_call_args: [Register::Ebx, Register::Ecx, Register::Edx, Register::Esi],
+    sys_call: AsmInstr::Int(0x80),
+    sys_calls: SysCalls {
+        read: 3,
+        write: 4,
+        exit: 1,
+        rt_sigaction: 174,
+        rt_sigreturn: 173,
+    },
+    rip_relative: false,
+    saved_cell_ptr: Some((Register::Edi, 36)),
+    ucontext: Operand::Mem(Memory {
+        label: None,
+        disp: 12,
+        base: Some(Register::Esp),
+        index: None,
+    }),
+    ucontext_cell_ptr: 56,
+    sigaction_len: 20,
+    sigaction_flags_at: 4,
+    sigaction_restorer_at: 8,
+};
+
+/// 64-bit Linux, calling the kernel with `syscall`
+pub static X86_64_LINUX: Abi = Abi {
+    word: Size::Qword,
+    cell_ptr: Register::Rbx,
+    sys_call_args: [Register::Rdi, Register::Rsi, Register::Rdx, Register::R10],
+    sys_call: AsmInstr::Syscall,
+    sys_calls: SysCalls {
+        read: 0,
+        write: 1,
+        exit: 60,
+        rt_sigaction: 13,
+        rt_sigreturn: 15,
+    },
+    rip_relative: true,
+    saved_cell_ptr: None,
+    ucontext: Operand::Reg(Register::Rdx),
+    ucontext_cell_ptr: 128,
+    sigaction_len: 32,
+    sigaction_flags_at: 8,
+    sigaction_restorer_at: 16,
+};
+
+impl Abi {
+    /// Gives the register of the word size, named by its 64-bit name
+    /// Eg = 'Rsi' is 'Esi' on 32-bit targets
+    fn reg(&self, reg: Register) -> Register {
+        match self.word {
+            Size::Qword => reg,
+            _ => reg.dword(),
+        }
+    }
+
+    /// Gives the memory at a label, relative to the instruction pointer when the target
+    /// needs it
+    fn memory(&self, label: &str) -> Memory {
+        if self.rip_relative {
+            Memory::rip(label)
+        } else {
+            Memory::label(label)
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+pub enum FileDescriptor {
     StdOut = 1,
     StdIn = 0,
+    StdErr = 2,
 }
 
-// ---------------------- [ REG ] ---------------------- \\
+/// Memory a syscall reads from or writes into
+enum SysCallBuffer {
+    /// The cell the cell_ptr points at
+    Cell,
+    /// The prompt for the user
+    Prompt,
+    /// The output computed at compile time with its length
+    PrecomputedOutput(usize),
+    /// The first line of the tape dump
+    DumpHeader,
+}
 
-/// Resets the [REG_TEMP]
-fn asm_reset_temp() -> String {
-    format!("xor    {}, {}", REG_TEMP, REG_TEMP)
+/// Template for an instruction on a register and an immediate
+fn asm_reg_imm(op: BinaryOp, size: Size, reg: Register, value: i64) -> AsmInstr {
+    AsmInstr::binary(op, size, Operand::Reg(reg), Operand::Imm(value))
 }
 
-/// Save [REG_CELL_LEN] & [REG_CELL_PTR]
-fn save_reg() -> String {
-    format!(
-        r#"# Save Reg for cell_memory_(len & ptr)
-    mov     {}, {}
-    mov     {}, {}"#,
-        REG_CELL_LEN, REG_TEMP_SAVE_LEN,
-        REG_CELL_PTR, REG_TEMP_SAVE_PTR
-    )
+/// Template for an instruction on two registers
+fn asm_reg_reg(op: BinaryOp, size: Size, dst: Register, src: Register) -> AsmInstr {
+    AsmInstr::binary(op, size, Operand::Reg(dst), Operand::Reg(src))
 }
 
-/// Restore [REG_CELL_LEN] & [REG_CELL_PTR]
-fn restore_reg() -> String {
-    format!(
-        r#"# Restore Reg for cell_(len & ptr)
-    mov     {}, {}
-    mov     {}, {}"#,
-        REG_TEMP_SAVE_LEN, REG_CELL_LEN,
-        REG_TEMP_SAVE_PTR, REG_CELL_PTR
-    )
+// ---------------------- [ REG ] ---------------------- \\
+
+/// Keeps the cell_ptr in its saved register when a syscall takes its register
+fn save_reg(abi: &Abi) -> Vec<AsmInstr> {
+    match abi.saved_cell_ptr {
+        Some((saved, _)) => vec![
+            AsmInstr::Comment(String::from("Save Reg for cell_ptr")),
+            asm_reg_reg(BinaryOp::Mov, abi.word, saved, abi.cell_ptr),
+        ],
+        None => vec![],
+    }
+}
+
+/// Gets the cell_ptr back from its saved register
+fn restore_reg(abi: &Abi) -> Vec<AsmInstr> {
+    match abi.saved_cell_ptr {
+        Some((saved, _)) => vec![
+            AsmInstr::Comment(String::from("Restore Reg for cell_ptr")),
+            asm_reg_reg(BinaryOp::Mov, abi.word, abi.cell_ptr, saved),
+        ],
+        None => vec![],
+    }
 }
 
 // ------------------ [ END REG ] ------------------ \\
@@ -60,66 +202,110 @@ fn restore_reg() -> String {
 // --------------------- [ INIT ] --------------------- \\
 // --------------- [ MUST USE ] --------------- \\
 
-/// Represents the start of the data section of the assembly file
+/// Represents the start of the data sections of the assembly file
 #[must_use]
-pub fn asm_data_init() -> String {
-    format!(
-        r#"
-.data
-    {}: .long 256             # Define a variable to store the length of the array
-    {}: .space 256                # Define an array of characters with length 256
-    "#,
-        CELL_MEMORY_LEN, CELL_MEMORY
-    )
+pub fn asm_data_init() -> Vec<AsmInstr> {
+    vec![
+        AsmInstr::Section(Section::Bss),
+        AsmInstr::Data {
+            label: CELL_MEMORY.to_string(),
+            data: Data::Space(TAPE_LEN),
+        },
+        AsmInstr::Section(Section::Data),
+    ]
 }
 
 /// Represents the entry point of the program
 #[must_use]
-pub fn asm_main_init() -> String {
-    format!(
-        r#"
-.text
-.globl _start
-_start:
-    movl    {}, {}         # Load the length of the array into ecx
-    xor     {}, {}              # Initialize a counter register to 0
-
-fill_array:
-    movb    $0, {}({})         # Store 0 to the array at index CELL_PTR_REG
-    inc     {}                    # Increment the counter
-    cmp     {}, {}              # Compare the counter to the length of the array
-    jl      fill_array              # Jump to fill_array if counter is less than the length
-    xor     {}, {}              # Reset the cell_ptr to 0
-"#,
-        CELL_MEMORY_LEN, REG_CELL_LEN,
-        REG_CELL_PTR, REG_CELL_PTR,
-        CELL_MEMORY, REG_CELL_PTR,
-        REG_CELL_PTR,
-        REG_CELL_LEN, REG_CELL_PTR,
-        REG_CELL_PTR, REG_CELL_PTR
-    )
+pub fn asm_main_init(abi: &Abi) -> Vec<AsmInstr> {
+    vec![
+        AsmInstr::Section(Section::Text),
+        AsmInstr::Global(String::from("_start")),
+        AsmInstr::Label(String::from("_start")),
+        AsmInstr::Comment(String::from(
+            "Point the cell_ptr at the array, zero filled by the loader",
+        )),
+        AsmInstr::Lea {
+            dst: abi.cell_ptr,
+            src: abi.memory(CELL_MEMORY),
+        },
+    ]
 }
 
 /// Marks the exit portion of the program
 #[must_use]
-pub fn asm_exit() -> String {
-    r#"
-EXIT:                               # Exiting the program
-    movl    $1, %eax                # sys_exit syscall number
-    xorl    %ebx, %ebx              # exit status 0
-    int     $0x80                   # syscall
-    "#.to_string()
+pub fn asm_exit(abi: &Abi, exit_code: ExitCode) -> Vec<AsmInstr> {
+    let status = abi.sys_call_args[0].dword();
+    let status = match exit_code {
+        ExitCode::Zero => vec![
+            AsmInstr::Comment(String::from("exit status 0")),
+            asm_reg_reg(BinaryOp::Xor, Size::Dword, status, status),
+        ],
+        ExitCode::Cell => vec![
+            AsmInstr::Comment(String::from("exit status of the current cell")),
+            AsmInstr::Movzx {
+                dst: status,
+                src: asm_cell_at(abi, 0),
+            },
+        ],
+    };
+    [
+        vec![
+            AsmInstr::Label(String::from("EXIT")),
+            asm_reg_imm(BinaryOp::Mov, Size::Dword, Register::Eax, abi.sys_calls.exit),
+        ],
+        status,
+        vec![abi.sys_call.clone()],
+    ]
+    .concat()
 }
 
 // --------------- [ MUST USE END ] --------------- \\
 
 /// Creates a method for prompting the user when the program needs it
-pub fn asm_stdin_init() -> String {
-    format!(
-        r#"{}:   .asciz "{}"        # Prompt for user
-    "#,
-        PROMPT_MEMORY, PROMPT,
-    )
+pub fn asm_stdin_init() -> Vec<AsmInstr> {
+    vec![AsmInstr::Data {
+        label: PROMPT_MEMORY.to_string(),
+        data: Data::Asciz(PROMPT.to_string()),
+    }]
+}
+
+/// Stores the output computed at compile time
+pub fn asm_precomputed_output_init(output: &[u8]) -> Vec<AsmInstr> {
+    vec![
+        AsmInstr::Comment(String::from("Output computed at compile time")),
+        AsmInstr::Data {
+            label: PRECOMPUTED_OUTPUT_MEMORY.to_string(),
+            data: Data::Bytes(output.to_vec()),
+        },
+    ]
+}
+
+/// Stores the text and the buffer of the tape dump, with the `struct sigaction` of
+/// its handler when it is dumped on a signal
+pub fn asm_dump_init(abi: &Abi, tape_dump: TapeDump) -> Vec<AsmInstr> {
+    let mut data = vec![
+        AsmInstr::Data {
+            label: DUMP_HEADER_MEMORY.to_string(),
+            data: Data::Asciz(DUMP_HEADER.to_string()),
+        },
+        AsmInstr::Data {
+            label: DUMP_HEX_MEMORY.to_string(),
+            data: Data::Asciz(HEX_DIGITS.to_string()),
+        },
+        AsmInstr::Section(Section::Bss),
+        AsmInstr::Data {
+            label: DUMP_BUFFER_MEMORY.to_string(),
+            data: Data::Space(MAX_DUMP_LEN),
+        },
+    ];
+    if tape_dump == TapeDump::Signal {
+        data.push(AsmInstr::Data {
+            label: DUMP_SIGACTION_MEMORY.to_string(),
+            data: Data::Space(abi.sigaction_len),
+        });
+    }
+    data
 }
 
 // --------------------- [ INIT END ] --------------------- \\
@@ -130,89 +316,108 @@ pub fn asm_stdin_init() -> String {
 // ---------------- [ I/O ] ---------------- \\
 
 /// Prints the byte of the current cell
-pub fn asm_print_cell() -> String {
-    format!(
-        r#"
-    {}
-    "#,
-        asm_sys_call(SysCall::Write, FileDescriptor::StdOut, false))
+pub fn asm_print_cell(abi: &Abi) -> Vec<AsmInstr> {
+    asm_sys_call(
+        abi,
+        abi.sys_calls.write,
+        FileDescriptor::StdOut,
+        SysCallBuffer::Cell,
+    )
+}
+
+/// Prints the output computed at compile time in a single write
+pub fn asm_print_precomputed_output(abi: &Abi, len: usize) -> Vec<AsmInstr> {
+    asm_sys_call(
+        abi,
+        abi.sys_calls.write,
+        FileDescriptor::StdOut,
+        SysCallBuffer::PrecomputedOutput(len),
+    )
 }
 
 /// Reads a character from the console and writes it to that particular index
-pub fn asm_read_to_cell() -> String {
-    format!(
-        r#"
-    {}
-    {}
-    "#,
-        asm_sys_call(SysCall::Write, FileDescriptor::StdOut, true),
-        asm_sys_call(SysCall::Read, FileDescriptor::StdIn, false))
+pub fn asm_read_to_cell(abi: &Abi) -> Vec<AsmInstr> {
+    [
+        asm_sys_call(
+            abi,
+            abi.sys_calls.write,
+            FileDescriptor::StdOut,
+            SysCallBuffer::Prompt,
+        ),
+        asm_sys_call(
+            abi,
+            abi.sys_calls.read,
+            FileDescriptor::StdIn,
+            SysCallBuffer::Cell,
+        ),
+    ]
+    .concat()
 }
 
 // ------------ [ SYS_CALLS ] ------------ \\
 
 /// Template for a syscall
 fn asm_sys_call(
-    sys_call: SysCall,
+    abi: &Abi,
+    sys_call: i64,
     file_descriptor: FileDescriptor,
-    is_prompt: bool,
-) -> String {
+    buffer: SysCallBuffer,
+) -> Vec<AsmInstr> {
     /*
-       Get the char address to print
-       Save reg for cell_len and cell_ptr
-       print
-       Restore reg for cell_len and cell_ptr
+       Save the cell_ptr if the syscall takes its register
+       Get the address of the buffer
+       Call the kernel
+       Restore the cell_ptr
     */
-    format!(
-        r#"
-    # {}
-    {}
-
-    {}
-    movl    ${}, %eax                 # sys_write syscall number
-    movl    ${}, %ebx                 # file descriptor for stdout
-    movl    ${}, %edx                 # length of the character
-    int     $0x80                    # syscall
-
-    {}
-    "#,
-        if is_prompt {
-            "Prompt user for input"
-        } else {
-            "Print Character at index"
+    let [descriptor, buffer_reg, len_reg, _] = abi.sys_call_args;
+    let comment = match buffer {
+        SysCallBuffer::Cell => "Print Character at index",
+        SysCallBuffer::Prompt => "Prompt user for input",
+        SysCallBuffer::PrecomputedOutput(_) => "Print output computed at compile time",
+        SysCallBuffer::DumpHeader => "Print the header of the dump",
+    };
+    let buffer_address = match buffer {
+        SysCallBuffer::Cell => asm_reg_reg(BinaryOp::Mov, abi.word, buffer_reg, abi.cell_ptr),
+        SysCallBuffer::Prompt => AsmInstr::Lea {
+            dst: buffer_reg,
+            src: abi.memory(PROMPT_MEMORY),
         },
-        save_reg(),
-        if is_prompt {
-            asm_init_ecx_for_sys_call_prompt()
-        } else {
-            asm_init_ecx_for_sys_call_index()
+        SysCallBuffer::PrecomputedOutput(_) => AsmInstr::Lea {
+            dst: buffer_reg,
+            src: abi.memory(PRECOMPUTED_OUTPUT_MEMORY),
         },
-        sys_call as usize,
-        file_descriptor as usize,
-        if is_prompt { PROMPT.len() } else { 1 },
-        restore_reg()
-    )
-}
+        SysCallBuffer::DumpHeader => AsmInstr::Lea {
+            dst: buffer_reg,
+            src: abi.memory(DUMP_HEADER_MEMORY),
+        },
+    };
+    let len = match buffer {
+        SysCallBuffer::Cell => 1,
+        SysCallBuffer::Prompt => PROMPT.len(),
+        SysCallBuffer::PrecomputedOutput(len) => len,
+        SysCallBuffer::DumpHeader => DUMP_HEADER.len(),
+    };
 
-/// Gives the address to ecx for the prompt when doing a syscall
-fn asm_init_ecx_for_sys_call_prompt() -> String {
-    format!(
-        "\
-    mov     ${}, {}                  # Moves the address for prompt",
-        PROMPT_MEMORY, REG_CELL_LEN
-    )
+    [
+        vec![AsmInstr::Comment(comment.to_string())],
+        save_reg(abi),
+        vec![
+            buffer_address,
+            asm_reg_imm(BinaryOp::Mov, Size::Dword, Register::Eax, sys_call),
+            asm_reg_imm(
+                BinaryOp::Mov,
+                Size::Dword,
+                descriptor.dword(),
+                file_descriptor as i64,
+            ),
+            asm_reg_imm(BinaryOp::Mov, Size::Dword, len_reg.dword(), len as i64),
+            abi.sys_call.clone(),
+        ],
+        restore_reg(abi),
+    ]
+    .concat()
 }
 
-/// Gives the address of the character in the array to be printed
-fn asm_init_ecx_for_sys_call_index() -> String {
-    format!(
-        "\
-    {}        # Read Get the address of the Character to Print
-    mov     {}, {}               # Moves the address for printing",
-        asm_get_index_mem_offset(),
-        REG_TEMP_NOT_PUBLIC, REG_CELL_LEN
-    )
-}
 // ------------ [ SYS_CALLS END ] ------------ \\
 
 // -------------- [ I/O END ] -------------- \\
@@ -220,56 +425,56 @@ fn asm_init_ecx_for_sys_call_index() -> String {
 
 // ----------------- [ LOOPS ] ----------------- \\
 
-/// Gives the template name of the loops
-pub fn asm_loop_label(level: usize, count: usize) -> String {
-    format!("{}_L{}_C{}", LOOP, level, count)
+/// Gives the label of the body of a loop
+/// Eg = 'LOOP_L0_C1'
+pub fn asm_loop_label(name: &str) -> String {
+    format!("{}_{}", LOOP, name)
 }
 
-/// Name of the return loop function
-/// Eg = 'LOOP2_C2_RET'
-fn asm_loop_ret(level: usize, count: usize) -> String {
-    format!("{}_RET", asm_loop_label(level, count))
+/// Compares the current cell with zero
+fn asm_test_current_cell(abi: &Abi) -> AsmInstr {
+    AsmInstr::binary(
+        BinaryOp::Cmp,
+        Size::Byte,
+        Operand::Mem(asm_cell_at(abi, 0)),
+        Operand::Imm(0),
+    )
 }
 
-/// Gives the name of the loop function
-/// Eg = 'LOOP2_C2:'
-pub fn asm_loop_name(level: usize, count: usize) -> String {
-    format!(r#"
-{}:
-"#, asm_loop_label(level, count))
+/// Name of the label after the loop
+/// Eg = 'LOOP_L2_C2_END'
+fn asm_loop_end_label(name: &str) -> String {
+    format!("{}_END", asm_loop_label(name))
 }
 
-/// Calls a loop and gives a label to jump back to when it's done
-/// jmp LOOP1
-/// LOOP1_RET:
-pub fn asm_loop_call(level: usize, count: usize) -> String {
-    format!(
-        r#"
-    jmp     {}
-    {}:
-    "#,
-        asm_loop_label(level, count),
-        asm_loop_ret(level, count)
-    )
+/// Skips the loop if the current index is zero and names the body of the loop
+/// je LOOP_L0_C1_END
+/// LOOP_L0_C1:
+pub fn asm_loop_call(abi: &Abi, name: &str) -> Vec<AsmInstr> {
+    vec![
+        AsmInstr::Comment(String::from("Skip the loop if current index is zero")),
+        asm_test_current_cell(abi),
+        AsmInstr::Jcc {
+            cond: Cond::Equal,
+            label: asm_loop_end_label(name),
+        },
+        AsmInstr::Label(asm_loop_label(name)),
+    ]
 }
 
-/// The end of the loop with the necessary checks for if it should go another
-/// round or return
-pub fn asm_loop_end(level: usize, count: usize) -> String {
-    format!(
-        r#"
-    # Check if current index is zero
-    {}
-    cmp     $0, {}
-    jne     {}
-    # End loop if curr index is zero
-    jmp {}
-    "#,
-        asm_extract_at_index(),
-        REG_TEMP,
-        asm_loop_label(level, count),
-        asm_loop_ret(level, count)
-    )
+/// The end of the loop going another round unless the current index is zero
+/// jne LOOP_L0_C1
+/// LOOP_L0_C1_END:
+pub fn asm_loop_end(abi: &Abi, name: &str) -> Vec<AsmInstr> {
+    vec![
+        AsmInstr::Comment(String::from("Repeat the loop unless current index is zero")),
+        asm_test_current_cell(abi),
+        AsmInstr::Jcc {
+            cond: Cond::NotEqual,
+            label: asm_loop_label(name),
+        },
+        AsmInstr::Label(asm_loop_end_label(name)),
+    ]
 }
 
 // ----------------- [ LOOPS END ] ----------------- \\
@@ -278,78 +483,68 @@ pub fn asm_loop_end(level: usize, count: usize) -> String {
 // ----------------- [ CELL_PTR & MEMORY MODIFICATIONS ] ----------------- \\
 
 /// Increments the cell ptr
-pub fn asm_cell_ptr_increment(offset: usize) -> String {
-    asm_offset_cell_ptr("add", offset)
+pub fn asm_cell_ptr_increment(abi: &Abi, offset: usize) -> Vec<AsmInstr> {
+    asm_offset_cell_ptr(abi, BinaryOp::Add, offset)
 }
 
 /// Decrements the cell ptr
-pub fn asm_cell_ptr_decrement(offset: usize) -> String {
-    asm_offset_cell_ptr("sub", offset)
+pub fn asm_cell_ptr_decrement(abi: &Abi, offset: usize) -> Vec<AsmInstr> {
+    asm_offset_cell_ptr(abi, BinaryOp::Sub, offset)
 }
 
-/// Increments the value in the cell
-pub fn asm_cell_increment(offset: usize) -> String {
-    asm_modify_cell("add", offset)
+/// Increments the value in the cell `offset` cells away from the cell ptr
+pub fn asm_cell_increment(abi: &Abi, amount: usize, offset: isize) -> Vec<AsmInstr> {
+    asm_modify_cell(abi, BinaryOp::Add, amount, offset)
 }
 
-/// Decrements the value in the cell
-pub fn asm_cell_decrement(offset: usize) -> String {
-    asm_modify_cell("sub", offset)
+/// Decrements the value in the cell `offset` cells away from the cell ptr
+pub fn asm_cell_decrement(abi: &Abi, amount: usize, offset: isize) -> Vec<AsmInstr> {
+    asm_modify_cell(abi, BinaryOp::Sub, amount, offset)
 }
 
 /// Adds an offset to the cell ptr
-fn asm_offset_cell_ptr(instr: &str, amount: usize) -> String {
-    // add $1, %eax
-    format!(
-        "
-    {}      ${}, {} \n",
-        instr, amount, REG_CELL_PTR
-    )
-}
-
-// TODO: Fix incrementation and decrementation so as to be in set of { Z mod 255 }
-/// Incr/Decr the value in the cell
-fn asm_modify_cell(instr: &str, amount: usize) -> String {
-    /*
-        access val at index
-        add to that val
-        put back to index
-    */
-    format!(
-        "
-    {}
-    {}      ${}, {}
-    {}
-    ",
-        asm_extract_at_index(),
-        instr, amount, REG_TEMP,
-        asm_store_to_index()
-    )
+fn asm_offset_cell_ptr(abi: &Abi, op: BinaryOp, amount: usize) -> Vec<AsmInstr> {
+    vec![asm_reg_imm(op, abi.word, abi.cell_ptr, amount as i64)]
 }
 
-/// Extracts the num at index [REG_CELL_PTR] into [REG_TEMP]
-fn asm_extract_at_index() -> String {
-    format!(
-        r#"
-    {}
-    mov     ({}), {}"#,
-        asm_get_index_mem_offset(),
-        REG_TEMP_NOT_PUBLIC, REG_TEMP
-    )
+/// Incr/Decr the value in the cell `offset` cells away from the cell ptr in memory
+fn asm_modify_cell(abi: &Abi, op: BinaryOp, amount: usize, offset: isize) -> Vec<AsmInstr> {
+    // Cells wrap around so only the low byte of the amount matters
+    vec![AsmInstr::binary(
+        op,
+        Size::Byte,
+        Operand::Mem(asm_cell_at(abi, offset)),
+        Operand::Imm(amount as u8 as i64),
+    )]
 }
 
-/// Gets the memory address of the value in
-/// [CELL_MEMORY] at index [REG_CELL_PTR] to [REG_TEMP_NOT_PUBLIC]
-fn asm_get_index_mem_offset() -> String {
-    format!(
-        "lea     {}({}), {}",
-        CELL_MEMORY, REG_CELL_PTR, REG_TEMP_NOT_PUBLIC,
-    )
+/// Gives the memory of the cell `offset` cells away from the cell ptr
+/// Eg = '(%edx)', '-1(%rbx)'
+fn asm_cell_at(abi: &Abi, offset: isize) -> Memory {
+    Memory::base(abi.cell_ptr).disp(offset)
 }
 
-/// Stores the value in [REG_TEMP] to memory located in [REG_TEMP_NOT_PUBLIC]
-fn asm_store_to_index() -> String {
-    format!("mov    {}, ({})", REG_TEMP, REG_TEMP_NOT_PUBLIC)
+/// Restores the tape and the cell ptr computed at compile time
+pub fn asm_restore_snapshot(abi: &Abi, tape: &[u8], cell_ptr: usize) -> Vec<AsmInstr> {
+    let mut code = vec![AsmInstr::Comment(String::from(
+        "Restore the state computed at compile time",
+    ))];
+    tape.iter()
+        .enumerate()
+        .filter(|(_, &cell)| cell != 0)
+        .for_each(|(idx, &cell)| {
+            code.push(AsmInstr::binary(
+                BinaryOp::Mov,
+                Size::Byte,
+                Operand::Mem(abi.memory(CELL_MEMORY).disp(idx as isize)),
+                Operand::Imm(cell as i64),
+            ))
+        });
+    code.push(AsmInstr::Lea {
+        dst: abi.cell_ptr,
+        src: abi.memory(CELL_MEMORY).disp(cell_ptr as isize),
+    });
+    code
 }
 
 // ------------ [ CELL_PTR & MEMORY MODIFICATIONS END ] ------------ \\
@@ -358,24 +553,712 @@ fn asm_store_to_index() -> String {
 // ---------------------- [ FUNCTIONS END  ] ---------------------- \\
 
 
-// ---------------------- [ Extra ] ---------------------- \\
-
-/// Prints out all the characters in the [CELL_MEMORY]
-pub fn asm_debug_memory() -> String {
-    format!(
-        r#"
-DEBUG_PRINT_CELL_MEMORY:        # Printing the array
-    movl    ${}, %eax              # sys_write syscall number
-    movl    ${}, %ebx              # file descriptor for stdout
-    movl    ${}, %ecx              # pointer to the array
-    movl    {}, %edx               # length of the array
-    int     $0x80                   # syscall
-    "#,
-        SysCall::Write as usize,
-        FileDescriptor::StdOut as usize,
-        CELL_MEMORY,
-        CELL_MEMORY_LEN
+// ----------------- [ TAPE DUMP ] ----------------- \\
+
+/// Stores a character `disp` bytes after the cursor of the dump
+fn asm_dump_char(cursor: Register, disp: isize, char: u8) -> AsmInstr {
+    AsmInstr::binary(
+        BinaryOp::Mov,
+        Size::Byte,
+        Operand::Mem(Memory::base(cursor).disp(disp)),
+        Operand::Imm(char as i64),
     )
 }
 
-// ---------------------- [ END Extra ] ---------------------- \\
+/// Writes the low `count` hex digits of '%edx' `disp` bytes after the cursor in the
+/// word sized 'di', looking the digits up in the table at 'si'
+fn asm_dump_hex_digits(abi: &Abi, count: usize, disp: isize) -> Vec<AsmInstr> {
+    let [ax, si, di] = [Register::Rax, Register::Rsi, Register::Rdi].map(|reg| abi.reg(reg));
+    let mut code = vec![];
+    for digit in 0..count {
+        code.push(asm_reg_reg(BinaryOp::Mov, Size::Dword, Register::Eax, Register::Edx));
+        let shift = 4 * (count - 1 - digit);
+        if shift > 0 {
+            code.push(asm_reg_imm(BinaryOp::Shr, Size::Dword, Register::Eax, shift as i64));
+        }
+        code.extend([
+            asm_reg_imm(BinaryOp::And, Size::Dword, Register::Eax, 15),
+            AsmInstr::binary(
+                BinaryOp::Mov,
+                Size::Byte,
+                Operand::Reg(REG_TEMP_BYTE),
+                Operand::Mem(Memory::base(si).index(ax)),
+            ),
+            AsmInstr::binary(
+                BinaryOp::Mov,
+                Size::Byte,
+                Operand::Mem(Memory::base(di).disp(disp + digit as isize)),
+                Operand::Reg(REG_TEMP_BYTE),
+            ),
+        ]);
+    }
+    code
+}
+
+/// Writes the pointer and the used cells of the tape to stderr, in the format of
+/// [Tape::dump](crate::tape::Tape::dump), with the cell_ptr pointing at the current cell
+/// DUMP_TAPE:
+///     ...
+///     ret
+pub fn asm_dump_tape(abi: &Abi) -> Vec<AsmInstr> {
+    // 'bx' holds the index of the current cell then the end of the dump, 'cx' counts the
+    // cells, 'dx' holds the number being written, 'si' the hex digits and 'di' the cursor
+    let [ax, bx, si, di] =
+        [Register::Rax, Register::Rbx, Register::Rsi, Register::Rdi].map(|reg| abi.reg(reg));
+    let [descriptor, buffer_reg, len_reg, _] = abi.sys_call_args;
+    let label = |name: &str| format!("{}_{}", DUMP_TAPE, name);
+    let tape = || AsmInstr::Lea {
+        dst: ax,
+        src: abi.memory(CELL_MEMORY),
+    };
+    let cell = || Memory::base(ax).index(abi.reg(Register::Rcx));
+
+    let mut index = vec![
+        AsmInstr::Comment(String::from("Write the index of the current cell")),
+        tape(),
+    ];
+    if abi.cell_ptr != bx {
+        index.push(asm_reg_reg(BinaryOp::Mov, abi.word, bx, abi.cell_ptr));
+    }
+
+    [
+        vec![
+            AsmInstr::Label(DUMP_TAPE.to_string()),
+            AsmInstr::Comment(String::from("Keep the cell_ptr for the exit status")),
+            AsmInstr::Push(abi.cell_ptr),
+        ],
+        asm_sys_call(
+            abi,
+            abi.sys_calls.write,
+            FileDescriptor::StdErr,
+            SysCallBuffer::DumpHeader,
+        ),
+        index,
+        vec![
+            asm_reg_reg(BinaryOp::Sub, abi.word, bx, ax),
+            AsmInstr::Lea { dst: si, src: abi.memory(DUMP_HEX_MEMORY) },
+            AsmInstr::Lea { dst: di, src: abi.memory(DUMP_BUFFER_MEMORY) },
+            asm_reg_reg(BinaryOp::Mov, Size::Dword, Register::Edx, Register::Ebx),
+        ],
+        asm_dump_hex_digits(abi, INDEX_DIGITS, 0),
+        vec![
+            asm_reg_imm(BinaryOp::Add, abi.word, di, INDEX_DIGITS as i64),
+            AsmInstr::Comment(String::from(
+                "Look for the last cell that is not zero, stopping at the current one",
+            )),
+            asm_reg_imm(BinaryOp::Mov, Size::Dword, Register::Ecx, TAPE_LEN as i64),
+            AsmInstr::Label(label("SCAN")),
+            asm_reg_imm(BinaryOp::Sub, Size::Dword, Register::Ecx, 1),
+            asm_reg_reg(BinaryOp::Cmp, Size::Dword, Register::Ecx, Register::Ebx),
+            AsmInstr::Jcc { cond: Cond::Equal, label: label("FOUND") },
+            tape(),
+            AsmInstr::binary(BinaryOp::Cmp, Size::Byte, Operand::Mem(cell()), Operand::Imm(0)),
+            AsmInstr::Jcc { cond: Cond::Equal, label: label("SCAN") },
+            AsmInstr::Label(label("FOUND")),
+            asm_reg_imm(BinaryOp::Add, Size::Dword, Register::Ecx, 1),
+            asm_reg_reg(BinaryOp::Mov, Size::Dword, Register::Ebx, Register::Ecx),
+            asm_reg_reg(BinaryOp::Xor, Size::Dword, Register::Ecx, Register::Ecx),
+            AsmInstr::Label(label("NEXT")),
+            asm_reg_reg(BinaryOp::Cmp, Size::Dword, Register::Ecx, Register::Ebx),
+            AsmInstr::Jcc { cond: Cond::Equal, label: label("DONE") },
+            asm_reg_reg(BinaryOp::Mov, Size::Dword, Register::Eax, Register::Ecx),
+            asm_reg_imm(BinaryOp::And, Size::Dword, Register::Eax, CELLS_PER_LINE as i64 - 1),
+            AsmInstr::Jcc { cond: Cond::NotEqual, label: label("CELL") },
+            AsmInstr::Comment(String::from("A new line starts with the index of its first cell")),
+            asm_dump_char(di, 0, b'\n'),
+            asm_reg_reg(BinaryOp::Mov, Size::Dword, Register::Edx, Register::Ecx),
+        ],
+        asm_dump_hex_digits(abi, INDEX_DIGITS, 1),
+        vec![
+            asm_dump_char(di, INDEX_DIGITS as isize + 1, b':'),
+            asm_reg_imm(BinaryOp::Add, abi.word, di, INDEX_DIGITS as i64 + 2),
+            AsmInstr::Label(label("CELL")),
+            tape(),
+            AsmInstr::Movzx { dst: Register::Edx, src: cell() },
+            asm_dump_char(di, 0, b' '),
+        ],
+        asm_dump_hex_digits(abi, CELL_DIGITS, 1),
+        vec![
+            asm_reg_imm(BinaryOp::Add, abi.word, di, CELL_DIGITS as i64 + 1),
+            asm_reg_imm(BinaryOp::Add, Size::Dword, Register::Ecx, 1),
+            AsmInstr::Jmp(label("NEXT")),
+            AsmInstr::Label(label("DONE")),
+            asm_dump_char(di, 0, b'\n'),
+            asm_reg_imm(BinaryOp::Add, abi.word, di, 1),
+            AsmInstr::Comment(String::from("Print the dump")),
+            AsmInstr::Lea { dst: buffer_reg, src: abi.memory(DUMP_BUFFER_MEMORY) },
+            asm_reg_reg(BinaryOp::Mov, abi.word, len_reg, di),
+            asm_reg_reg(BinaryOp::Sub, abi.word, len_reg, buffer_reg),
+            asm_reg_imm(BinaryOp::Mov, Size::Dword, Register::Eax, abi.sys_calls.write),
+            asm_reg_imm(
+                BinaryOp::Mov,
+                Size::Dword,
+                descriptor.dword(),
+                FileDescriptor::StdErr as i64,
+            ),
+            abi.sys_call.clone(),
+            AsmInstr::Pop(abi.cell_ptr),
+            AsmInstr::Ret,
+        ],
+    ]
+    .concat()
+}
+
+/// Makes the program dump its tape on `SIGUSR1` with `rt_sigaction`
+pub fn asm_install_signal_handler(abi: &Abi) -> Vec<AsmInstr> {
+    let [signal, action, old_action, mask_len] = abi.sys_call_args;
+    let ax = abi.reg(Register::Rax);
+    let sigaction = |disp: isize| abi.memory(DUMP_SIGACTION_MEMORY).disp(disp);
+    let mut code = vec![AsmInstr::Comment(String::from("Dump the tape on SIGUSR1"))];
+    code.extend(save_reg(abi));
+    for (routine, disp) in [
+        (DUMP_SIGNAL_HANDLER, 0),
+        (DUMP_SIGNAL_RESTORER, abi.sigaction_restorer_at),
+    ] {
+        code.push(AsmInstr::Lea { dst: ax, src: abi.memory(routine) });
+        code.push(AsmInstr::binary(
+            BinaryOp::Mov,
+            abi.word,
+            Operand::Mem(sigaction(disp)),
+            Operand::Reg(ax),
+        ));
+    }
+    code.extend([
+        AsmInstr::binary(
+            BinaryOp::Mov,
+            abi.word,
+            Operand::Mem(sigaction(abi.sigaction_flags_at)),
+            Operand::Imm(SIGACTION_FLAGS),
+        ),
+        asm_reg_imm(BinaryOp::Mov, Size::Dword, Register::Eax, abi.sys_calls.rt_sigaction),
+        asm_reg_imm(BinaryOp::Mov, Size::Dword, signal.dword(), SIGUSR1),
+        AsmInstr::Lea { dst: action, src: sigaction(0) },
+        asm_reg_reg(BinaryOp::Xor, Size::Dword, old_action.dword(), old_action.dword()),
+        AsmInstr::Comment(String::from("Size of the signal mask")),
+        asm_reg_imm(BinaryOp::Mov, abi.word, mask_len, 8),
+        abi.sys_call.clone(),
+    ]);
+    code.extend(restore_reg(abi));
+    code
+}
+
+/// Dumps the tape with the cell_ptr of the interrupted code, which gets its registers
+/// back on return. Interrupted syscalls may have taken the register of the cell_ptr,
+/// which is then found in its saved register
+/// DUMP_SIGNAL_HANDLER:
+///     ...
+/// DUMP_SIGNAL_RESTORER:
+///     ...
+pub fn asm_signal_handler(abi: &Abi) -> Vec<AsmInstr> {
+    let label = |name: &str| format!("{}_{}", DUMP_SIGNAL_HANDLER, name);
+    let mut code = vec![
+        AsmInstr::Label(DUMP_SIGNAL_HANDLER.to_string()),
+        AsmInstr::Comment(String::from("Registers of the interrupted code")),
+    ];
+    let ucontext = match &abi.ucontext {
+        Operand::Reg(reg) => *reg,
+        ucontext => {
+            let ax = abi.reg(Register::Rax);
+            code.push(AsmInstr::binary(
+                BinaryOp::Mov,
+                abi.word,
+                Operand::Reg(ax),
+                ucontext.clone(),
+            ));
+            ax
+        }
+    };
+    let saved = |disp: isize| {
+        AsmInstr::binary(
+            BinaryOp::Mov,
+            abi.word,
+            Operand::Reg(abi.cell_ptr),
+            Operand::Mem(Memory::base(ucontext).disp(disp)),
+        )
+    };
+    code.push(saved(abi.ucontext_cell_ptr));
+    if let Some((_, ucontext_saved)) = abi.saved_cell_ptr {
+        let cx = abi.reg(Register::Rcx);
+        let bound = |disp: isize| {
+            vec![
+                AsmInstr::Lea {
+                    dst: cx,
+                    src: abi.memory(CELL_MEMORY).disp(disp),
+                },
+                asm_reg_reg(BinaryOp::Cmp, abi.word, abi.cell_ptr, cx),
+            ]
+        };
+        code.extend(bound(0));
+        code.push(AsmInstr::Jcc { cond: Cond::Less, label: label("SAVED") });
+        code.extend(bound(TAPE_LEN as isize));
+        code.extend([
+            AsmInstr::Jcc { cond: Cond::Less, label: label("CALL") },
+            AsmInstr::Label(label("SAVED")),
+            saved(ucontext_saved),
+            AsmInstr::Label(label("CALL")),
+        ]);
+    }
+    code.extend([
+        AsmInstr::Call(DUMP_TAPE.to_string()),
+        AsmInstr::Ret,
+        AsmInstr::Label(DUMP_SIGNAL_RESTORER.to_string()),
+        asm_reg_imm(BinaryOp::Mov, Size::Dword, Register::Eax, abi.sys_calls.rt_sigreturn),
+        abi.sys_call.clone(),
+    ]);
+    code
+}
+
+// ----------------- [ TAPE DUMP END ] ----------------- \\
+
+// ---------------------- [ BACKEND ] ---------------------- \\
+
+/// Generates x86 Linux assembly for the word size and syscalls of an [Abi], in AT&T
+/// syntax for GNU `as` or in Intel syntax for NASM
+pub struct X86Backend {
+    /// Calling convention of the target
+    abi: &'static Abi,
+    /// Syntax the assembly is printed in
+    syntax: Syntax,
+    /// Runtime behaviour of the program
+    options: CodegenOptions,
+    /// The current cell kept in [REG_TEMP_BYTE] between loops, pointer moves and I/O
+    cache: CellCache,
+}
+
+impl X86Backend {
+    /// Creates a new `X86Backend` for the ABI printing in the given syntax.
+    pub fn new(abi: &'static Abi, syntax: Syntax, options: CodegenOptions) -> Self {
+        X86Backend {
+            abi,
+            syntax,
+            options,
+            cache: CellCache::new(REG_TEMP_BYTE, asm_cell_at(abi, 0)),
+        }
+    }
+}
+
+impl Backend for X86Backend {
+    fn data_section(&mut self, used_stdin: bool, precomputed_output: &[u8]) -> Vec<AsmInstr> {
+        let mut data = asm_data_init();
+        if used_stdin {
+            data.extend(asm_stdin_init());
+        }
+        if !precomputed_output.is_empty() {
+            data.extend(asm_precomputed_output_init(precomputed_output));
+        }
+        if self.options.tape_dump != TapeDump::Off {
+            data.extend(asm_dump_init(self.abi, self.options.tape_dump));
+        }
+        data
+    }
+
+    fn prologue(&mut self) -> Vec<AsmInstr> {
+        let mut code = asm_main_init(self.abi);
+        if self.options.tape_dump == TapeDump::Signal {
+            code.extend(asm_install_signal_handler(self.abi));
+        }
+        code
+    }
+
+    fn epilogue(&mut self) -> Vec<AsmInstr> {
+        let mut code = self.cache.flush();
+        if self.options.tape_dump != TapeDump::Off {
+            code.push(AsmInstr::Call(DUMP_TAPE.to_string()));
+        }
+        code.extend(asm_exit(self.abi, self.options.exit_code));
+        if self.options.tape_dump != TapeDump::Off {
+            code.extend(asm_dump_tape(self.abi));
+        }
+        if self.options.tape_dump == TapeDump::Signal {
+            code.extend(asm_signal_handler(self.abi));
+        }
+        code
+    }
+
+    fn move_ptr(&mut self, amount: isize) -> Vec<AsmInstr> {
+        let code = if amount < 0 {
+            asm_cell_ptr_decrement(self.abi, amount.unsigned_abs())
+        } else {
+            asm_cell_ptr_increment(self.abi, amount.unsigned_abs())
+        };
+        [self.cache.flush(), code].concat()
+    }
+
+    fn add(&mut self, amount: isize, offset: isize) -> Vec<AsmInstr> {
+        // Cells wrap around so only the low byte of the amount matters, the signal
+        // handler and debuggers reading the current cell from memory
+        match offset {
+            _ if self.options.keeps_tape_in_memory() && amount < 0 => {
+                asm_cell_decrement(self.abi, amount.unsigned_abs(), offset)
+            }
+            _ if self.options.keeps_tape_in_memory() => {
+                asm_cell_increment(self.abi, amount.unsigned_abs(), offset)
+            }
+            0 if amount < 0 => self.cache.modify(BinaryOp::Sub, amount.unsigned_abs() as u8),
+            0 => self.cache.modify(BinaryOp::Add, amount as u8),
+            _ if amount < 0 => asm_cell_decrement(self.abi, amount.unsigned_abs(), offset),
+            _ => asm_cell_increment(self.abi, amount.unsigned_abs(), offset),
+        }
+    }
+
+    fn loop_begin(&mut self, name: &str) -> Vec<AsmInstr> {
+        [self.cache.flush(), asm_loop_call(self.abi, name)].concat()
+    }
+
+    fn loop_end(&mut self, name: &str) -> Vec<AsmInstr> {
+        [self.cache.flush(), asm_loop_end(self.abi, name)].concat()
+    }
+
+    fn input(&mut self) -> Vec<AsmInstr> {
+        [self.cache.flush(), asm_read_to_cell(self.abi)].concat()
+    }
+
+    fn output(&mut self) -> Vec<AsmInstr> {
+        [self.cache.flush(), asm_print_cell(self.abi)].concat()
+    }
+
+    fn restore_snapshot(&mut self, tape: &[u8], cell_ptr: usize) -> Vec<AsmInstr> {
+        [self.cache.flush(), asm_restore_snapshot(self.abi, tape, cell_ptr)].concat()
+    }
+
+    fn print_precomputed_output(&mut self, output: &[u8]) -> Vec<AsmInstr> {
+        [
+            self.cache.flush(),
+            asm_print_precomputed_output(self.abi, output.len()),
+        ]
+        .concat()
+    }
+
+    fn debug_info(&mut self, source_path: &str) -> Vec<AsmInstr> {
+        debug_sections(source_path, self.abi.word)
+    }
+
+    fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+}
+
+// ---------------------- [ BACKEND END ] ---------------------- \\
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm_generator::asm::printer;
+    use crate::asm_generator::{generate, Target, TEST_OPTIONS};
+
+    /// Character starting a comment in the syntax
+    fn comment_char(syntax: Syntax) -> char {
+        match syntax {
+            Syntax::Att => '#',
+            Syntax::Intel => ';',
+        }
+    }
+
+    /// Keeps the instruction lines of a piece of assembly, without comments
+    fn lines(syntax: Syntax, asm: &str) -> Vec<String> {
+        asm.lines()
+            .map(|line| line.split(comment_char(syntax)).next().unwrap().trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect()
+    }
+
+    /// Prints the instructions in the syntax one per line, without comments
+    fn instructions(syntax: Syntax, code: &[AsmInstr]) -> Vec<String> {
+        let asm: String = code.iter().map(|instr| printer::print(instr, syntax)).collect();
+        lines(syntax, &asm)
+    }
+
+    /// Prints the instructions in AT&T syntax, comments included
+    fn printed(code: &[AsmInstr]) -> String {
+        code.iter().map(|instr| printer::print(instr, Syntax::Att)).collect()
+    }
+
+    /// Creates a backend printing in AT&T syntax
+    fn backend(abi: &'static Abi, options: CodegenOptions) -> X86Backend {
+        X86Backend::new(abi, Syntax::Att, options)
+    }
+
+    #[test]
+    fn modifies_cells_through_the_cell_ptr() {
+        assert_eq!(
+            asm_cell_increment(&I386_LINUX, 3, -1),
+            [AsmInstr::binary(
+                BinaryOp::Add,
+                Size::Byte,
+                Operand::Mem(Memory::base(Register::Edx).disp(-1)),
+                Operand::Imm(3),
+            )]
+        );
+        assert_eq!(
+            instructions(Syntax::Att, &asm_cell_increment(&X86_64_LINUX, 258, 0)),
+            ["addb $2, (%rbx)"]
+        );
+        assert_eq!(
+            instructions(Syntax::Att, &asm_cell_decrement(&X86_64_LINUX, 1, -1)),
+            ["subb $1, -1(%rbx)"]
+        );
+    }
+
+    #[test]
+    fn moves_the_cell_ptr_with_registers_of_the_word_size() {
+        assert_eq!(
+            instructions(Syntax::Att, &asm_cell_ptr_increment(&I386_LINUX, 3)),
+            ["addl $3, %edx"]
+        );
+        assert_eq!(
+            instructions(Syntax::Att, &asm_cell_ptr_increment(&X86_64_LINUX, 3)),
+            ["addq $3, %rbx"]
+        );
+        assert_eq!(
+            instructions(Syntax::Att, &asm_cell_ptr_decrement(&X86_64_LINUX, 2)),
+            ["subq $2, %rbx"]
+        );
+    }
+
+    #[test]
+    fn swaps_the_operands_in_intel_syntax() {
+        let code = asm_cell_increment(&I386_LINUX, 3, -1);
+        assert_eq!(instructions(Syntax::Att, &code), ["addb $3, -1(%edx)"]);
+        assert_eq!(instructions(Syntax::Intel, &code), ["add byte [edx-1], 3"]);
+        assert_eq!(
+            instructions(Syntax::Intel, &asm_cell_ptr_decrement(&I386_LINUX, 2)),
+            ["sub edx, 2"]
+        );
+    }
+
+    #[test]
+    fn prints_with_the_syscall_abi() {
+        assert_eq!(
+            instructions(Syntax::Att, &asm_print_cell(&I386_LINUX)),
+            [
+                "movl %edx, %edi",
+                "movl %edx, %ecx",
+                "movl $4, %eax",
+                "movl $1, %ebx",
+                "movl $1, %edx",
+                "int $0x80",
+                "movl %edi, %edx",
+            ]
+        );
+        assert_eq!(
+            instructions(Syntax::Att, &asm_print_cell(&X86_64_LINUX)),
+            [
+                "movq %rbx, %rsi",
+                "movl $1, %eax",
+                "movl $1, %edi",
+                "movl $1, %edx",
+                "syscall",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_with_the_syscall_abi() {
+        let read = instructions(Syntax::Att, &asm_read_to_cell(&X86_64_LINUX));
+        assert_eq!(read.len(), 10);
+        assert_eq!(read[0], "lea input_prompt(%rip), %rsi");
+        assert_eq!(
+            read[5..],
+            [
+                "movq %rbx, %rsi",
+                "movl $0, %eax",
+                "movl $0, %edi",
+                "movl $1, %edx",
+                "syscall",
+            ]
+        );
+
+        let read = instructions(Syntax::Att, &asm_read_to_cell(&I386_LINUX));
+        assert_eq!(read[1], "lea input_prompt, %ecx");
+        assert!(read.contains(&String::from("movl $3, %eax")));
+    }
+
+    #[test]
+    fn exits_with_the_syscall_abi() {
+        assert_eq!(
+            instructions(Syntax::Att, &asm_exit(&X86_64_LINUX, ExitCode::Zero)),
+            ["EXIT:", "movl $60, %eax", "xorl %edi, %edi", "syscall"]
+        );
+        assert_eq!(
+            instructions(Syntax::Att, &asm_exit(&I386_LINUX, ExitCode::Zero)),
+            ["EXIT:", "movl $1, %eax", "xorl %ebx, %ebx", "int $0x80"]
+        );
+    }
+
+    #[test]
+    fn exits_with_the_current_cell() {
+        assert_eq!(
+            instructions(Syntax::Att, &asm_exit(&X86_64_LINUX, ExitCode::Cell)),
+            ["EXIT:", "movl $60, %eax", "movzbl (%rbx), %edi", "syscall"]
+        );
+
+        // The cell is stored before the exit reads it
+        let mut backend = backend(
+            &X86_64_LINUX,
+            CodegenOptions {
+                exit_code: ExitCode::Cell,
+                ..TEST_OPTIONS
+            },
+        );
+        backend.add(1, 0);
+        assert_eq!(
+            instructions(Syntax::Att, &backend.epilogue())[..2],
+            ["movb %al, (%rbx)", "EXIT:"]
+        );
+    }
+
+    #[test]
+    fn backend_picks_the_direction_from_the_sign() {
+        let mut backend = backend(&X86_64_LINUX, TEST_OPTIONS);
+        assert_eq!(instructions(Syntax::Att, &backend.move_ptr(-2)), ["subq $2, %rbx"]);
+        assert_eq!(instructions(Syntax::Att, &backend.move_ptr(2)), ["addq $2, %rbx"]);
+        assert_eq!(instructions(Syntax::Att, &backend.add(-1, 1)), ["subb $1, 1(%rbx)"]);
+        assert_eq!(
+            instructions(Syntax::Att, &backend.add(1, 0)),
+            ["movb (%rbx), %al", "addb $1, %al"]
+        );
+    }
+
+    #[test]
+    fn backend_keeps_the_current_cell_in_a_register() {
+        let mut backend = backend(&I386_LINUX, TEST_OPTIONS);
+        let code = [backend.add(1, 0), backend.add(-2, 0), backend.move_ptr(1)].concat();
+        assert_eq!(
+            instructions(Syntax::Att, &code),
+            [
+                "movb (%edx), %al",
+                "addb $1, %al",
+                "subb $2, %al",
+                "movb %al, (%edx)",
+                "addl $1, %edx",
+            ]
+        );
+    }
+
+    #[test]
+    fn backend_loads_the_cached_cell_once_per_block() {
+        let mut backend = backend(&X86_64_LINUX, TEST_OPTIONS);
+        let code = [backend.add(1, 0), backend.add(3, 1), backend.add(-2, 0)].concat();
+        assert_eq!(
+            instructions(Syntax::Att, &code),
+            ["movb (%rbx), %al", "addb $1, %al", "addb $3, 1(%rbx)", "subb $2, %al"]
+        );
+    }
+
+    #[test]
+    fn backend_flushes_the_cached_cell_before_loops_moves_and_syscalls() {
+        let flushes: [fn(&mut X86Backend) -> Vec<AsmInstr>; 5] = [
+            |backend| backend.loop_begin("L0_C1"),
+            |backend| backend.loop_end("L0_C1"),
+            |backend| backend.move_ptr(1),
+            |backend| backend.input(),
+            |backend| backend.output(),
+        ];
+        for flush in flushes {
+            let mut backend = backend(&X86_64_LINUX, TEST_OPTIONS);
+            backend.add(1, 0);
+            let code = instructions(Syntax::Att, &flush(&mut backend));
+            assert_eq!(code[0], "movb %al, (%rbx)");
+            assert!(!code[1..].contains(&"movb %al, (%rbx)".to_string()));
+
+            // The next change loads the cell again
+            assert_eq!(instructions(Syntax::Att, &backend.add(1, 0))[0], "movb (%rbx), %al");
+        }
+    }
+
+    #[test]
+    fn backend_flushes_before_the_loop_labels() {
+        let mut backend = backend(&X86_64_LINUX, TEST_OPTIONS);
+        backend.add(1, 0);
+        assert_eq!(
+            instructions(Syntax::Att, &backend.loop_begin("L0_C1")),
+            ["movb %al, (%rbx)", "cmpb $0, (%rbx)", "je LOOP_L0_C1_END", "LOOP_L0_C1:"]
+        );
+
+        backend.add(1, 0);
+        assert_eq!(
+            instructions(Syntax::Att, &backend.loop_end("L0_C1")),
+            ["movb %al, (%rbx)", "cmpb $0, (%rbx)", "jne LOOP_L0_C1", "LOOP_L0_C1_END:"]
+        );
+    }
+
+    #[test]
+    fn backend_only_declares_the_data_the_program_uses() {
+        let mut backend = backend(&X86_64_LINUX, TEST_OPTIONS);
+        assert!(!printed(&backend.data_section(false, &[])).contains(PROMPT_MEMORY));
+        assert!(!printed(&backend.data_section(false, &[])).contains(PRECOMPUTED_OUTPUT_MEMORY));
+        assert!(printed(&backend.data_section(true, &[])).contains(PROMPT_MEMORY));
+        assert!(printed(&backend.data_section(false, b"hi")).contains(".byte 104, 105"));
+    }
+
+    #[test]
+    fn backend_dumps_the_tape_before_exiting() {
+        for (abi, flush) in [
+            (&I386_LINUX, "movb %al, (%edx)"),
+            (&X86_64_LINUX, "movb %al, (%rbx)"),
+        ] {
+            let mut backend = backend(
+                abi,
+                CodegenOptions {
+                    tape_dump: TapeDump::Exit,
+                    ..TEST_OPTIONS
+                },
+            );
+            let code = [backend.add(1, 0), backend.epilogue()].concat();
+            let code = instructions(Syntax::Att, &code);
+            let call = code.iter().position(|line| line == "call DUMP_TAPE").unwrap();
+            assert_eq!(code[call - 1], flush);
+            assert_eq!(code[call + 1], "EXIT:");
+            assert!(code.contains(&String::from("DUMP_TAPE:")));
+            assert!(!code.contains(&String::from("DUMP_SIGNAL_HANDLER:")));
+            assert!(printed(&backend.data_section(false, &[])).contains(DUMP_BUFFER_MEMORY));
+        }
+    }
+
+    #[test]
+    fn backend_keeps_the_tape_in_memory_for_the_signal_handler() {
+        for (abi, cell, rt_sigaction) in [
+            (&I386_LINUX, "(%edx)", "movl $174, %eax"),
+            (&X86_64_LINUX, "(%rbx)", "movl $13, %eax"),
+        ] {
+            let mut backend = backend(
+                abi,
+                CodegenOptions {
+                    tape_dump: TapeDump::Signal,
+                    ..TEST_OPTIONS
+                },
+            );
+            // The handler can run between any two instructions, so cells are never cached
+            assert_eq!(instructions(Syntax::Att, &backend.add(1, 0)), [format!("addb $1, {}", cell)]);
+            assert_eq!(instructions(Syntax::Att, &backend.add(-2, 0)), [format!("subb $2, {}", cell)]);
+            assert!(instructions(Syntax::Att, &backend.prologue()).contains(&String::from(rt_sigaction)));
+            assert!(instructions(Syntax::Att, &backend.epilogue())
+                .contains(&String::from("DUMP_SIGNAL_HANDLER:")));
+            assert!(printed(&backend.data_section(false, &[])).contains(DUMP_SIGACTION_MEMORY));
+        }
+    }
+
+    #[test]
+    fn signal_handler_finds_the_cell_ptr_in_the_ucontext() {
+        let x86_64 = instructions(Syntax::Att, &asm_signal_handler(&X86_64_LINUX));
+        assert_eq!(x86_64[..3], ["DUMP_SIGNAL_HANDLER:", "movq 128(%rdx), %rbx", "call DUMP_TAPE"]);
+
+        // Syscalls take the cell_ptr of 32-bit code, kept in '%edi' meanwhile
+        let i386 = instructions(Syntax::Att, &asm_signal_handler(&I386_LINUX));
+        assert_eq!(i386[1..3], ["movl 12(%esp), %eax", "movl 56(%eax), %edx"]);
+        assert!(i386.contains(&String::from("movl 36(%eax), %edx")));
+    }
+
+    #[test]
+    fn generates_the_same_program_in_both_syntaxes() {
+        let program = "+[->+<]>.,";
+        let att = lines(Syntax::Att, &generate(program, Target::I386Linux));
+        let intel = lines(Syntax::Intel, &generate(program, Target::I386LinuxNasm));
+
+        assert_eq!(att.len(), intel.len());
+        assert!(intel.iter().all(|line| !line.contains('%') && !line.contains('$')));
+        assert_eq!(intel[0], "section .bss");
+        assert!(intel.contains(&"int 0x80".to_string()));
+    }
+}
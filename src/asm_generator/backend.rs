@@ -0,0 +1,201 @@
+use crate::asm_generator::asm::{AsmInstr, Syntax};
+
+/// Code generator for a target, driven by the [AsmContext](super::AsmContext) as it
+/// walks the syntax tree.
+///
+/// Every method gives the code for one piece of the program, the context takes care
+/// of putting the pieces in order.
+pub trait Backend {
+    /// Code placed before the data section, Eg = includes and declarations.
+    fn header(&mut self) -> Vec<AsmInstr> {
+        vec![]
+    }
+
+    /// The data section of the program.
+    ///
+    /// # Arguments
+    ///
+    /// * `used_stdin` - Whether the program reads input.
+    /// * `precomputed_output` - Output computed at compile time that gets printed.
+    fn data_section(&mut self, used_stdin: bool, precomputed_output: &[u8]) -> Vec<AsmInstr>;
+
+    /// The entry point of the program, setting up the tape and the cell pointer.
+    fn prologue(&mut self) -> Vec<AsmInstr>;
+
+    /// Ends the program.
+    fn epilogue(&mut self) -> Vec<AsmInstr>;
+
+    /// A comment describing the code that follows.
+    fn comment(&mut self, text: &str) -> Vec<AsmInstr> {
+        vec![AsmInstr::Comment(text.to_string())]
+    }
+
+    /// Moves the cell pointer by `amount` cells.
+    fn move_ptr(&mut self, amount: isize) -> Vec<AsmInstr>;
+
+    /// Adds `amount` to the cell `offset` cells away from the cell pointer.
+    fn add(&mut self, amount: isize, offset: isize) -> Vec<AsmInstr>;
+
+    /// Starts a loop, skipping it when the current cell is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Tells the loop apart from every other one, Eg = 'L0_C1' for the first
+    ///   loop at the top level.
+    fn loop_begin(&mut self, name: &str) -> Vec<AsmInstr>;
+
+    /// Ends the body of the loop with the given name, repeating it while the current
+    /// cell is not zero.
+    fn loop_end(&mut self, name: &str) -> Vec<AsmInstr>;
+
+    /// Reads a byte of input into the current cell.
+    fn input(&mut self) -> Vec<AsmInstr>;
+
+    /// Prints the current cell.
+    fn output(&mut self) -> Vec<AsmInstr>;
+
+    /// Sets the tape and the cell pointer to a state computed at compile time.
+    fn restore_snapshot(&mut self, tape: &[u8], cell_ptr: usize) -> Vec<AsmInstr>;
+
+    /// Prints output computed at compile time.
+    fn print_precomputed_output(&mut self, output: &[u8]) -> Vec<AsmInstr>;
+
+    /// Names the source file the [Backend::source_line] markers refer to, placed before
+    /// any code. Targets that are not assembly give nothing.
+    fn source_file(&mut self, path: &str) -> Vec<AsmInstr> {
+        vec![AsmInstr::SourceFile(path.to_string())]
+    }
+
+    /// Marks the code that follows as coming from `line` and `column` of the source
+    /// file, both counting from 1.
+    fn source_line(&mut self, line: usize, column: usize) -> Vec<AsmInstr> {
+        vec![AsmInstr::Loc { line, column }]
+    }
+
+    /// Debug information describing the program, placed after all of its code.
+    fn debug_info(&mut self, _source_path: &str) -> Vec<AsmInstr> {
+        vec![]
+    }
+
+    /// Syntax the instructions are printed in.
+    fn syntax(&self) -> Syntax {
+        Syntax::Att
+    }
+}
+
+/// Code generator for a target that is source code rather than assembly.
+///
+/// Mirrors [Backend] with every piece being text, which gets passed along as
+/// [AsmInstr::Text].
+pub trait TextBackend {
+    /// See [Backend::header].
+    fn header(&self) -> String {
+        String::new()
+    }
+
+    /// See [Backend::data_section].
+    fn data_section(&self, used_stdin: bool, precomputed_output: &[u8]) -> String;
+
+    /// See [Backend::prologue].
+    fn prologue(&self) -> String;
+
+    /// See [Backend::epilogue].
+    fn epilogue(&self) -> String;
+
+    /// See [Backend::comment].
+    fn comment(&self, text: &str) -> String;
+
+    /// See [Backend::move_ptr].
+    fn move_ptr(&self, amount: isize) -> String;
+
+    /// See [Backend::add].
+    fn add(&self, amount: isize, offset: isize) -> String;
+
+    /// See [Backend::loop_begin].
+    fn loop_begin(&self, name: &str) -> String;
+
+    /// See [Backend::loop_end].
+    fn loop_end(&self, name: &str) -> String;
+
+    /// See [Backend::input].
+    fn input(&self) -> String;
+
+    /// See [Backend::output].
+    fn output(&self) -> String;
+
+    /// See [Backend::restore_snapshot].
+    fn restore_snapshot(&self, tape: &[u8], cell_ptr: usize) -> String;
+
+    /// See [Backend::print_precomputed_output].
+    fn print_precomputed_output(&self, output: &[u8]) -> String;
+}
+
+/// Wraps the text in an instruction unless it is empty.
+fn text(code: String) -> Vec<AsmInstr> {
+    match code.is_empty() {
+        true => vec![],
+        false => vec![AsmInstr::Text(code)],
+    }
+}
+
+impl<T: TextBackend> Backend for T {
+    fn header(&mut self) -> Vec<AsmInstr> {
+        text(TextBackend::header(self))
+    }
+
+    fn data_section(&mut self, used_stdin: bool, precomputed_output: &[u8]) -> Vec<AsmInstr> {
+        text(TextBackend::data_section(self, used_stdin, precomputed_output))
+    }
+
+    fn prologue(&mut self) -> Vec<AsmInstr> {
+        text(TextBackend::prologue(self))
+    }
+
+    fn epilogue(&mut self) -> Vec<AsmInstr> {
+        text(TextBackend::epilogue(self))
+    }
+
+    fn comment(&mut self, comment: &str) -> Vec<AsmInstr> {
+        text(TextBackend::comment(self, comment))
+    }
+
+    fn move_ptr(&mut self, amount: isize) -> Vec<AsmInstr> {
+        text(TextBackend::move_ptr(self, amount))
+    }
+
+    fn add(&mut self, amount: isize, offset: isize) -> Vec<AsmInstr> {
+        text(TextBackend::add(self, amount, offset))
+    }
+
+    fn loop_begin(&mut self, name: &str) -> Vec<AsmInstr> {
+        text(TextBackend::loop_begin(self, name))
+    }
+
+    fn loop_end(&mut self, name: &str) -> Vec<AsmInstr> {
+        text(TextBackend::loop_end(self, name))
+    }
+
+    fn input(&mut self) -> Vec<AsmInstr> {
+        text(TextBackend::input(self))
+    }
+
+    fn output(&mut self) -> Vec<AsmInstr> {
+        text(TextBackend::output(self))
+    }
+
+    fn restore_snapshot(&mut self, tape: &[u8], cell_ptr: usize) -> Vec<AsmInstr> {
+        text(TextBackend::restore_snapshot(self, tape, cell_ptr))
+    }
+
+    fn print_precomputed_output(&mut self, output: &[u8]) -> Vec<AsmInstr> {
+        text(TextBackend::print_precomputed_output(self, output))
+    }
+
+    fn source_file(&mut self, _path: &str) -> Vec<AsmInstr> {
+        vec![]
+    }
+
+    fn source_line(&mut self, _line: usize, _column: usize) -> Vec<AsmInstr> {
+        vec![]
+    }
+}
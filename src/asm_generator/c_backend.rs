@@ -0,0 +1,249 @@
+use std::cell::Cell;
+
+use crate::asm_generator::backend::TextBackend;
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump};
+use crate::grammar::{PROMPT, TAPE_LEN};
+use crate::tape::{CELLS_PER_LINE, DUMP_HEADER};
+
+static CELL_MEMORY: &str = "tape";
+static CELL_PTR: &str = "p";
+static PRECOMPUTED_OUTPUT_MEMORY: &str = "precomputed_output";
+static DUMP_TAPE: &str = "dump_tape";
+
+/// Number of spaces each block indents its body by
+const INDENT_WIDTH: usize = 4;
+
+/// Generates a portable C program with `unsigned char` cells.
+///
+/// Like the assembly targets the tape holds [TAPE_LEN] cells, `,` prompts the user
+/// and leaves the cell untouched on end of file.
+pub struct CBackend {
+    /// What `main` returns and whether it dumps the tape
+    options: CodegenOptions,
+    /// Number of loops the code being generated is in
+    depth: Cell<usize>,
+}
+
+impl CBackend {
+    /// Creates a new `CBackend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - What `main` returns and whether it dumps the tape.
+    pub fn new(options: CodegenOptions) -> Self {
+        CBackend {
+            options,
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Indents a statement to the current block of `main`.
+    fn statement(&self, code: &str) -> String {
+        format!("{}{}\n", " ".repeat((self.depth.get() + 1) * INDENT_WIDTH), code)
+    }
+}
+
+impl TextBackend for CBackend {
+    fn header(&self) -> String {
+        String::from("#include <stdio.h>\n\n")
+    }
+
+    fn data_section(&self, _used_stdin: bool, precomputed_output: &[u8]) -> String {
+        let mut data = format!("static unsigned char {}[{}];\n", CELL_MEMORY, TAPE_LEN);
+
+        if !precomputed_output.is_empty() {
+            let bytes = precomputed_output
+                .chunks(16)
+                .map(|chunk| {
+                    let chunk: Vec<String> = chunk.iter().map(|byte| byte.to_string()).collect();
+                    format!("    {},\n", chunk.join(", "))
+                })
+                .collect::<String>();
+            data.push_str(&format!(
+                "\n/* Output computed at compile time */\nstatic const unsigned char {}[] = {{\n{}}};\n",
+                PRECOMPUTED_OUTPUT_MEMORY, bytes
+            ));
+        }
+        if self.options.tape_dump != TapeDump::Off {
+            data.push_str(&format!(
+                r#"
+/* Writes the pointer and the used cells of the tape to stderr */
+static void {dump}(const unsigned char *{p}) {{
+    int last = {last};
+    int i;
+    while (last > {p} - {tape} && !{tape}[last])
+        last--;
+    fprintf(stderr, "{header}%04x", (unsigned)({p} - {tape}));
+    for (i = 0; i <= last; i++) {{
+        if (i % {per_line} == 0)
+            fprintf(stderr, "\n%04x:", i);
+        fprintf(stderr, " %02x", {tape}[i]);
+    }}
+    fputc('\n', stderr);
+}}
+"#,
+                dump = DUMP_TAPE,
+                p = CELL_PTR,
+                tape = CELL_MEMORY,
+                last = TAPE_LEN - 1,
+                header = DUMP_HEADER,
+                per_line = CELLS_PER_LINE,
+            ));
+        }
+        data
+    }
+
+    fn prologue(&self) -> String {
+        format!(
+            "\nint main(void) {{\n{}",
+            self.statement(&format!("unsigned char *{} = {};", CELL_PTR, CELL_MEMORY))
+        )
+    }
+
+    fn epilogue(&self) -> String {
+        let dump = match self.options.tape_dump {
+            TapeDump::Off => String::new(),
+            _ => [
+                self.statement("fflush(stdout);"),
+                self.statement(&format!("{}({});", DUMP_TAPE, CELL_PTR)),
+            ]
+            .concat(),
+        };
+        match self.options.exit_code {
+            ExitCode::Zero => format!(
+                "\n{}{}{}}}\n",
+                dump,
+                // Programs evaluated at compile time never read the pointer again
+                self.statement(&format!("(void){};", CELL_PTR)),
+                self.statement("return 0;")
+            ),
+            ExitCode::Cell => format!(
+                "\n{}{}}}\n",
+                dump,
+                self.statement(&format!("return *{};", CELL_PTR))
+            ),
+        }
+    }
+
+    fn comment(&self, text: &str) -> String {
+        // The text may be a line of the source, which can hold anything
+        self.statement(&format!("/* {} */", text.replace("*/", "* /")))
+    }
+
+    fn move_ptr(&self, amount: isize) -> String {
+        match amount {
+            _ if amount < 0 => self.statement(&format!("{} -= {};", CELL_PTR, -amount)),
+            _ => self.statement(&format!("{} += {};", CELL_PTR, amount)),
+        }
+    }
+
+    fn add(&self, amount: isize, offset: isize) -> String {
+        // Cells wrap around so only the low byte of the amount matters
+        let (op, amount) = match amount {
+            _ if amount < 0 => ("-=", amount.unsigned_abs() as u8),
+            _ => ("+=", amount as u8),
+        };
+        self.statement(&format!("{}[{}] {} {};", CELL_PTR, offset, op, amount))
+    }
+
+    fn loop_begin(&self, _name: &str) -> String {
+        let code = self.statement(&format!("while (*{}) {{", CELL_PTR));
+        self.depth.set(self.depth.get() + 1);
+        code
+    }
+
+    fn loop_end(&self, _name: &str) -> String {
+        self.depth.set(self.depth.get() - 1);
+        self.statement("}")
+    }
+
+    fn input(&self) -> String {
+        [
+            self.statement("{"),
+            self.statement("    int c;"),
+            self.statement(&format!("    fputs(\"{}\", stdout);", PROMPT)),
+            self.statement("    fflush(stdout);"),
+            self.statement("    c = getchar();"),
+            self.statement("    if (c != EOF)"),
+            self.statement(&format!("        *{} = (unsigned char)c;", CELL_PTR)),
+            self.statement("}"),
+        ]
+        .concat()
+    }
+
+    fn output(&self) -> String {
+        self.statement(&format!("putchar(*{});", CELL_PTR))
+    }
+
+    fn restore_snapshot(&self, tape: &[u8], cell_ptr: usize) -> String {
+        let mut code = self.statement("/* Restore the state computed at compile time */");
+        tape.iter()
+            .enumerate()
+            .filter(|(_, &cell)| cell != 0)
+            .for_each(|(idx, cell)| {
+                code.push_str(&self.statement(&format!("{}[{}] = {};", CELL_MEMORY, idx, cell)))
+            });
+        code.push_str(&self.statement(&format!(
+            "{} = {} + {};",
+            CELL_PTR, CELL_MEMORY, cell_ptr
+        )));
+        code
+    }
+
+    fn print_precomputed_output(&self, output: &[u8]) -> String {
+        self.statement(&format!(
+            "fwrite({}, 1, {}, stdout);",
+            PRECOMPUTED_OUTPUT_MEMORY,
+            output.len()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm_generator::{generate, Target};
+
+    /// Keeps the statements of `main`, with their indentation
+    fn body(code: &str) -> Vec<&str> {
+        let start = code.find("int main(void) {\n").unwrap();
+        code[start..]
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim_start().starts_with("/*"))
+            .collect()
+    }
+
+    #[test]
+    fn nests_loops_as_blocks() {
+        assert_eq!(
+            body(&generate("+[->[-]<]>.", Target::C)),
+            [
+                "    unsigned char *p = tape;",
+                "    p[0] += 1;",
+                "    while (*p) {",
+                "        p[0] -= 1;",
+                "        p += 1;",
+                "        while (*p) {",
+                "            p[0] -= 1;",
+                "        }",
+                "        p -= 1;",
+                "    }",
+                "    p += 1;",
+                "    putchar(*p);",
+                "",
+                "    (void)p;",
+                "    return 0;",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_the_cell_untouched_on_end_of_file() {
+        let code = generate(",", Target::C);
+        assert!(code.starts_with("#include <stdio.h>\n\nstatic unsigned char tape[256];\n"));
+        assert!(code.contains(
+            "        c = getchar();\n        if (c != EOF)\n            *p = (unsigned char)c;\n"
+        ));
+    }
+}
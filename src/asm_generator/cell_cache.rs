@@ -0,0 +1,72 @@
+use std::mem;
+
+use crate::asm_generator::asm::{AsmInstr, BinaryOp, Memory, Operand, Register, Size};
+
+/// Keeps the current cell in a byte register across straight-line code.
+///
+/// Consecutive changes to the current cell then need a single load and a single
+/// store. The backends [flush](CellCache::flush) it before anything reading the cell
+/// from memory or moving the cell pointer, Eg = loops, pointer moves and I/O.
+pub struct CellCache {
+    /// Byte register holding the cell
+    register: Register,
+    /// Memory of the current cell
+    cell: Memory,
+    /// Whether the register holds the cell, changed since it was loaded
+    cached: bool,
+}
+
+impl CellCache {
+    /// Creates an empty `CellCache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `register` - The byte register to keep the cell in.
+    /// * `cell` - The memory of the current cell.
+    pub fn new(register: Register, cell: Memory) -> Self {
+        CellCache {
+            register,
+            cell,
+            cached: false,
+        }
+    }
+
+    /// Adds to the current cell in the register, loading it first if it is not there yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Either [BinaryOp::Add] or [BinaryOp::Sub].
+    /// * `amount` - The amount to add or subtract, cells wrapping around.
+    pub fn modify(&mut self, op: BinaryOp, amount: u8) -> Vec<AsmInstr> {
+        let mut code = vec![];
+        if !self.cached {
+            code.push(AsmInstr::binary(
+                BinaryOp::Mov,
+                Size::Byte,
+                Operand::Reg(self.register),
+                Operand::Mem(self.cell.clone()),
+            ));
+        }
+        code.push(AsmInstr::binary(
+            op,
+            Size::Byte,
+            Operand::Reg(self.register),
+            Operand::Imm(amount as i64),
+        ));
+        self.cached = true;
+        code
+    }
+
+    /// Stores the register back to the cell and forgets it.
+    pub fn flush(&mut self) -> Vec<AsmInstr> {
+        match mem::replace(&mut self.cached, false) {
+            true => vec![AsmInstr::binary(
+                BinaryOp::Mov,
+                Size::Byte,
+                Operand::Mem(self.cell.clone()),
+                Operand::Reg(self.register),
+            )],
+            false => vec![],
+        }
+    }
+}
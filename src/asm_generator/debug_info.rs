@@ -0,0 +1,246 @@
+use std::env;
+
+use crate::asm_generator::asm::printer::escape;
+use crate::asm_generator::asm::{AsmInstr, Data, Section, Size};
+use crate::grammar::TAPE_LEN;
+
+static CELL_MEMORY: &str = "array";
+static ENTRY: &str = "_start";
+static TAPE_NAME: &str = "tape";
+static CELL_NAME: &str = "cell";
+static PRODUCER: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+static TEXT_END: &str = ".Ltext_end";
+static LINE_TABLE: &str = ".Ldebug_line0";
+static ABBREV_TABLE: &str = ".Ldebug_abbrev0";
+static INFO_START: &str = ".Ldebug_info0";
+static INFO_END: &str = ".Ldebug_info_end";
+static CELL_TYPE: &str = ".Ldebug_cell";
+static TAPE_TYPE: &str = ".Ldebug_tape";
+
+/// Version of the `.debug_info` written, the last one without a string table
+const DWARF_VERSION: u16 = 4;
+
+// ------------------- [ DWARF ] ------------------- \\
+
+const DW_TAG_ARRAY_TYPE: u8 = 0x01;
+const DW_TAG_COMPILE_UNIT: u8 = 0x11;
+const DW_TAG_SUBRANGE_TYPE: u8 = 0x21;
+const DW_TAG_BASE_TYPE: u8 = 0x24;
+const DW_TAG_SUBPROGRAM: u8 = 0x2e;
+const DW_TAG_VARIABLE: u8 = 0x34;
+
+const DW_AT_LOCATION: u8 = 0x02;
+const DW_AT_NAME: u8 = 0x03;
+const DW_AT_BYTE_SIZE: u8 = 0x0b;
+const DW_AT_STMT_LIST: u8 = 0x10;
+const DW_AT_LOW_PC: u8 = 0x11;
+const DW_AT_HIGH_PC: u8 = 0x12;
+const DW_AT_LANGUAGE: u8 = 0x13;
+const DW_AT_COMP_DIR: u8 = 0x1b;
+const DW_AT_PRODUCER: u8 = 0x25;
+const DW_AT_UPPER_BOUND: u8 = 0x2f;
+const DW_AT_ENCODING: u8 = 0x3e;
+const DW_AT_EXTERNAL: u8 = 0x3f;
+const DW_AT_TYPE: u8 = 0x49;
+
+const DW_FORM_ADDR: u8 = 0x01;
+const DW_FORM_DATA2: u8 = 0x05;
+const DW_FORM_STRING: u8 = 0x08;
+const DW_FORM_DATA1: u8 = 0x0b;
+const DW_FORM_REF4: u8 = 0x13;
+const DW_FORM_SEC_OFFSET: u8 = 0x17;
+const DW_FORM_EXPRLOC: u8 = 0x18;
+const DW_FORM_FLAG_PRESENT: u8 = 0x19;
+
+const DW_CHILDREN_NO: u8 = 0;
+const DW_CHILDREN_YES: u8 = 1;
+/// Brain FK has no language code, C lets `gdb` take expressions like `tape[3]`
+const DW_LANG_C99: u8 = 0x0c;
+/// Cells print as numbers rather than characters
+const DW_ATE_UNSIGNED: u8 = 0x07;
+const DW_OP_ADDR: u8 = 0x03;
+
+/// Codes of the layouts in `.debug_abbrev`, one per kind of entry in `.debug_info`
+const ABBREV_COMPILE_UNIT: u8 = 1;
+const ABBREV_ENTRY: u8 = 2;
+const ABBREV_CELL: u8 = 3;
+const ABBREV_TAPE_TYPE: u8 = 4;
+const ABBREV_TAPE_RANGE: u8 = 5;
+const ABBREV_TAPE: u8 = 6;
+
+// ------------------- [ DWARF END ] ------------------- \\
+
+/// Shorthand for data without a label
+fn data(data: Data) -> AsmInstr {
+    AsmInstr::Data {
+        label: String::new(),
+        data,
+    }
+}
+
+/// Shorthand for a few bytes without a label
+fn bytes(bytes: &[u8]) -> AsmInstr {
+    data(Data::Bytes(bytes.to_vec()))
+}
+
+/// Describes the layout of the entries of a kind in `.debug_abbrev`.
+///
+/// Every code, tag, attribute and form is below 128, so each takes a single byte of
+/// LEB128.
+///
+/// # Arguments
+///
+/// * `code` - The code the entries refer to the layout by.
+/// * `tag` - What the entries describe.
+/// * `children` - Whether the entries are followed by nested ones.
+/// * `attributes` - The attributes of the entries along with their forms.
+fn abbrev(code: u8, tag: u8, children: u8, attributes: &[(u8, u8)]) -> AsmInstr {
+    let mut layout = vec![code, tag, children];
+    for &(attribute, form) in attributes {
+        layout.extend([attribute, form]);
+    }
+    layout.extend([0, 0]);
+    bytes(&layout)
+}
+
+/// The layouts of the entries written by [debug_sections]
+fn debug_abbrev() -> Vec<AsmInstr> {
+    vec![
+        AsmInstr::Section(Section::DebugAbbrev),
+        AsmInstr::Label(ABBREV_TABLE.to_string()),
+        abbrev(
+            ABBREV_COMPILE_UNIT,
+            DW_TAG_COMPILE_UNIT,
+            DW_CHILDREN_YES,
+            &[
+                (DW_AT_PRODUCER, DW_FORM_STRING),
+                (DW_AT_LANGUAGE, DW_FORM_DATA1),
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_COMP_DIR, DW_FORM_STRING),
+                (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_ADDR),
+            ],
+        ),
+        abbrev(
+            ABBREV_ENTRY,
+            DW_TAG_SUBPROGRAM,
+            DW_CHILDREN_NO,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_EXTERNAL, DW_FORM_FLAG_PRESENT),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_ADDR),
+            ],
+        ),
+        abbrev(
+            ABBREV_CELL,
+            DW_TAG_BASE_TYPE,
+            DW_CHILDREN_NO,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+                (DW_AT_ENCODING, DW_FORM_DATA1),
+            ],
+        ),
+        abbrev(
+            ABBREV_TAPE_TYPE,
+            DW_TAG_ARRAY_TYPE,
+            DW_CHILDREN_YES,
+            &[(DW_AT_TYPE, DW_FORM_REF4)],
+        ),
+        abbrev(
+            ABBREV_TAPE_RANGE,
+            DW_TAG_SUBRANGE_TYPE,
+            DW_CHILDREN_NO,
+            &[(DW_AT_UPPER_BOUND, DW_FORM_DATA2)],
+        ),
+        abbrev(
+            ABBREV_TAPE,
+            DW_TAG_VARIABLE,
+            DW_CHILDREN_NO,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_TYPE, DW_FORM_REF4),
+                (DW_AT_EXTERNAL, DW_FORM_FLAG_PRESENT),
+                (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+            ],
+        ),
+        bytes(&[0]),
+    ]
+}
+
+/// Gives the debug information describing the program, placed after all of its code.
+///
+/// The assembler builds the line table from the [AsmInstr::Loc] directives, this adds
+/// the compile unit pointing at it along with the entry point and the tape as an array
+/// of cells, so `gdb` can step through the source and `print tape`.
+///
+/// # Arguments
+///
+/// * `source_path` - The path of the Brain FK program.
+/// * `address_size` - The size of an address on the target.
+///
+/// # Returns
+///
+/// The instructions ending the code and describing it.
+pub fn debug_sections(source_path: &str, address_size: Size) -> Vec<AsmInstr> {
+    let address_len: u8 = match address_size {
+        Size::Qword => 8,
+        _ => 4,
+    };
+    let address = |label: &str| data(Data::Expr(address_size, label.to_string()));
+    let reference = |label: &str| data(Data::Expr(Size::Dword, format!("{} - {}", label, INFO_START)));
+    let string = |text: &str| data(Data::Asciz(escape(text)));
+    let comp_dir = env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default();
+
+    let mut code = vec![
+        // The compile unit covers the code up to here
+        AsmInstr::Section(Section::Text),
+        AsmInstr::Label(TEXT_END.to_string()),
+        // The assembler appends the line table after the label
+        AsmInstr::Section(Section::DebugLine),
+        AsmInstr::Label(LINE_TABLE.to_string()),
+        AsmInstr::Section(Section::DebugInfo),
+        AsmInstr::Label(INFO_START.to_string()),
+        data(Data::Expr(Size::Dword, format!("{} - {} - 4", INFO_END, INFO_START))),
+        bytes(&DWARF_VERSION.to_le_bytes()),
+        data(Data::Expr(Size::Dword, ABBREV_TABLE.to_string())),
+        bytes(&[address_len]),
+        bytes(&[ABBREV_COMPILE_UNIT]),
+        string(PRODUCER),
+        bytes(&[DW_LANG_C99]),
+        string(source_path),
+        string(&comp_dir),
+        data(Data::Expr(Size::Dword, LINE_TABLE.to_string())),
+        address(ENTRY),
+        address(TEXT_END),
+        bytes(&[ABBREV_ENTRY]),
+        string(ENTRY),
+        address(ENTRY),
+        address(TEXT_END),
+        AsmInstr::Label(CELL_TYPE.to_string()),
+        bytes(&[ABBREV_CELL]),
+        string(CELL_NAME),
+        bytes(&[1, DW_ATE_UNSIGNED]),
+        AsmInstr::Label(TAPE_TYPE.to_string()),
+        bytes(&[ABBREV_TAPE_TYPE]),
+        reference(CELL_TYPE),
+        bytes(&[ABBREV_TAPE_RANGE]),
+        bytes(&(TAPE_LEN as u16 - 1).to_le_bytes()),
+        // End of the children of the array
+        bytes(&[0]),
+        bytes(&[ABBREV_TAPE]),
+        string(TAPE_NAME),
+        reference(TAPE_TYPE),
+        bytes(&[1 + address_len, DW_OP_ADDR]),
+        address(CELL_MEMORY),
+        // End of the children of the compile unit
+        bytes(&[0]),
+        AsmInstr::Label(INFO_END.to_string()),
+    ];
+    code.extend(debug_abbrev());
+    code
+}
@@ -0,0 +1,287 @@
+use std::fs::{File, Permissions};
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+
+use colored::Colorize;
+
+use crate::asm_generator::x86_64_encoder::{Environment, Symbol, X86_64Encoder};
+use crate::asm_generator::CodegenOptions;
+use crate::grammar::Expression;
+
+/// Address the executable is loaded at
+const BASE_ADDR: usize = 0x400000;
+/// Alignment of the segments in memory
+const PAGE_SIZE: usize = 0x1000;
+/// Size of the ELF64 file header
+const ELF_HEADER_SIZE: usize = 64;
+/// Size of an ELF64 program header
+const PROGRAM_HEADER_SIZE: usize = 56;
+/// Number of program headers, one segment for the code and one for the tape
+const NUM_OF_PROGRAM_HEADERS: usize = 2;
+
+/// Permissions of a segment
+enum SegmentFlags {
+    ReadExecute = 0b101,
+    ReadWrite = 0b110,
+}
+
+/// Writes the syntax tree as a static x86-64 Linux ELF executable, without going
+/// through an assembler or a linker.
+///
+/// The file holds a read only segment with the headers, the code and its constants
+/// followed by a zero filled segment for the tape and the memory the tape dump uses.
+///
+/// # Arguments
+///
+/// * `syntax_tree` - The optimized program.
+/// * `file_path` - Path of the executable to create.
+/// * `options` - Runtime behaviour of the executable.
+pub fn write_executable(syntax_tree: &[Expression], file_path: &str, options: CodegenOptions) {
+    match write_to_file(&encode_executable(syntax_tree, options), file_path) {
+        Ok(_) => {
+            eprintln!("{}", "The executable was successfully generated".green());
+        }
+        Err(error) => {
+            eprintln!("{}", "Could not generate the executable".red());
+            eprintln!("{}", error)
+        }
+    };
+}
+
+/// Encodes the syntax tree as the bytes of a static x86-64 Linux ELF executable, for
+/// writing it somewhere else than a file, Eg = stdout.
+///
+/// # Arguments
+///
+/// * `syntax_tree` - The optimized program.
+/// * `options` - Runtime behaviour of the executable.
+///
+/// # Returns
+///
+/// The bytes of the executable.
+pub fn encode_executable(syntax_tree: &[Expression], options: CodegenOptions) -> Vec<u8> {
+    link(&X86_64Encoder::encode(syntax_tree, Environment::Executable(options)))
+}
+
+/// Lays the program out in memory, fills in the addresses of the code and puts the
+/// headers in front of it.
+///
+/// # Returns
+///
+/// The bytes of the executable.
+fn link(encoder: &X86_64Encoder) -> Vec<u8> {
+    let code_offset = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE * NUM_OF_PROGRAM_HEADERS;
+    let mut code = encoder.code().to_vec();
+
+    // Constants go right after the code
+    let mut data: Vec<u8> = vec![];
+    let mut symbol_addr = |symbol: Symbol| match encoder.symbol_data(symbol) {
+        Some(bytes) => {
+            let addr = BASE_ADDR + code_offset + code.len() + data.len();
+            data.extend_from_slice(bytes);
+            addr
+        }
+        None => 0,
+    };
+    let prompt_addr = symbol_addr(Symbol::Prompt);
+    let precomputed_output_addr = symbol_addr(Symbol::PrecomputedOutput);
+    let dump_header_addr = symbol_addr(Symbol::DumpHeader);
+    let hex_digits_addr = symbol_addr(Symbol::HexDigits);
+
+    // The tape starts on the page after everything from the file, followed by the
+    // rest of the zeroed memory
+    let file_len = code_offset + code.len() + data.len();
+    let tape_addr = (BASE_ADDR + file_len).next_multiple_of(PAGE_SIZE);
+    let mut zeroed_len = 0;
+    let mut zeroed_addr = |symbol: Symbol| {
+        let addr = tape_addr + zeroed_len;
+        zeroed_len += encoder.zeroed_len(symbol);
+        addr
+    };
+    zeroed_addr(Symbol::Tape);
+    let sig_action_addr = zeroed_addr(Symbol::SigAction);
+    let dump_buffer_addr = zeroed_addr(Symbol::DumpBuffer);
+
+    for fixup in encoder.fixups() {
+        let addr = match fixup.symbol {
+            Symbol::Tape => tape_addr,
+            Symbol::Prompt => prompt_addr,
+            Symbol::PrecomputedOutput => precomputed_output_addr,
+            Symbol::DumpHeader => dump_header_addr,
+            Symbol::HexDigits => hex_digits_addr,
+            Symbol::SigAction => sig_action_addr,
+            Symbol::DumpBuffer => dump_buffer_addr,
+        } + fixup.addend;
+        code[fixup.position..fixup.position + 4].copy_from_slice(&(addr as u32).to_le_bytes());
+    }
+
+    let mut executable = elf_header(BASE_ADDR + code_offset);
+    executable.extend(program_header(
+        SegmentFlags::ReadExecute,
+        0,
+        BASE_ADDR,
+        file_len,
+        file_len,
+    ));
+    executable.extend(program_header(SegmentFlags::ReadWrite, 0, tape_addr, 0, zeroed_len));
+    executable.extend(code);
+    executable.extend(data);
+    executable
+}
+
+/// Gives the ELF64 file header of an x86-64 executable starting at `entry`
+fn elf_header(entry: usize) -> Vec<u8> {
+    let mut header = vec![
+        0x7F, b'E', b'L', b'F', // Magic number
+        2,                      // 64-bit
+        1,                      // Little endian
+        1,                      // ELF version
+        0,                      // System V ABI
+    ];
+    header.extend_from_slice(&[0; 8]); // Padding
+    header.extend_from_slice(&2u16.to_le_bytes()); // Executable file
+    header.extend_from_slice(&0x3Eu16.to_le_bytes()); // x86-64
+    header.extend_from_slice(&1u32.to_le_bytes()); // ELF version
+    header.extend_from_slice(&(entry as u64).to_le_bytes());
+    header.extend_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes()); // Program headers
+    header.extend_from_slice(&0u64.to_le_bytes()); // No section headers
+    header.extend_from_slice(&0u32.to_le_bytes()); // Flags
+    header.extend_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+    header.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    header.extend_from_slice(&(NUM_OF_PROGRAM_HEADERS as u16).to_le_bytes());
+    header.extend_from_slice(&[0; 6]); // Section header size, count and name index
+    header
+}
+
+/// Gives the ELF64 program header of a loadable segment
+fn program_header(
+    flags: SegmentFlags,
+    offset: usize,
+    addr: usize,
+    file_size: usize,
+    mem_size: usize,
+) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&1u32.to_le_bytes()); // Loadable segment
+    header.extend_from_slice(&(flags as u32).to_le_bytes());
+    header.extend_from_slice(&(offset as u64).to_le_bytes());
+    header.extend_from_slice(&(addr as u64).to_le_bytes()); // Virtual address
+    header.extend_from_slice(&(addr as u64).to_le_bytes()); // Physical address
+    header.extend_from_slice(&(file_size as u64).to_le_bytes());
+    header.extend_from_slice(&(mem_size as u64).to_le_bytes());
+    header.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+    header
+}
+
+/// Writes the executable and lets everyone run it.
+fn write_to_file(executable: &[u8], file_path: &str) -> Result<(), io::Error> {
+    let mut file = File::create(file_path)?;
+    file.write_all(executable)?;
+    file.set_permissions(Permissions::from_mode(0o755))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm_generator::{ExitCode, TapeDump, TEST_OPTIONS};
+    use crate::grammar::TAPE_LEN;
+    use crate::optimizer::PassManager;
+    use crate::parser::Parser;
+
+    /// Offset of the code in the executable
+    const CODE_OFFSET: usize = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE * NUM_OF_PROGRAM_HEADERS;
+
+    /// Links a program without running any optimization pass over it
+    fn build(program: &str) -> (X86_64Encoder, Vec<u8>) {
+        let mut parser = Parser::new(program.to_string(), PassManager::with_level(0));
+        parser.generate_syntax_tree();
+        let encoder = X86_64Encoder::encode(parser.get_ast().unwrap(), Environment::Executable(TEST_OPTIONS));
+        let executable = link(&encoder);
+        (encoder, executable)
+    }
+
+    fn u16_at(bytes: &[u8], at: usize) -> usize {
+        u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    fn u64_at(bytes: &[u8], at: usize) -> usize {
+        u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn writes_the_headers() {
+        let (_, executable) = build("+.");
+        let tape_header = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE;
+
+        assert_eq!(executable[..4], [0x7F, b'E', b'L', b'F']);
+        // e_entry and e_phnum
+        assert_eq!(u64_at(&executable, 24), BASE_ADDR + CODE_OFFSET);
+        assert_eq!(u16_at(&executable, 56), 2);
+        // p_filesz and p_memsz of the code segment
+        assert_eq!(u64_at(&executable, ELF_HEADER_SIZE + 32), executable.len());
+        assert_eq!(u64_at(&executable, ELF_HEADER_SIZE + 40), executable.len());
+        // p_filesz and p_memsz of the tape segment
+        assert_eq!(u64_at(&executable, tape_header + 32), 0);
+        assert_eq!(u64_at(&executable, tape_header + 40), TAPE_LEN);
+    }
+
+    #[test]
+    fn resolves_every_fixup() {
+        let (encoder, executable) = build(",>.");
+        let tape_addr = u64_at(&executable, ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE + 16);
+
+        assert_eq!(tape_addr % PAGE_SIZE, 0);
+        assert!(tape_addr >= BASE_ADDR + executable.len());
+        for fixup in encoder.fixups() {
+            let addr = u32_at(&executable, CODE_OFFSET + fixup.position);
+            match fixup.symbol {
+                Symbol::Tape => assert_eq!(addr, tape_addr + fixup.addend),
+                symbol => {
+                    let data = encoder.symbol_data(symbol).unwrap();
+                    let at = addr - BASE_ADDR;
+                    assert_eq!(&executable[at..at + data.len()], data);
+                }
+            }
+        }
+        assert!(encoder.fixups().iter().any(|fixup| fixup.symbol == Symbol::Prompt));
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn exits_with_the_current_cell() {
+        let path = std::env::temp_dir().join(format!("bf_exit_code_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        for (exit_code, status) in [(ExitCode::Zero, 0), (ExitCode::Cell, 2)] {
+            let syntax_tree = crate::optimizer::parse("+++>++<->");
+            write_to_file(&encode_executable(&syntax_tree, CodegenOptions { exit_code, ..TEST_OPTIONS }), path).unwrap();
+            let run = std::process::Command::new(path).status().unwrap();
+            assert_eq!(run.code(), Some(status), "Exit code {}", exit_code.name());
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn dumps_the_tape_to_stderr() {
+        let path = std::env::temp_dir().join(format!("bf_tape_dump_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        for tape_dump in [TapeDump::Exit, TapeDump::Signal] {
+            let syntax_tree = crate::optimizer::parse("+++>++<->>+++++++++++++++++[>+>-<<-]");
+            let options = CodegenOptions { tape_dump, ..TEST_OPTIONS };
+            write_to_file(&encode_executable(&syntax_tree, options), path).unwrap();
+            let run = std::process::Command::new(path).output().unwrap();
+            assert_eq!(
+                String::from_utf8(run.stderr).unwrap(),
+                "Cell pointer: 0002\n0000: 02 02 00 11 ef\n",
+                "Tape dump {}",
+                tape_dump.name()
+            );
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+use std::cell::Cell;
+
+use crate::asm_generator::backend::TextBackend;
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump};
+use crate::grammar::{PROMPT, TAPE_LEN};
+use crate::tape::{CELLS_PER_LINE, DUMP_HEADER};
+
+static CELL_MEMORY: &str = "tape";
+static CELL_PTR: &str = "p";
+static PROMPT_MEMORY: &str = "PROMPT";
+static PRECOMPUTED_OUTPUT_MEMORY: &str = "PRECOMPUTED_OUTPUT";
+
+/// Number of spaces each block indents its body by
+const INDENT_WIDTH: usize = 4;
+
+/// Generates an ES module running the program on a `Uint8Array` tape.
+///
+/// The module exports `run(input)` taking the whole input and giving back the whole
+/// output, which works in browsers and in Node alike, and runs it on stdin and stdout
+/// when started with `node`. Like the assembly targets `,` prompts the user and leaves
+/// the cell untouched on end of file. When exiting with the current cell `run` also
+/// leaves it in the exported `exitCode`, and when dumping the tape it leaves the dump in
+/// the exported `tapeDump`, which Node writes to stderr.
+pub struct JsBackend {
+    /// What Node exits with and whether it dumps the tape
+    options: CodegenOptions,
+    /// Number of loops the code being generated is in
+    depth: Cell<usize>,
+}
+
+impl JsBackend {
+    /// Creates a new `JsBackend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - What Node exits with and whether it dumps the tape.
+    pub fn new(options: CodegenOptions) -> Self {
+        JsBackend {
+            options,
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Indents a statement to the current block of `run`.
+    fn statement(&self, code: &str) -> String {
+        format!("{}{}\n", " ".repeat((self.depth.get() + 1) * INDENT_WIDTH), code)
+    }
+
+    /// Gives the cell `offset` cells away from the cell ptr
+    /// Eg = 'tape[p]', 'tape[p - 1]'
+    fn cell_at(offset: isize) -> String {
+        match offset {
+            0 => format!("{}[{}]", CELL_MEMORY, CELL_PTR),
+            _ if offset < 0 => format!("{}[{} - {}]", CELL_MEMORY, CELL_PTR, -offset),
+            _ => format!("{}[{} + {}]", CELL_MEMORY, CELL_PTR, offset),
+        }
+    }
+}
+
+impl TextBackend for JsBackend {
+    fn data_section(&self, used_stdin: bool, precomputed_output: &[u8]) -> String {
+        let mut data = String::new();
+
+        if used_stdin {
+            data.push_str(&format!(
+                "const {} = new TextEncoder().encode(\"{}\");\n\n",
+                PROMPT_MEMORY, PROMPT
+            ));
+        }
+        if !precomputed_output.is_empty() {
+            let bytes = precomputed_output
+                .chunks(16)
+                .map(|chunk| {
+                    let chunk: Vec<String> = chunk.iter().map(|byte| byte.to_string()).collect();
+                    format!("    {},\n", chunk.join(", "))
+                })
+                .collect::<String>();
+            data.push_str(&format!(
+                "/** Output computed at compile time */\nconst {} = new Uint8Array([\n{}]);\n\n",
+                PRECOMPUTED_OUTPUT_MEMORY, bytes
+            ));
+        }
+        data
+    }
+
+    fn prologue(&self) -> String {
+        let exit_code = match self.options.exit_code {
+            ExitCode::Zero => "",
+            ExitCode::Cell => "/** Current cell when `run` last returned */\nexport let exitCode = 0;\n\n",
+        };
+        let tape_dump = match self.options.tape_dump {
+            TapeDump::Off => "",
+            _ => "/** Pointer and used cells of the tape when `run` last returned */\nexport let tapeDump = \"\";\n\n",
+        };
+        format!(
+            "{}{}/**\n \
+             * Runs the program, reading `,` from `input` and giving back what `.` wrote.\n \
+             *\n \
+             * @param {{Uint8Array}} input\n \
+             * @returns {{Uint8Array}}\n \
+             */\n\
+             export function run(input) {{\n{}{}{}{}",
+            exit_code,
+            tape_dump,
+            self.statement(&format!("const {} = new Uint8Array({});", CELL_MEMORY, TAPE_LEN)),
+            self.statement(&format!("let {} = 0;", CELL_PTR)),
+            self.statement("let inputPos = 0;"),
+            self.statement("const output = [];")
+        )
+    }
+
+    fn epilogue(&self) -> String {
+        let (save_exit_code, set_exit_code) = match self.options.exit_code {
+            ExitCode::Zero => (String::new(), ""),
+            ExitCode::Cell => (
+                self.statement(&format!("exitCode = {};", Self::cell_at(0))),
+                "        process.exitCode = exitCode;\n",
+            ),
+        };
+        let (save_tape_dump, write_tape_dump) = match self.options.tape_dump {
+            TapeDump::Off => (String::new(), ""),
+            _ => (
+                [
+                    self.statement("// Leave the pointer and the used cells of the tape in `tapeDump`"),
+                    self.statement(&format!(
+                        "const last = Math.max({}.findLastIndex((cell) => cell !== 0), {});",
+                        CELL_MEMORY, CELL_PTR
+                    )),
+                    self.statement(&format!(
+                        "tapeDump = `{}${{{}.toString(16).padStart(4, \"0\")}}`;",
+                        DUMP_HEADER, CELL_PTR
+                    )),
+                    self.statement("for (let idx = 0; idx <= last; idx++) {"),
+                    self.statement(&format!("    if (idx % {} === 0) {{", CELLS_PER_LINE)),
+                    self.statement("        tapeDump += `\\n${idx.toString(16).padStart(4, \"0\")}:`;"),
+                    self.statement("    }"),
+                    self.statement(&format!(
+                        "    tapeDump += ` ${{{}[idx].toString(16).padStart(2, \"0\")}}`;",
+                        CELL_MEMORY
+                    )),
+                    self.statement("}"),
+                    self.statement("tapeDump += \"\\n\";"),
+                ]
+                .concat(),
+                "        process.stderr.write(tapeDump);\n",
+            ),
+        };
+        format!(
+            "\n{}{}{}}}\n\n\
+             // Runs the program on stdin and stdout when started with `node program.mjs`\n\
+             if (typeof process !== \"undefined\" && process.argv?.[1] !== undefined) {{\n    \
+             const {{ pathToFileURL }} = await import(\"node:url\");\n    \
+             if (import.meta.url === pathToFileURL(process.argv[1]).href) {{\n        \
+             const {{ readFileSync }} = await import(\"node:fs\");\n        \
+             process.stdout.write(run(readFileSync(0)));\n{}{}    \
+             }}\n\
+             }}\n",
+            save_exit_code,
+            save_tape_dump,
+            self.statement("return Uint8Array.from(output);"),
+            write_tape_dump,
+            set_exit_code
+        )
+    }
+
+    fn comment(&self, text: &str) -> String {
+        self.statement(&format!("// {}", text))
+    }
+
+    fn move_ptr(&self, amount: isize) -> String {
+        match amount {
+            _ if amount < 0 => self.statement(&format!("{} -= {};", CELL_PTR, -amount)),
+            _ => self.statement(&format!("{} += {};", CELL_PTR, amount)),
+        }
+    }
+
+    fn add(&self, amount: isize, offset: isize) -> String {
+        // Stores to a Uint8Array wrap around so only the low byte of the amount matters
+        let (op, amount) = match amount {
+            _ if amount < 0 => ("-=", amount.unsigned_abs() as u8),
+            _ => ("+=", amount as u8),
+        };
+        self.statement(&format!("{} {} {};", Self::cell_at(offset), op, amount))
+    }
+
+    fn loop_begin(&self, _name: &str) -> String {
+        let code = self.statement(&format!("while ({} !== 0) {{", Self::cell_at(0)));
+        self.depth.set(self.depth.get() + 1);
+        code
+    }
+
+    fn loop_end(&self, _name: &str) -> String {
+        self.depth.set(self.depth.get() - 1);
+        self.statement("}")
+    }
+
+    fn input(&self) -> String {
+        [
+            self.statement(&format!("output.push(...{});", PROMPT_MEMORY)),
+            self.statement("if (inputPos < input.length) {"),
+            self.statement(&format!("    {} = input[inputPos++];", Self::cell_at(0))),
+            self.statement("}"),
+        ]
+        .concat()
+    }
+
+    fn output(&self) -> String {
+        self.statement(&format!("output.push({});", Self::cell_at(0)))
+    }
+
+    fn restore_snapshot(&self, tape: &[u8], cell_ptr: usize) -> String {
+        let mut code = self.statement("// Restore the state computed at compile time");
+        tape.iter()
+            .enumerate()
+            .filter(|(_, &cell)| cell != 0)
+            .for_each(|(idx, cell)| {
+                code.push_str(&self.statement(&format!("{}[{}] = {};", CELL_MEMORY, idx, cell)))
+            });
+        code.push_str(&self.statement(&format!("{} = {};", CELL_PTR, cell_ptr)));
+        code
+    }
+
+    fn print_precomputed_output(&self, _output: &[u8]) -> String {
+        self.statement(&format!(
+            "for (const byte of {}) output.push(byte);",
+            PRECOMPUTED_OUTPUT_MEMORY
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm_generator::{generate, Target};
+
+    /// Keeps the statements of `run`, with their indentation
+    fn body(code: &str) -> Vec<&str> {
+        let start = code.find("export function run(input) {\n").unwrap();
+        code[start..]
+            .lines()
+            .skip(1)
+            .take_while(|line| *line != "}")
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .collect()
+    }
+
+    #[test]
+    fn nests_loops_as_blocks() {
+        assert_eq!(
+            body(&generate("-[>+<-]>.", Target::Js)),
+            [
+                "    const tape = new Uint8Array(256);",
+                "    let p = 0;",
+                "    let inputPos = 0;",
+                "    const output = [];",
+                "    tape[p] -= 1;",
+                "    while (tape[p] !== 0) {",
+                "        p += 1;",
+                "        tape[p] += 1;",
+                "        p -= 1;",
+                "        tape[p] -= 1;",
+                "    }",
+                "    p += 1;",
+                "    output.push(tape[p]);",
+                "",
+                "    return Uint8Array.from(output);",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_the_cell_untouched_on_end_of_file() {
+        assert!(generate(",", Target::Js).contains(
+            "    output.push(...PROMPT);\n    if (inputPos < input.length) {\n        tape[p] = input[inputPos++];\n    }\n"
+        ));
+    }
+}
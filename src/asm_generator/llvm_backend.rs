@@ -0,0 +1,430 @@
+use std::cell::Cell;
+
+use crate::asm_generator::backend::TextBackend;
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump};
+use crate::grammar::{PROMPT, TAPE_LEN};
+use crate::tape::{CELLS_PER_LINE, DUMP_HEADER};
+
+static CELL_MEMORY: &str = "@tape";
+static CELL_PTR: &str = "%p";
+static PROMPT_MEMORY: &str = "@input_prompt";
+static PRECOMPUTED_OUTPUT_MEMORY: &str = "@precomputed_output";
+static WRITE_BYTES: &str = "@write_bytes";
+static DUMP_TAPE: &str = "@dump_tape";
+static LOOP: &str = "loop";
+
+/// Generates textual LLVM IR (opaque pointers, LLVM 15 and later) using the C library
+/// for I/O.
+///
+/// The tape is a global array and the cell pointer lives in an `alloca`, leaving it to
+/// `mem2reg` to keep it in a register. Like the assembly targets `,` prompts the user
+/// and leaves the cell untouched on end of file.
+pub struct LlvmBackend {
+    /// What `main` returns and whether it dumps the tape
+    options: CodegenOptions,
+    /// Number of the next unnamed value or block
+    next_id: Cell<usize>,
+}
+
+impl LlvmBackend {
+    /// Creates a new `LlvmBackend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - What `main` returns and whether it dumps the tape.
+    pub fn new(options: CodegenOptions) -> Self {
+        LlvmBackend {
+            options,
+            next_id: Cell::new(0),
+        }
+    }
+
+    /// Gives a fresh name for a value or a block.
+    fn fresh(&self, prefix: &str) -> String {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        format!("{}{}", prefix, id)
+    }
+
+    /// Gives the name of a loop's block
+    /// Eg = 'loop_L0_C1.cond'
+    fn loop_block(name: &str, block: &str) -> String {
+        format!("{}_{}.{}", LOOP, name, block)
+    }
+
+    /// Loads the address of the cell `offset` cells away from the cell pointer.
+    ///
+    /// # Returns
+    ///
+    /// The code and the name of the value holding the address.
+    fn cell_at(&self, offset: isize) -> (String, String) {
+        let ptr = self.fresh("%ptr");
+        let mut code = format!("  {} = load ptr, ptr {}\n", ptr, CELL_PTR);
+        if offset == 0 {
+            return (code, ptr);
+        }
+
+        let cell = self.fresh("%cell");
+        code.push_str(&format!(
+            "  {} = getelementptr inbounds i8, ptr {}, i64 {}\n",
+            cell, ptr, offset
+        ));
+        (code, cell)
+    }
+
+    /// Gives the constant address of the cell at `idx` on the tape.
+    fn tape_at(idx: usize) -> String {
+        format!(
+            "getelementptr inbounds ([{} x i8], ptr {}, i64 0, i64 {})",
+            TAPE_LEN, CELL_MEMORY, idx
+        )
+    }
+
+    /// Writes the bytes as the contents of an LLVM string constant.
+    fn escape(bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .map(|&byte| match byte {
+                b' '..=b'~' if byte != b'"' && byte != b'\\' => (byte as char).to_string(),
+                _ => format!("\\{:02X}", byte),
+            })
+            .collect()
+    }
+}
+
+impl TextBackend for LlvmBackend {
+    fn header(&self) -> String {
+        let mut header = String::from(
+            "; Uses opaque pointers, needs LLVM 15 or later\n\n\
+             declare i32 @putchar(i32)\ndeclare i32 @getchar()\ndeclare i32 @fflush(ptr)\n",
+        );
+        if self.options.tape_dump != TapeDump::Off {
+            header.push_str("declare i32 @dprintf(i32, ptr, ...)\n");
+        }
+        header.push('\n');
+        header
+    }
+
+    fn data_section(&self, used_stdin: bool, precomputed_output: &[u8]) -> String {
+        let mut data = format!(
+            "{} = internal global [{} x i8] zeroinitializer\n",
+            CELL_MEMORY, TAPE_LEN
+        );
+
+        if used_stdin {
+            data.push_str(&format!(
+                "{} = private constant [{} x i8] c\"{}\"\n",
+                PROMPT_MEMORY,
+                PROMPT.len(),
+                Self::escape(PROMPT.as_bytes())
+            ));
+        }
+        if !precomputed_output.is_empty() {
+            data.push_str(&format!(
+                "; Output computed at compile time\n{} = private constant [{} x i8] c\"{}\"\n",
+                PRECOMPUTED_OUTPUT_MEMORY,
+                precomputed_output.len(),
+                Self::escape(precomputed_output)
+            ));
+        }
+
+        // Prints a buffer one byte at a time
+        data.push_str(&format!(
+            r#"
+define internal void {}(ptr %buf, i64 %len) {{
+entry:
+  br label %cond
+cond:
+  %idx = phi i64 [ 0, %entry ], [ %next, %body ]
+  %more = icmp ult i64 %idx, %len
+  br i1 %more, label %body, label %end
+body:
+  %addr = getelementptr inbounds i8, ptr %buf, i64 %idx
+  %byte = load i8, ptr %addr
+  %char = zext i8 %byte to i32
+  call i32 @putchar(i32 %char)
+  %next = add i64 %idx, 1
+  br label %cond
+end:
+  ret void
+}}
+"#,
+            WRITE_BYTES
+        ));
+
+        if self.options.tape_dump != TapeDump::Off {
+            let formats = [
+                ("@dump_header", format!("{}%04x\0", DUMP_HEADER)),
+                ("@dump_line", String::from("\n%04x:\0")),
+                ("@dump_cell", String::from(" %02x\0")),
+                ("@dump_end", String::from("\n\0")),
+            ];
+            formats.iter().for_each(|(name, format)| {
+                data.push_str(&format!(
+                    "{} = private constant [{} x i8] c\"{}\"\n",
+                    name,
+                    format.len(),
+                    Self::escape(format.as_bytes())
+                ))
+            });
+
+            // Writes the pointer and the used cells of the tape to stderr
+            data.push_str(&format!(
+                r#"
+define internal void {dump}(ptr %cur) {{
+entry:
+  %cur.int = ptrtoint ptr %cur to i64
+  %tape.int = ptrtoint ptr {tape} to i64
+  %ptr = sub i64 %cur.int, %tape.int
+  %ptr32 = trunc i64 %ptr to i32
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @dump_header, i32 %ptr32)
+  br label %scan
+scan:
+  %last = phi i64 [ {last}, %entry ], [ %prev, %scan.cell ]
+  %at.ptr = icmp ule i64 %last, %ptr
+  br i1 %at.ptr, label %found, label %scan.cell
+scan.cell:
+  %scan.addr = getelementptr inbounds i8, ptr {tape}, i64 %last
+  %scan.byte = load i8, ptr %scan.addr
+  %used = icmp ne i8 %scan.byte, 0
+  %prev = sub i64 %last, 1
+  br i1 %used, label %found, label %scan
+found:
+  %end = add i64 %last, 1
+  br label %cond
+cond:
+  %idx = phi i64 [ 0, %found ], [ %next, %cell ]
+  %more = icmp ult i64 %idx, %end
+  br i1 %more, label %body, label %done
+body:
+  %col = urem i64 %idx, {per_line}
+  %new.line = icmp eq i64 %col, 0
+  %idx32 = trunc i64 %idx to i32
+  br i1 %new.line, label %line, label %cell
+line:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @dump_line, i32 %idx32)
+  br label %cell
+cell:
+  %addr = getelementptr inbounds i8, ptr {tape}, i64 %idx
+  %byte = load i8, ptr %addr
+  %value = zext i8 %byte to i32
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @dump_cell, i32 %value)
+  %next = add i64 %idx, 1
+  br label %cond
+done:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @dump_end)
+  ret void
+}}
+"#,
+                dump = DUMP_TAPE,
+                tape = CELL_MEMORY,
+                last = TAPE_LEN - 1,
+                per_line = CELLS_PER_LINE,
+            ));
+        }
+        data
+    }
+
+    fn prologue(&self) -> String {
+        format!(
+            "\ndefine i32 @main() {{\nentry:\n  {} = alloca ptr\n  store ptr {}, ptr {}\n",
+            CELL_PTR, CELL_MEMORY, CELL_PTR
+        )
+    }
+
+    fn epilogue(&self) -> String {
+        let mut code = String::new();
+        if self.options.tape_dump != TapeDump::Off {
+            let (load, cell) = self.cell_at(0);
+            code.push_str(&format!(
+                "  call i32 @fflush(ptr null)\n{}  call void {}(ptr {})\n",
+                load, DUMP_TAPE, cell
+            ));
+        }
+        match self.options.exit_code {
+            ExitCode::Zero => code.push_str("  ret i32 0\n}\n"),
+            ExitCode::Cell => {
+                let (load, cell) = self.cell_at(0);
+                code.push_str(&load);
+                let value = self.fresh("%value");
+                let status = self.fresh("%status");
+                code.push_str(&format!(
+                    "  {} = load i8, ptr {}\n  {} = zext i8 {} to i32\n  ret i32 {}\n}}\n",
+                    value, cell, status, value, status
+                ));
+            }
+        }
+        code
+    }
+
+    fn comment(&self, text: &str) -> String {
+        format!("  ; {}\n", text)
+    }
+
+    fn move_ptr(&self, amount: isize) -> String {
+        let (mut code, cell) = self.cell_at(amount);
+        code.push_str(&format!("  store ptr {}, ptr {}\n", cell, CELL_PTR));
+        code
+    }
+
+    fn add(&self, amount: isize, offset: isize) -> String {
+        let (mut code, cell) = self.cell_at(offset);
+        let value = self.fresh("%value");
+        let sum = self.fresh("%sum");
+
+        // Cells wrap around so only the low byte of the amount matters
+        code.push_str(&format!(
+            "  {} = load i8, ptr {}\n  {} = add i8 {}, {}\n  store i8 {}, ptr {}\n",
+            value, cell,
+            sum, value, amount as u8,
+            sum, cell
+        ));
+        code
+    }
+
+    fn loop_begin(&self, name: &str) -> String {
+        let (load, cell) = self.cell_at(0);
+        let value = self.fresh("%value");
+        let is_zero = self.fresh("%is_zero");
+        let cond = Self::loop_block(name, "cond");
+
+        format!(
+            "  br label %{}\n{}:\n{}  {} = load i8, ptr {}\n  {} = icmp eq i8 {}, 0\n  br i1 {}, label %{}, label %{}\n{}:\n",
+            cond, cond,
+            load,
+            value, cell,
+            is_zero, value,
+            is_zero, Self::loop_block(name, "end"), Self::loop_block(name, "body"),
+            Self::loop_block(name, "body")
+        )
+    }
+
+    fn loop_end(&self, name: &str) -> String {
+        format!(
+            "  br label %{}\n{}:\n",
+            Self::loop_block(name, "cond"),
+            Self::loop_block(name, "end")
+        )
+    }
+
+    fn input(&self) -> String {
+        let (load, cell) = self.cell_at(0);
+        let char = self.fresh("%char");
+        let is_eof = self.fresh("%is_eof");
+        let byte = self.fresh("%byte");
+        let store = self.fresh("input.store");
+        let done = self.fresh("input.done");
+
+        format!(
+            "  call void {}(ptr {}, i64 {})\n  call i32 @fflush(ptr null)\n  {} = call i32 @getchar()\n  {} = icmp eq i32 {}, -1\n  br i1 {}, label %{}, label %{}\n{}:\n{}  {} = trunc i32 {} to i8\n  store i8 {}, ptr {}\n  br label %{}\n{}:\n",
+            WRITE_BYTES, PROMPT_MEMORY, PROMPT.len(),
+            char,
+            is_eof, char,
+            is_eof, done, store,
+            store,
+            load, byte, char,
+            byte, cell,
+            done,
+            done
+        )
+    }
+
+    fn output(&self) -> String {
+        let (mut code, cell) = self.cell_at(0);
+        let value = self.fresh("%value");
+        let char = self.fresh("%char");
+
+        code.push_str(&format!(
+            "  {} = load i8, ptr {}\n  {} = zext i8 {} to i32\n  call i32 @putchar(i32 {})\n",
+            value, cell,
+            char, value,
+            char
+        ));
+        code
+    }
+
+    fn restore_snapshot(&self, tape: &[u8], cell_ptr: usize) -> String {
+        let mut code = String::from("  ; Restore the state computed at compile time\n");
+        tape.iter()
+            .enumerate()
+            .filter(|(_, &cell)| cell != 0)
+            .for_each(|(idx, cell)| {
+                code.push_str(&format!("  store i8 {}, ptr {}\n", cell, Self::tape_at(idx)))
+            });
+        code.push_str(&format!(
+            "  store ptr {}, ptr {}\n",
+            Self::tape_at(cell_ptr),
+            CELL_PTR
+        ));
+        code
+    }
+
+    fn print_precomputed_output(&self, output: &[u8]) -> String {
+        format!(
+            "  call void {}(ptr {}, i64 {})\n",
+            WRITE_BYTES,
+            PRECOMPUTED_OUTPUT_MEMORY,
+            output.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm_generator::{generate, Target};
+
+    /// Keeps the instructions and labels of `main`, without comments
+    fn body(code: &str) -> Vec<&str> {
+        let start = code.find("define i32 @main() {\n").unwrap();
+        code[start..]
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim_start().starts_with(';'))
+            .collect()
+    }
+
+    #[test]
+    fn declares_the_llvm_version_it_needs() {
+        assert!(generate("+", Target::Llvm).starts_with("; Uses opaque pointers, needs LLVM 15"));
+    }
+
+    #[test]
+    fn branches_around_loops_and_wraps_cells() {
+        assert_eq!(
+            body(&generate("+[-]", Target::Llvm)),
+            [
+                "entry:",
+                "  %p = alloca ptr",
+                "  store ptr @tape, ptr %p",
+                "  %ptr0 = load ptr, ptr %p",
+                "  %value1 = load i8, ptr %ptr0",
+                "  %sum2 = add i8 %value1, 1",
+                "  store i8 %sum2, ptr %ptr0",
+                "  br label %loop_L0_C1.cond",
+                "loop_L0_C1.cond:",
+                "  %ptr3 = load ptr, ptr %p",
+                "  %value4 = load i8, ptr %ptr3",
+                "  %is_zero5 = icmp eq i8 %value4, 0",
+                "  br i1 %is_zero5, label %loop_L0_C1.end, label %loop_L0_C1.body",
+                "loop_L0_C1.body:",
+                "  %ptr6 = load ptr, ptr %p",
+                "  %value7 = load i8, ptr %ptr6",
+                "  %sum8 = add i8 %value7, 255",
+                "  store i8 %sum8, ptr %ptr6",
+                "  br label %loop_L0_C1.cond",
+                "loop_L0_C1.end:",
+                "  ret i32 0",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_the_cell_untouched_on_end_of_file() {
+        let code = generate(",", Target::Llvm);
+        assert!(code.contains("@input_prompt = private constant [19 x i8] c\"Enter a character: \"\n"));
+        assert!(code.contains(
+            "  %is_eof2 = icmp eq i32 %char1, -1\n  br i1 %is_eof2, label %input.done5, label %input.store4\n"
+        ));
+    }
+}
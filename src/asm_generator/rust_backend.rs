@@ -0,0 +1,267 @@
+use std::cell::Cell;
+
+use crate::asm_generator::backend::TextBackend;
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump};
+use crate::grammar::{PROMPT, TAPE_LEN};
+use crate::tape::{CELLS_PER_LINE, DUMP_HEADER};
+
+static CELL_MEMORY: &str = "tape";
+static CELL_PTR: &str = "p";
+static PRECOMPUTED_OUTPUT_MEMORY: &str = "PRECOMPUTED_OUTPUT";
+
+/// Number of spaces each block indents its body by
+const INDENT_WIDTH: usize = 4;
+
+/// Generates Rust source running the program on a `Vec<u8>` tape.
+///
+/// The program becomes `run(input, output)` over any `Read` and `Write`, along with
+/// a `main` using stdin and stdout when generating a standalone file. Like the assembly
+/// targets `,` prompts the user and leaves the cell untouched on end of file.
+/// When exiting with the current cell `run` gives it back and `main` exits with it.
+pub struct RustBackend {
+    /// Whether to add a `main` function
+    standalone: bool,
+    /// What the program exits with and whether it dumps the tape
+    options: CodegenOptions,
+    /// Number of loops the code being generated is in
+    depth: Cell<usize>,
+}
+
+impl RustBackend {
+    /// Creates a new `RustBackend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `standalone` - Whether to generate a whole program or only the `run` function.
+    /// * `options` - What the program exits with and whether it dumps the tape.
+    pub fn new(standalone: bool, options: CodegenOptions) -> Self {
+        RustBackend {
+            standalone,
+            options,
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Indents a statement to the current block of `run`.
+    fn statement(&self, code: &str) -> String {
+        format!("{}{}\n", " ".repeat((self.depth.get() + 1) * INDENT_WIDTH), code)
+    }
+
+    /// Gives the cell `offset` cells away from the cell ptr
+    /// Eg = 'tape[p]', 'tape[p - 1]'
+    fn cell_at(offset: isize) -> String {
+        match offset {
+            0 => format!("{}[{}]", CELL_MEMORY, CELL_PTR),
+            _ if offset < 0 => format!("{}[{} - {}]", CELL_MEMORY, CELL_PTR, -offset),
+            _ => format!("{}[{} + {}]", CELL_MEMORY, CELL_PTR, offset),
+        }
+    }
+}
+
+impl TextBackend for RustBackend {
+    fn data_section(&self, _used_stdin: bool, precomputed_output: &[u8]) -> String {
+        if precomputed_output.is_empty() {
+            return String::new();
+        }
+
+        let bytes = precomputed_output
+            .chunks(16)
+            .map(|chunk| {
+                let chunk: Vec<String> = chunk.iter().map(|byte| byte.to_string()).collect();
+                format!("    {},\n", chunk.join(", "))
+            })
+            .collect::<String>();
+        format!(
+            "/// Output computed at compile time\nconst {}: &[u8] = &[\n{}];\n\n",
+            PRECOMPUTED_OUTPUT_MEMORY, bytes
+        )
+    }
+
+    fn prologue(&self) -> String {
+        let (doc, returns) = match self.options.exit_code {
+            ExitCode::Zero => ("", "()"),
+            ExitCode::Cell => (" giving back the current cell", "u8"),
+        };
+        format!(
+            "/// Runs the program, reading `,` from `input` and writing `.` to `output`{}.\n\
+             #[allow(unused_mut, unused_variables, unused_assignments)]\n\
+             pub fn run(\n    input: &mut impl std::io::Read,\n    output: &mut impl std::io::Write,\n\
+             ) -> std::io::Result<{}> {{\n{}{}",
+            doc,
+            returns,
+            self.statement(&format!("let mut {} = vec![0u8; {}];", CELL_MEMORY, TAPE_LEN)),
+            self.statement(&format!("let mut {}: usize = 0;", CELL_PTR))
+        )
+    }
+
+    fn epilogue(&self) -> String {
+        let dump = match self.options.tape_dump {
+            TapeDump::Off => String::new(),
+            _ => [
+                self.statement("// Write the pointer and the used cells of the tape to stderr"),
+                self.statement("output.flush()?;"),
+                self.statement(&format!(
+                    "let last = {}.iter().rposition(|&cell| cell != 0).unwrap_or(0).max({});",
+                    CELL_MEMORY, CELL_PTR
+                )),
+                self.statement(&format!("eprint!(\"{}{{:04x}}\", {});", DUMP_HEADER, CELL_PTR)),
+                self.statement(&format!(
+                    "for (idx, cell) in {}[..=last].iter().enumerate() {{",
+                    CELL_MEMORY
+                )),
+                self.statement(&format!("    if idx % {} == 0 {{", CELLS_PER_LINE)),
+                self.statement("        eprint!(\"\\n{:04x}:\", idx);"),
+                self.statement("    }"),
+                self.statement("    eprint!(\" {:02x}\", cell);"),
+                self.statement("}"),
+                self.statement("eprintln!();"),
+            ]
+            .concat(),
+        };
+        let mut code = match self.options.exit_code {
+            ExitCode::Zero => format!("\n{}{}}}\n", dump, self.statement("Ok(())")),
+            ExitCode::Cell => format!(
+                "\n{}{}}}\n",
+                dump,
+                self.statement(&format!("Ok({})", Self::cell_at(0)))
+            ),
+        };
+        match (self.standalone, self.options.exit_code) {
+            (false, _) => {}
+            (true, ExitCode::Zero) => code.push_str(
+                "\nfn main() -> std::io::Result<()> {\n    \
+                 let mut output = std::io::BufWriter::new(std::io::stdout().lock());\n    \
+                 run(&mut std::io::stdin().lock(), &mut output)?;\n    \
+                 std::io::Write::flush(&mut output)\n\
+                 }\n",
+            ),
+            (true, ExitCode::Cell) => code.push_str(
+                "\nfn main() -> std::io::Result<()> {\n    \
+                 let mut output = std::io::BufWriter::new(std::io::stdout().lock());\n    \
+                 let status = run(&mut std::io::stdin().lock(), &mut output)?;\n    \
+                 std::io::Write::flush(&mut output)?;\n    \
+                 std::process::exit(status as i32)\n\
+                 }\n",
+            ),
+        }
+        code
+    }
+
+    fn comment(&self, text: &str) -> String {
+        self.statement(&format!("// {}", text))
+    }
+
+    fn move_ptr(&self, amount: isize) -> String {
+        match amount {
+            _ if amount < 0 => self.statement(&format!("{} -= {};", CELL_PTR, -amount)),
+            _ => self.statement(&format!("{} += {};", CELL_PTR, amount)),
+        }
+    }
+
+    fn add(&self, amount: isize, offset: isize) -> String {
+        // Cells wrap around so only the low byte of the amount matters
+        let (op, amount) = match amount {
+            _ if amount < 0 => ("wrapping_sub", amount.unsigned_abs() as u8),
+            _ => ("wrapping_add", amount as u8),
+        };
+        let cell = Self::cell_at(offset);
+        self.statement(&format!("{} = {}.{}({});", cell, cell, op, amount))
+    }
+
+    fn loop_begin(&self, _name: &str) -> String {
+        let code = self.statement(&format!("while {} != 0 {{", Self::cell_at(0)));
+        self.depth.set(self.depth.get() + 1);
+        code
+    }
+
+    fn loop_end(&self, _name: &str) -> String {
+        self.depth.set(self.depth.get() - 1);
+        self.statement("}")
+    }
+
+    fn input(&self) -> String {
+        [
+            self.statement("{"),
+            self.statement(&format!("    output.write_all(b\"{}\")?;", PROMPT)),
+            self.statement("    output.flush()?;"),
+            self.statement("    let mut byte = [0u8];"),
+            self.statement("    if input.read(&mut byte)? == 1 {"),
+            self.statement(&format!("        {} = byte[0];", Self::cell_at(0))),
+            self.statement("    }"),
+            self.statement("}"),
+        ]
+        .concat()
+    }
+
+    fn output(&self) -> String {
+        self.statement(&format!("output.write_all(&[{}])?;", Self::cell_at(0)))
+    }
+
+    fn restore_snapshot(&self, tape: &[u8], cell_ptr: usize) -> String {
+        let mut code = self.statement("// Restore the state computed at compile time");
+        tape.iter()
+            .enumerate()
+            .filter(|(_, &cell)| cell != 0)
+            .for_each(|(idx, cell)| {
+                code.push_str(&self.statement(&format!("{}[{}] = {};", CELL_MEMORY, idx, cell)))
+            });
+        code.push_str(&self.statement(&format!("{} = {};", CELL_PTR, cell_ptr)));
+        code
+    }
+
+    fn print_precomputed_output(&self, _output: &[u8]) -> String {
+        self.statement(&format!("output.write_all({})?;", PRECOMPUTED_OUTPUT_MEMORY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm_generator::{generate, Target};
+
+    /// Keeps the statements of `run`, with their indentation
+    fn body(code: &str) -> Vec<&str> {
+        let start = code.find(") -> std::io::Result<()> {\n").unwrap();
+        code[start..]
+            .lines()
+            .skip(1)
+            .take_while(|line| *line != "}")
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .collect()
+    }
+
+    #[test]
+    fn wraps_cells_and_nests_loops_as_blocks() {
+        assert_eq!(
+            body(&generate("-[>+<-]>.", Target::Rust)),
+            [
+                "    let mut tape = vec![0u8; 256];",
+                "    let mut p: usize = 0;",
+                "    tape[p] = tape[p].wrapping_sub(1);",
+                "    while tape[p] != 0 {",
+                "        p += 1;",
+                "        tape[p] = tape[p].wrapping_add(1);",
+                "        p -= 1;",
+                "        tape[p] = tape[p].wrapping_sub(1);",
+                "    }",
+                "    p += 1;",
+                "    output.write_all(&[tape[p]])?;",
+                "",
+                "    Ok(())",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_adds_main_to_standalone_programs() {
+        assert!(generate("+", Target::Rust).contains("\nfn main() -> std::io::Result<()> {\n"));
+        assert!(!generate("+", Target::RustFn).contains("fn main"));
+        assert!(generate("+", Target::RustFn).contains("pub fn run(\n"));
+    }
+
+    #[test]
+    fn leaves_the_cell_untouched_on_end_of_file() {
+        assert!(generate(",", Target::RustFn).contains(
+            "        if input.read(&mut byte)? == 1 {\n            tape[p] = byte[0];\n        }\n"
+        ));
+    }
+}
@@ -0,0 +1,407 @@
+use std::cell::Cell;
+
+use crate::asm_generator::backend::TextBackend;
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump};
+use crate::grammar::{PROMPT, TAPE_LEN};
+use crate::tape::{CELLS_PER_LINE, DUMP_HEADER, HEX_DIGITS, MAX_DUMP_LEN};
+
+static CELL_PTR: &str = "$p";
+static WRITE: &str = "$write";
+static READ: &str = "$read";
+static DUMP_TAPE: &str = "$dump_tape";
+static DUMP_HEX: &str = "$dump_hex";
+static LOOP: &str = "$LOOP";
+
+/// Address of the `iovec` handed to WASI, right after the tape
+const IOVEC_ADDR: usize = TAPE_LEN;
+/// Address WASI stores the number of bytes read or written at
+const NUM_BYTES_ADDR: usize = IOVEC_ADDR + 8;
+/// Address `,` reads the byte into before it is copied to the cell
+const INPUT_ADDR: usize = NUM_BYTES_ADDR + 4;
+/// Address of the input prompt
+const PROMPT_ADDR: usize = INPUT_ADDR + 4;
+/// Address of the output computed at compile time
+const PRECOMPUTED_OUTPUT_ADDR: usize = PROMPT_ADDR + PROMPT.len();
+
+/// Size of a WebAssembly memory page
+const PAGE_SIZE: usize = 65536;
+/// Number of spaces each block indents its body by
+const INDENT_WIDTH: usize = 2;
+
+/// Generates a WebAssembly text module running on WASI.
+///
+/// The tape lives at the start of the exported linear memory, followed by the scratch
+/// space WASI calls need. `_start` runs the program, `.` and `,` go through `fd_write`
+/// and `fd_read`, and like the assembly targets `,` prompts the user and leaves the
+/// cell untouched on end of file.
+pub struct WatBackend {
+    /// What `_start` exits with, through `proc_exit` unless it is 0, and whether it
+    /// dumps the tape
+    options: CodegenOptions,
+    /// Number of loops the code being generated is in
+    depth: Cell<usize>,
+}
+
+impl WatBackend {
+    /// Creates a new `WatBackend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - What `_start` exits with and whether it dumps the tape.
+    pub fn new(options: CodegenOptions) -> Self {
+        WatBackend {
+            options,
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Indents an instruction to the current block of `_start`.
+    fn statement(&self, code: &str) -> String {
+        format!("{}{}\n", " ".repeat((self.depth.get() + 2) * INDENT_WIDTH), code)
+    }
+
+    /// Gives the static offset of a load or store along with the address of the cell
+    /// `offset` cells away from the cell pointer, as static offsets cannot be negative.
+    /// Eg = ('offset=2 ', '(local.get $p)'), ('', '(i32.sub (local.get $p) (i32.const 1))')
+    fn cell_at(offset: isize) -> (String, String) {
+        match offset {
+            0 => (String::new(), format!("(local.get {})", CELL_PTR)),
+            _ if offset < 0 => (
+                String::new(),
+                format!("(i32.sub (local.get {}) (i32.const {}))", CELL_PTR, -offset),
+            ),
+            _ => (format!("offset={} ", offset), format!("(local.get {})", CELL_PTR)),
+        }
+    }
+
+    /// Gives the name of a loop's block
+    /// Eg = '$LOOP_L0_C1', '$LOOP_L0_C1_END'
+    fn loop_label(name: &str, suffix: &str) -> String {
+        format!("{}_{}{}", LOOP, name, suffix)
+    }
+
+    /// Writes the bytes as the contents of a WAT string.
+    fn escape(bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .map(|&byte| match byte {
+                b' '..=b'~' if byte != b'"' && byte != b'\\' => (byte as char).to_string(),
+                _ => format!("\\{:02x}", byte),
+            })
+            .collect()
+    }
+}
+
+impl TextBackend for WatBackend {
+    fn header(&self) -> String {
+        let mut header = String::from(
+            "(module\n  \
+             (import \"wasi_snapshot_preview1\" \"fd_write\"\n    \
+             (func $fd_write (param i32 i32 i32 i32) (result i32)))\n  \
+             (import \"wasi_snapshot_preview1\" \"fd_read\"\n    \
+             (func $fd_read (param i32 i32 i32 i32) (result i32)))\n",
+        );
+        if self.options.exit_code == ExitCode::Cell {
+            header.push_str(
+                "  (import \"wasi_snapshot_preview1\" \"proc_exit\"\n    \
+                 (func $proc_exit (param i32)))\n",
+            );
+        }
+        header.push('\n');
+        header
+    }
+
+    fn data_section(&self, _used_stdin: bool, precomputed_output: &[u8]) -> String {
+        // The tape dump follows the output computed at compile time, its buffer starting
+        // with the header
+        let hex_addr = PRECOMPUTED_OUTPUT_ADDR + precomputed_output.len();
+        let dump_addr = hex_addr + HEX_DIGITS.len();
+        let memory_size = match self.options.tape_dump {
+            TapeDump::Off => hex_addr,
+            _ => dump_addr + DUMP_HEADER.len() + MAX_DUMP_LEN,
+        };
+        let mut data = format!(
+            "  ;; The tape starts at address 0\n  (memory (export \"memory\") {})\n",
+            memory_size.div_ceil(PAGE_SIZE)
+        );
+        data.push_str(&format!(
+            "  (data (i32.const {}) \"{}\")\n",
+            PROMPT_ADDR,
+            Self::escape(PROMPT.as_bytes())
+        ));
+        if !precomputed_output.is_empty() {
+            data.push_str(&format!(
+                "  ;; Output computed at compile time\n  (data (i32.const {}) \"{}\")\n",
+                PRECOMPUTED_OUTPUT_ADDR,
+                Self::escape(precomputed_output)
+            ));
+        }
+
+        // Prints a buffer to stdout
+        data.push_str(&format!(
+            r#"
+  (func {write} (param $buf i32) (param $len i32)
+    (i32.store (i32.const {iovec}) (local.get $buf))
+    (i32.store (i32.const {iovec_len}) (local.get $len))
+    (drop (call $fd_write (i32.const 1) (i32.const {iovec}) (i32.const 1) (i32.const {num_bytes}))))
+"#,
+            write = WRITE,
+            iovec = IOVEC_ADDR,
+            iovec_len = IOVEC_ADDR + 4,
+            num_bytes = NUM_BYTES_ADDR
+        ));
+
+        // Prompts for a byte from stdin and stores it in the cell unless at end of file
+        data.push_str(&format!(
+            r#"
+  (func {read} (param $cell i32)
+    (call {write} (i32.const {prompt}) (i32.const {prompt_len}))
+    (i32.store (i32.const {iovec}) (i32.const {input}))
+    (i32.store (i32.const {iovec_len}) (i32.const 1))
+    (drop (call $fd_read (i32.const 0) (i32.const {iovec}) (i32.const 1) (i32.const {num_bytes})))
+    (if (i32.eq (i32.load (i32.const {num_bytes})) (i32.const 1))
+      (then (i32.store8 (local.get $cell) (i32.load8_u (i32.const {input}))))))
+"#,
+            read = READ,
+            write = WRITE,
+            prompt = PROMPT_ADDR,
+            prompt_len = PROMPT.len(),
+            iovec = IOVEC_ADDR,
+            iovec_len = IOVEC_ADDR + 4,
+            input = INPUT_ADDR,
+            num_bytes = NUM_BYTES_ADDR
+        ));
+
+        if self.options.tape_dump != TapeDump::Off {
+            data.push_str(&format!(
+                "\n  (data (i32.const {}) \"{}\")\n  (data (i32.const {}) \"{}\")\n",
+                hex_addr,
+                HEX_DIGITS,
+                dump_addr,
+                Self::escape(DUMP_HEADER.as_bytes())
+            ));
+
+            // Writes the low hex digits of a value, then the pointer and the used cells of
+            // the tape to stderr
+            data.push_str(&format!(
+                r#"
+  (func {hex} (param $out i32) (param $value i32) (param $count i32)
+    (loop $digit
+      (local.set $count (i32.sub (local.get $count) (i32.const 1)))
+      (i32.store8 (i32.add (local.get $out) (local.get $count))
+        (i32.load8_u offset={hex_addr} (i32.and (local.get $value) (i32.const 15))))
+      (local.set $value (i32.shr_u (local.get $value) (i32.const 4)))
+      (br_if $digit (local.get $count))))
+
+  (func {dump} (param $p i32)
+    (local $last i32) (local $idx i32) (local $out i32)
+    (local.set $out (i32.const {buffer}))
+    (call {hex} (local.get $out) (local.get $p) (i32.const 4))
+    (local.set $out (i32.add (local.get $out) (i32.const 4)))
+    (local.set $last (i32.const {last}))
+    (block $found
+      (loop $scan
+        (br_if $found (i32.le_u (local.get $last) (local.get $p)))
+        (br_if $found (i32.load8_u (local.get $last)))
+        (local.set $last (i32.sub (local.get $last) (i32.const 1)))
+        (br $scan)))
+    (block $done
+      (loop $cell
+        (br_if $done (i32.gt_u (local.get $idx) (local.get $last)))
+        (if (i32.eqz (i32.rem_u (local.get $idx) (i32.const {per_line})))
+          (then
+            (i32.store8 (local.get $out) (i32.const 10))
+            (call {hex} (i32.add (local.get $out) (i32.const 1)) (local.get $idx) (i32.const 4))
+            (i32.store8 offset=5 (local.get $out) (i32.const 58))
+            (local.set $out (i32.add (local.get $out) (i32.const 6)))))
+        (i32.store8 (local.get $out) (i32.const 32))
+        (call {hex} (i32.add (local.get $out) (i32.const 1)) (i32.load8_u (local.get $idx)) (i32.const 2))
+        (local.set $out (i32.add (local.get $out) (i32.const 3)))
+        (local.set $idx (i32.add (local.get $idx) (i32.const 1)))
+        (br $cell)))
+    (i32.store8 (local.get $out) (i32.const 10))
+    (i32.store (i32.const {iovec}) (i32.const {dump_addr}))
+    (i32.store (i32.const {iovec_len})
+      (i32.sub (i32.add (local.get $out) (i32.const 1)) (i32.const {dump_addr})))
+    (drop (call $fd_write (i32.const 2) (i32.const {iovec}) (i32.const 1) (i32.const {num_bytes}))))
+"#,
+                hex = DUMP_HEX,
+                dump = DUMP_TAPE,
+                hex_addr = hex_addr,
+                dump_addr = dump_addr,
+                buffer = dump_addr + DUMP_HEADER.len(),
+                last = TAPE_LEN - 1,
+                per_line = CELLS_PER_LINE,
+                iovec = IOVEC_ADDR,
+                iovec_len = IOVEC_ADDR + 4,
+                num_bytes = NUM_BYTES_ADDR
+            ));
+        }
+        data
+    }
+
+    fn prologue(&self) -> String {
+        format!("\n  (func (export \"_start\")\n    (local {} i32)\n", CELL_PTR)
+    }
+
+    fn epilogue(&self) -> String {
+        let mut code = String::new();
+        if self.options.tape_dump != TapeDump::Off {
+            code.push_str(&self.statement(&format!("(call {} (local.get {}))", DUMP_TAPE, CELL_PTR)));
+        }
+        if self.options.exit_code == ExitCode::Cell {
+            code.push_str(&self.statement(&format!(
+                "(call $proc_exit (i32.load8_u (local.get {})))",
+                CELL_PTR
+            )));
+        }
+        code.push_str("  )\n)\n");
+        code
+    }
+
+    fn comment(&self, text: &str) -> String {
+        self.statement(&format!(";; {}", text))
+    }
+
+    fn move_ptr(&self, amount: isize) -> String {
+        let op = match amount {
+            _ if amount < 0 => "i32.sub",
+            _ => "i32.add",
+        };
+        self.statement(&format!(
+            "(local.set {} ({} (local.get {}) (i32.const {})))",
+            CELL_PTR,
+            op,
+            CELL_PTR,
+            amount.unsigned_abs()
+        ))
+    }
+
+    fn add(&self, amount: isize, offset: isize) -> String {
+        let (static_offset, addr) = Self::cell_at(offset);
+        // Cells wrap around so only the low byte of the amount matters, store8 drops the rest
+        self.statement(&format!(
+            "(i32.store8 {}{} (i32.add (i32.load8_u {}{}) (i32.const {})))",
+            static_offset, addr, static_offset, addr, amount as u8
+        ))
+    }
+
+    fn loop_begin(&self, name: &str) -> String {
+        let end = Self::loop_label(name, "_END");
+        let code = [
+            self.statement(&format!("(block {}", end)),
+            self.statement(&format!("  (loop {}", Self::loop_label(name, ""))),
+            self.statement(&format!(
+                "    (br_if {} (i32.eqz (i32.load8_u (local.get {}))))",
+                end, CELL_PTR
+            )),
+        ]
+        .concat();
+        self.depth.set(self.depth.get() + 2);
+        code
+    }
+
+    fn loop_end(&self, name: &str) -> String {
+        self.depth.set(self.depth.get() - 2);
+        [
+            self.statement(&format!("  (br {}))", Self::loop_label(name, ""))),
+            self.statement(")"),
+        ]
+        .concat()
+    }
+
+    fn input(&self) -> String {
+        self.statement(&format!("(call {} (local.get {}))", READ, CELL_PTR))
+    }
+
+    fn output(&self) -> String {
+        self.statement(&format!("(call {} (local.get {}) (i32.const 1))", WRITE, CELL_PTR))
+    }
+
+    fn restore_snapshot(&self, tape: &[u8], cell_ptr: usize) -> String {
+        let mut code = self.statement(";; Restore the state computed at compile time");
+        tape.iter()
+            .enumerate()
+            .filter(|(_, &cell)| cell != 0)
+            .for_each(|(idx, cell)| {
+                code.push_str(&self.statement(&format!(
+                    "(i32.store8 (i32.const {}) (i32.const {}))",
+                    idx, cell
+                )))
+            });
+        code.push_str(&self.statement(&format!(
+            "(local.set {} (i32.const {}))",
+            CELL_PTR, cell_ptr
+        )));
+        code
+    }
+
+    fn print_precomputed_output(&self, output: &[u8]) -> String {
+        self.statement(&format!(
+            "(call {} (i32.const {}) (i32.const {}))",
+            WRITE,
+            PRECOMPUTED_OUTPUT_ADDR,
+            output.len()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm_generator::{generate, Target, TEST_OPTIONS};
+
+    /// Keeps the instructions of `_start`, with their indentation
+    fn body(code: &str) -> Vec<&str> {
+        let start = code.find("(func (export \"_start\")\n").unwrap();
+        code[start..]
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim_start().starts_with(";;"))
+            .collect()
+    }
+
+    #[test]
+    fn breaks_out_of_loops_on_a_zero_cell() {
+        assert_eq!(
+            body(&generate("-[>+<-]>.", Target::Wat)),
+            [
+                "    (local $p i32)",
+                "    (i32.store8 (local.get $p) (i32.add (i32.load8_u (local.get $p)) (i32.const 255)))",
+                "    (block $LOOP_L0_C1_END",
+                "      (loop $LOOP_L0_C1",
+                "        (br_if $LOOP_L0_C1_END (i32.eqz (i32.load8_u (local.get $p))))",
+                "        (local.set $p (i32.add (local.get $p) (i32.const 1)))",
+                "        (i32.store8 (local.get $p) (i32.add (i32.load8_u (local.get $p)) (i32.const 1)))",
+                "        (local.set $p (i32.sub (local.get $p) (i32.const 1)))",
+                "        (i32.store8 (local.get $p) (i32.add (i32.load8_u (local.get $p)) (i32.const 255)))",
+                "      (br $LOOP_L0_C1))",
+                "    )",
+                "    (local.set $p (i32.add (local.get $p) (i32.const 1)))",
+                "    (call $write (local.get $p) (i32.const 1))",
+                "  )",
+                ")",
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_static_offsets_only_ahead_of_the_cell_ptr() {
+        let backend = WatBackend::new(TEST_OPTIONS);
+        assert_eq!(
+            backend.add(3, 2),
+            "    (i32.store8 offset=2 (local.get $p) (i32.add (i32.load8_u offset=2 (local.get $p)) (i32.const 3)))\n"
+        );
+        assert_eq!(
+            backend.add(1, -1),
+            "    (i32.store8 (i32.sub (local.get $p) (i32.const 1)) (i32.add (i32.load8_u (i32.sub (local.get $p) (i32.const 1))) (i32.const 1)))\n"
+        );
+    }
+
+    #[test]
+    fn only_stores_the_byte_read_when_there_is_one() {
+        assert!(generate(",", Target::Wat).contains(
+            "    (if (i32.eq (i32.load (i32.const 264)) (i32.const 1))\n      (then (i32.store8 (local.get $cell) (i32.load8_u (i32.const 268))))))\n"
+        ));
+    }
+}
@@ -0,0 +1,809 @@
+use crate::asm_generator::asm_instructions::{
+    FileDescriptor, SIGACTION_FLAGS, SIGUSR1, X86_64_LINUX,
+};
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump};
+use crate::grammar::{Expression, Token, PROMPT, TAPE_LEN};
+use crate::tape::{CELLS_PER_LINE, CELL_DIGITS, DUMP_HEADER, HEX_DIGITS, INDEX_DIGITS};
+
+// NOTE THE ADDRESS OF THE CURRENT CELL STAYS IN 'rbx' AS 'syscall' ONLY CLOBBERS 'rcx' & 'r11'
+// AND TAKES ITS ARGUMENTS IN 'rax' 'rdi' 'rsi' 'rdx'
+// FUNCTIONS KEEP THEIR ARGUMENTS IN THE CALLEE SAVED 'r12' TO 'r15' SO CALLBACKS LEAVE THEM BE
+
+/// Where the encoded program runs, deciding how it finds its tape and does its I/O
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Environment {
+    /// A static Linux executable using syscalls, with its memory at fixed addresses,
+    /// exiting and dumping its tape as the options say
+    Executable(CodegenOptions),
+    /// A function following the System V ABI called as
+    /// `fn(tape, context, read(context) -> i32, write(context, byte)) -> usize`, where
+    /// `read` gives a negative number on end of file. It gives the index of the cell
+    /// pointer, or an index past the tape as soon as the pointer leaves it
+    Function,
+}
+
+/// Memory the code refers to by its absolute address
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Symbol {
+    /// The first cell of the tape
+    Tape,
+    /// The prompt for the user
+    Prompt,
+    /// The output computed at compile time
+    PrecomputedOutput,
+    /// The text starting a tape dump
+    DumpHeader,
+    /// The digits of hexadecimal numbers
+    HexDigits,
+    /// The `struct sigaction` installing the signal handler
+    SigAction,
+    /// Where the tape dump is written before it goes to stderr
+    DumpBuffer,
+}
+
+/// Code called from elsewhere in the program
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Routine {
+    /// Writes the tape to stderr, taking the address of the current cell in rbx
+    DumpTape,
+    /// Dumps the tape on a signal
+    SignalHandler,
+    /// Returns from the signal handler to the program
+    SignalRestorer,
+}
+
+/// A 32-bit absolute address in the code to fill in once the layout of the
+/// program is known
+#[derive(Debug)]
+pub struct Fixup {
+    /// Index of the address in the code
+    pub position: usize,
+    /// Memory the address points into
+    pub symbol: Symbol,
+    /// Offset of the address from the start of the memory
+    pub addend: usize,
+}
+
+/// Encodes the syntax tree straight into x86-64 machine code.
+///
+/// The code keeps the address of the current cell in `rbx`. Executables use the same
+/// syscalls as the assembly target, so `,` prompts the user and leaves the cell
+/// untouched on end of file, and the addresses of the tape and of the constants are
+/// left as [Fixup]s for whoever lays the program out in memory. Functions leave the
+/// prompt to their `read` callback, need no fixups and check every cell they reach
+/// is on the tape.
+pub struct X86_64Encoder {
+    /// Where the program runs
+    environment: Environment,
+    /// Machine code of the whole program
+    code: Vec<u8>,
+    /// Addresses to fill in
+    fixups: Vec<Fixup>,
+    /// Jumps to the end of the program taken when the cell pointer leaves the tape
+    exits: Vec<usize>,
+    /// Start of every routine in the code
+    routines: Vec<(Routine, usize)>,
+    /// rel32s in the code pointing at a routine
+    routine_refs: Vec<(usize, Routine)>,
+    /// Used to check if the program requires the prompt
+    used_stdin: bool,
+    /// Output of the program computed at compile time
+    precomputed_output: Vec<u8>,
+}
+
+impl X86_64Encoder {
+    /// Encodes a whole program, from setting up the cell pointer to exiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `syntax_tree` - The optimized program.
+    /// * `environment` - Where the program runs.
+    ///
+    /// # Returns
+    ///
+    /// The encoder holding the machine code.
+    pub fn encode(syntax_tree: &[Expression], environment: Environment) -> Self {
+        let mut encoder = X86_64Encoder {
+            environment,
+            code: vec![],
+            fixups: vec![],
+            exits: vec![],
+            routines: vec![],
+            routine_refs: vec![],
+            used_stdin: false,
+            precomputed_output: vec![],
+        };
+
+        encoder.prologue();
+        encoder.encode_(syntax_tree);
+        encoder.epilogue();
+        encoder.patch_routine_refs();
+        encoder
+    }
+
+    /// Machine code of the program
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Addresses in the code to fill in
+    pub fn fixups(&self) -> &[Fixup] {
+        &self.fixups
+    }
+
+    /// Gives the bytes a symbol stands for, `None` for the memory which starts zeroed.
+    pub fn symbol_data(&self, symbol: Symbol) -> Option<&[u8]> {
+        let dumps_tape = self.tape_dump() != TapeDump::Off;
+        match symbol {
+            Symbol::Tape | Symbol::SigAction | Symbol::DumpBuffer => None,
+            Symbol::Prompt if self.used_stdin => Some(PROMPT.as_bytes()),
+            Symbol::Prompt => Some(&[]),
+            Symbol::PrecomputedOutput => Some(&self.precomputed_output),
+            Symbol::DumpHeader if dumps_tape => Some(DUMP_HEADER.as_bytes()),
+            Symbol::HexDigits if dumps_tape => Some(HEX_DIGITS.as_bytes()),
+            Symbol::DumpHeader | Symbol::HexDigits => Some(&[]),
+        }
+    }
+
+    /// Gives how many zeroed bytes a symbol without [data](Self::symbol_data) needs.
+    pub fn zeroed_len(&self, symbol: Symbol) -> usize {
+        match (symbol, self.tape_dump()) {
+            (Symbol::Tape, _) => TAPE_LEN,
+            (Symbol::SigAction, TapeDump::Signal) => X86_64_LINUX.sigaction_len,
+            (Symbol::DumpBuffer, TapeDump::Exit | TapeDump::Signal) => crate::tape::MAX_DUMP_LEN,
+            _ => 0,
+        }
+    }
+
+    /// When the program dumps its tape, never for functions as their caller has the tape
+    fn tape_dump(&self) -> TapeDump {
+        match self.environment {
+            Environment::Executable(options) => options.tape_dump,
+            Environment::Function => TapeDump::Off,
+        }
+    }
+
+    /// Recursively encodes the given expressions.
+    fn encode_(&mut self, expressions: &[Expression]) {
+        for expr in expressions {
+            match expr {
+                Expression::Loop(_loop, _) => {
+                    // Skip the loop if the current cell is zero
+                    self.cmp_cell_zero();
+                    let skip = self.jump_rel32(&[0x0F, 0x84]);
+                    let body = self.code.len();
+
+                    self.encode_(_loop);
+
+                    // Go another round unless the current cell is zero
+                    self.cmp_cell_zero();
+                    let repeat = self.jump_rel32(&[0x0F, 0x85]);
+                    self.patch_jump(repeat, body);
+                    self.patch_jump(skip, self.code.len());
+                }
+                Expression::Operator(_op) => {
+                    let count = _op.count as isize;
+                    match _op.type_name {
+                        Token::MoveBack => self.move_cell_ptr(-count),
+                        Token::MoveForward => self.move_cell_ptr(count),
+                        Token::Add => self.add_to_cell(count, _op.offset),
+                        Token::Sub => self.add_to_cell(-count, _op.offset),
+                        Token::StdOut => self.output(),
+                        Token::StdIn => {
+                            self.used_stdin = true;
+                            self.input();
+                        }
+                        _ => {
+                            eprintln!(
+                                "Unexpected Token: {:?} when generating machine code ",
+                                _op.type_name
+                            );
+                            std::process::exit(123);
+                        }
+                    }
+                }
+                Expression::Snapshot(snapshot) => {
+                    self.mov_rbx_tape();
+                    snapshot
+                        .tape
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &cell)| cell != 0)
+                        .for_each(|(idx, &cell)| self.mov_cell(idx as isize, cell));
+                    self.move_ptr(snapshot.cell_ptr as isize);
+
+                    if !snapshot.output.is_empty() {
+                        self.print_precomputed_output(&snapshot.output);
+                    }
+                }
+            }
+        }
+    }
+
+    // ----------------- [ ENVIRONMENT ] ----------------- \\
+
+    /// Points rbx to the first cell and saves what the environment needs
+    fn prologue(&mut self) {
+        match self.environment {
+            Environment::Executable(options) => {
+                self.mov_rbx_tape();
+                if options.tape_dump == TapeDump::Signal {
+                    self.install_signal_handler();
+                }
+            }
+            Environment::Function => {
+                // push rbx, r12, r13, r14, r15 which also aligns the stack for the callbacks
+                self.code
+                    .extend_from_slice(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+                // mov r15, rdi (tape)
+                self.code.extend_from_slice(&[0x49, 0x89, 0xFF]);
+                // mov r12, rsi (context)
+                self.code.extend_from_slice(&[0x49, 0x89, 0xF4]);
+                // mov r13, rdx (read)
+                self.code.extend_from_slice(&[0x49, 0x89, 0xD5]);
+                // mov r14, rcx (write)
+                self.code.extend_from_slice(&[0x49, 0x89, 0xCE]);
+                self.mov_rbx_tape();
+            }
+        }
+    }
+
+    /// Exits the program or returns to the caller, followed by the routines it calls
+    fn epilogue(&mut self) {
+        match self.environment {
+            Environment::Executable(options) => {
+                if options.tape_dump != TapeDump::Off {
+                    self.call(Routine::DumpTape);
+                }
+                self.exit(options.exit_code);
+
+                if options.tape_dump != TapeDump::Off {
+                    self.dump_tape();
+                }
+                if options.tape_dump == TapeDump::Signal {
+                    self.signal_handler();
+                }
+            }
+            Environment::Function => {
+                // mov rax, rbx
+                self.code.extend_from_slice(&[0x48, 0x89, 0xD8]);
+                // sub rax, r15 (index of the current cell)
+                self.code.extend_from_slice(&[0x4C, 0x29, 0xF8]);
+
+                // Leaving the tape returns the index past it left in rax
+                let end = self.code.len();
+                for exit in std::mem::take(&mut self.exits) {
+                    self.patch_jump(exit, end);
+                }
+
+                // pop r15, r14, r13, r12, rbx
+                self.code
+                    .extend_from_slice(&[0x41, 0x5F, 0x41, 0x5E, 0x41, 0x5D, 0x41, 0x5C, 0x5B]);
+                // ret
+                self.code.push(0xC3);
+            }
+        }
+    }
+
+    /// Moves the cell pointer, returning to the caller of a function if it left the tape
+    fn move_cell_ptr(&mut self, amount: isize) {
+        self.move_ptr(amount);
+        self.check_cell(0);
+    }
+
+    /// Adds to a cell, returning to the caller of a function first if the cell is not
+    /// on the tape
+    fn add_to_cell(&mut self, amount: isize, offset: isize) {
+        // The current cell was checked when the pointer got there
+        if offset != 0 {
+            self.check_cell(offset);
+        }
+        self.add(amount, offset);
+    }
+
+    /// Returns to the caller of a function if the cell `offset` cells away from the
+    /// current one is not on the tape, executables are not checked like the assembly
+    /// targets
+    fn check_cell(&mut self, offset: isize) {
+        if self.environment != Environment::Function {
+            return;
+        }
+
+        // lea rax, [rbx+disp32]
+        self.code.extend_from_slice(&[0x48, 0x8D, 0x83]);
+        self.code.extend_from_slice(&(offset as i32).to_le_bytes());
+        // sub rax, r15
+        self.code.extend_from_slice(&[0x4C, 0x29, 0xF8]);
+        // cmp rax, imm32
+        self.code.extend_from_slice(&[0x48, 0x3D]);
+        self.code.extend_from_slice(&(TAPE_LEN as u32).to_le_bytes());
+        // jae to the end, as an unsigned compare also catches cells before the tape
+        let exit = self.jump_rel32(&[0x0F, 0x83]);
+        self.exits.push(exit);
+    }
+
+    /// Points rbx to the first cell
+    fn mov_rbx_tape(&mut self) {
+        match self.environment {
+            Environment::Executable(_) => self.mov_rbx_address(Symbol::Tape, 0),
+            // mov rbx, r15
+            Environment::Function => self.code.extend_from_slice(&[0x4C, 0x89, 0xFB]),
+        }
+    }
+
+    /// Prints the current cell
+    fn output(&mut self) {
+        match self.environment {
+            Environment::Executable(_) => {
+                self.mov_rsi_rbx();
+                self.sys_call(X86_64_LINUX.sys_calls.write, FileDescriptor::StdOut, 1);
+            }
+            Environment::Function => {
+                // movzx esi, byte [rbx]
+                self.code.extend_from_slice(&[0x0F, 0xB6, 0x33]);
+                self.call_write();
+            }
+        }
+    }
+
+    /// Reads a byte into the current cell unless at end of file
+    fn input(&mut self) {
+        match self.environment {
+            Environment::Executable(_) => {
+                self.mov_rsi_address(Symbol::Prompt, 0);
+                self.sys_call(X86_64_LINUX.sys_calls.write, FileDescriptor::StdOut, PROMPT.len());
+                self.mov_rsi_rbx();
+                self.sys_call(X86_64_LINUX.sys_calls.read, FileDescriptor::StdIn, 1);
+            }
+            Environment::Function => {
+                // mov rdi, r12
+                self.code.extend_from_slice(&[0x4C, 0x89, 0xE7]);
+                // call r13
+                self.code.extend_from_slice(&[0x41, 0xFF, 0xD5]);
+                // test eax, eax
+                self.code.extend_from_slice(&[0x85, 0xC0]);
+                // js over the store
+                self.code.extend_from_slice(&[0x78, 0x02]);
+                // mov byte [rbx], al
+                self.code.extend_from_slice(&[0x88, 0x03]);
+            }
+        }
+    }
+
+    /// Prints output computed at compile time
+    fn print_precomputed_output(&mut self, output: &[u8]) {
+        match self.environment {
+            Environment::Executable(_) => {
+                let start = self.precomputed_output.len();
+                self.mov_rsi_address(Symbol::PrecomputedOutput, start);
+                self.sys_call(X86_64_LINUX.sys_calls.write, FileDescriptor::StdOut, output.len());
+            }
+            Environment::Function => output.iter().for_each(|&byte| {
+                // mov esi, imm32
+                self.code.push(0xBE);
+                self.code.extend_from_slice(&(byte as u32).to_le_bytes());
+                self.call_write();
+            }),
+        }
+        self.precomputed_output.extend_from_slice(output);
+    }
+
+    /// Calls the write callback with the byte in esi
+    fn call_write(&mut self) {
+        // mov rdi, r12
+        self.code.extend_from_slice(&[0x4C, 0x89, 0xE7]);
+        // call r14
+        self.code.extend_from_slice(&[0x41, 0xFF, 0xD6]);
+    }
+
+    // ----------------- [ ENVIRONMENT END ] ----------------- \\
+
+    // ----------------- [ TAPE DUMP ] ----------------- \\
+    // NOTE THE FORMAT IS THE ONE OF 'Tape::dump', BUILT IN THE DUMP BUFFER WITH 'rsi' POINTING
+    // TO THE HEX DIGITS, 'rdi' TO THE END OF THE TEXT, 'ecx' AT A CELL AND 'ebx' PAST THE LAST ONE
+
+    /// Writes the used part of the tape to stderr, from the first cell up to the last
+    /// one that is not zero or the current one
+    fn dump_tape(&mut self) {
+        self.start_routine(Routine::DumpTape);
+        // push rbx (kept for the exit status)
+        self.code.push(0x53);
+        // sub rbx, imm32 (index of the current cell)
+        self.code.extend_from_slice(&[0x48, 0x81, 0xEB]);
+        self.address(Symbol::Tape, 0);
+        self.mov_rsi_address(Symbol::DumpHeader, 0);
+        self.sys_call(X86_64_LINUX.sys_calls.write, FileDescriptor::StdErr, DUMP_HEADER.len());
+
+        self.mov_rsi_address(Symbol::HexDigits, 0);
+        // mov edi, imm32
+        self.code.push(0xBF);
+        self.address(Symbol::DumpBuffer, 0);
+        // mov edx, ebx
+        self.code.extend_from_slice(&[0x89, 0xDA]);
+        self.hex_digits(INDEX_DIGITS as u8, 0);
+        self.add_rdi(INDEX_DIGITS as u8);
+
+        // Look for the last cell that is not zero, stopping at the current one
+        // mov ecx, imm32
+        self.code.push(0xB9);
+        self.code.extend_from_slice(&(TAPE_LEN as u32).to_le_bytes());
+        let scan = self.code.len();
+        // sub ecx, 1
+        self.code.extend_from_slice(&[0x83, 0xE9, 0x01]);
+        // cmp ecx, ebx
+        self.code.extend_from_slice(&[0x39, 0xD9]);
+        let found = self.jump_rel32(&[0x0F, 0x84]);
+        // cmp byte [rcx+imm32], 0
+        self.code.extend_from_slice(&[0x80, 0xB9]);
+        self.address(Symbol::Tape, 0);
+        self.code.push(0x00);
+        let next = self.jump_rel32(&[0x0F, 0x84]);
+        self.patch_jump(next, scan);
+        self.patch_jump(found, self.code.len());
+        // add ecx, 1
+        self.code.extend_from_slice(&[0x83, 0xC1, 0x01]);
+        // mov ebx, ecx
+        self.code.extend_from_slice(&[0x89, 0xCB]);
+        // xor ecx, ecx
+        self.code.extend_from_slice(&[0x31, 0xC9]);
+
+        let next_cell = self.code.len();
+        // cmp ecx, ebx
+        self.code.extend_from_slice(&[0x39, 0xD9]);
+        let done = self.jump_rel32(&[0x0F, 0x84]);
+        // mov eax, ecx
+        self.code.extend_from_slice(&[0x89, 0xC8]);
+        // and eax, imm8 (index in the line)
+        self.code
+            .extend_from_slice(&[0x83, 0xE0, (CELLS_PER_LINE - 1) as u8]);
+        let same_line = self.jump_rel32(&[0x0F, 0x85]);
+        // A new line starts with the index of its first cell
+        self.mov_rdi_byte(0, b'\n');
+        // mov edx, ecx
+        self.code.extend_from_slice(&[0x89, 0xCA]);
+        self.hex_digits(INDEX_DIGITS as u8, 1);
+        self.mov_rdi_byte(INDEX_DIGITS as u8 + 1, b':');
+        self.add_rdi(INDEX_DIGITS as u8 + 2);
+        self.patch_jump(same_line, self.code.len());
+        // movzx edx, byte [rcx+imm32]
+        self.code.extend_from_slice(&[0x0F, 0xB6, 0x91]);
+        self.address(Symbol::Tape, 0);
+        self.mov_rdi_byte(0, b' ');
+        self.hex_digits(CELL_DIGITS as u8, 1);
+        self.add_rdi(CELL_DIGITS as u8 + 1);
+        // add ecx, 1
+        self.code.extend_from_slice(&[0x83, 0xC1, 0x01]);
+        let repeat = self.jump_rel32(&[0xE9]);
+        self.patch_jump(repeat, next_cell);
+        self.patch_jump(done, self.code.len());
+
+        self.mov_rdi_byte(0, b'\n');
+        self.add_rdi(1);
+        // mov edx, edi
+        self.code.extend_from_slice(&[0x89, 0xFA]);
+        // sub edx, imm32 (length of the dump)
+        self.code.extend_from_slice(&[0x81, 0xEA]);
+        self.address(Symbol::DumpBuffer, 0);
+        self.mov_rsi_address(Symbol::DumpBuffer, 0);
+        // mov eax, imm32
+        self.code.push(0xB8);
+        self.code.extend_from_slice(&(X86_64_LINUX.sys_calls.write as u32).to_le_bytes());
+        // mov edi, imm32
+        self.code.push(0xBF);
+        self.code.extend_from_slice(&(FileDescriptor::StdErr as u32).to_le_bytes());
+        // syscall
+        self.code.extend_from_slice(&[0x0F, 0x05]);
+        // pop rbx
+        self.code.push(0x5B);
+        // ret
+        self.code.push(0xC3);
+    }
+
+    /// Writes the low `count` hex digits of edx at `rdi + disp`
+    fn hex_digits(&mut self, count: u8, disp: u8) {
+        for digit in 0..count {
+            // mov eax, edx
+            self.code.extend_from_slice(&[0x89, 0xD0]);
+            let shift = 4 * (count - 1 - digit);
+            if shift > 0 {
+                // shr eax, imm8
+                self.code.extend_from_slice(&[0xC1, 0xE8, shift]);
+            }
+            // and eax, 15
+            self.code.extend_from_slice(&[0x83, 0xE0, 0x0F]);
+            // mov al, byte [rsi+rax]
+            self.code.extend_from_slice(&[0x8A, 0x04, 0x06]);
+            // mov byte [rdi+disp8], al
+            self.code.extend_from_slice(&[0x88, 0x47, disp + digit]);
+        }
+    }
+
+    /// mov byte [rdi+disp8], imm8
+    fn mov_rdi_byte(&mut self, disp: u8, value: u8) {
+        self.code.extend_from_slice(&[0xC6, 0x47, disp, value]);
+    }
+
+    /// add rdi, imm8
+    fn add_rdi(&mut self, amount: u8) {
+        self.code.extend_from_slice(&[0x48, 0x83, 0xC7, amount]);
+    }
+
+    /// Makes the program dump its tape on `SIGUSR1`
+    fn install_signal_handler(&mut self) {
+        let restorer_at = X86_64_LINUX.sigaction_restorer_at as usize;
+        for (routine, offset) in [(Routine::SignalHandler, 0), (Routine::SignalRestorer, restorer_at)] {
+            // lea rax, [rip+rel32]
+            self.code.extend_from_slice(&[0x48, 0x8D, 0x05]);
+            self.routine_ref(routine);
+            // mov qword [imm32], rax
+            self.code.extend_from_slice(&[0x48, 0x89, 0x04, 0x25]);
+            self.address(Symbol::SigAction, offset);
+        }
+        // mov qword [imm32], imm32
+        self.code.extend_from_slice(&[0x48, 0xC7, 0x04, 0x25]);
+        self.address(Symbol::SigAction, X86_64_LINUX.sigaction_flags_at as usize);
+        self.code.extend_from_slice(&(SIGACTION_FLAGS as u32).to_le_bytes());
+
+        // mov eax, imm32
+        self.code.push(0xB8);
+        self.code.extend_from_slice(&(X86_64_LINUX.sys_calls.rt_sigaction as u32).to_le_bytes());
+        // mov edi, imm32
+        self.code.push(0xBF);
+        self.code.extend_from_slice(&(SIGUSR1 as u32).to_le_bytes());
+        self.mov_rsi_address(Symbol::SigAction, 0);
+        // xor edx, edx (no old action)
+        self.code.extend_from_slice(&[0x31, 0xD2]);
+        // mov r10d, imm32 (size of the signal mask)
+        self.code.extend_from_slice(&[0x41, 0xBA]);
+        self.code.extend_from_slice(&8u32.to_le_bytes());
+        // syscall
+        self.code.extend_from_slice(&[0x0F, 0x05]);
+    }
+
+    /// Dumps the tape with the rbx of the interrupted code, which gets it back on return
+    fn signal_handler(&mut self) {
+        self.start_routine(Routine::SignalHandler);
+        // mov rbx, qword [rdx+disp32]
+        self.code.extend_from_slice(&[0x48, 0x8B, 0x9A]);
+        self.code.extend_from_slice(&(X86_64_LINUX.ucontext_cell_ptr as u32).to_le_bytes());
+        self.call(Routine::DumpTape);
+        // ret
+        self.code.push(0xC3);
+
+        self.start_routine(Routine::SignalRestorer);
+        // mov eax, imm32
+        self.code.push(0xB8);
+        self.code.extend_from_slice(&(X86_64_LINUX.sys_calls.rt_sigreturn as u32).to_le_bytes());
+        // syscall
+        self.code.extend_from_slice(&[0x0F, 0x05]);
+    }
+
+    /// call rel32
+    fn call(&mut self, routine: Routine) {
+        self.code.push(0xE8);
+        self.routine_ref(routine);
+    }
+
+    /// Leaves room for the rel32 of a routine, filled in once the whole program is encoded.
+    fn routine_ref(&mut self, routine: Routine) {
+        self.routine_refs.push((self.code.len(), routine));
+        self.code.extend_from_slice(&[0; 4]);
+    }
+
+    /// Marks the start of a routine
+    fn start_routine(&mut self, routine: Routine) {
+        self.routines.push((routine, self.code.len()));
+    }
+
+    /// Points the rel32s of the routines at their start.
+    fn patch_routine_refs(&mut self) {
+        for (position, routine) in std::mem::take(&mut self.routine_refs) {
+            let start = self
+                .routines
+                .iter()
+                .find(|(other, _)| *other == routine)
+                .map(|(_, start)| *start)
+                .expect("Every routine called is encoded");
+            self.patch_jump(position, start);
+        }
+    }
+
+    // ----------------- [ TAPE DUMP END ] ----------------- \\
+
+    // ----------------- [ INSTRUCTIONS ] ----------------- \\
+
+    /// add rbx, imm32
+    fn move_ptr(&mut self, amount: isize) {
+        self.code.extend_from_slice(&[0x48, 0x81, 0xC3]);
+        self.code.extend_from_slice(&(amount as i32).to_le_bytes());
+    }
+
+    /// add byte [rbx+disp32], imm8
+    fn add(&mut self, amount: isize, offset: isize) {
+        // Cells wrap around so only the low byte of the amount matters
+        self.code.extend_from_slice(&[0x80, 0x83]);
+        self.code.extend_from_slice(&(offset as i32).to_le_bytes());
+        self.code.push(amount as u8);
+    }
+
+    /// mov byte [rbx+disp32], imm8
+    fn mov_cell(&mut self, offset: isize, value: u8) {
+        self.code.extend_from_slice(&[0xC6, 0x83]);
+        self.code.extend_from_slice(&(offset as i32).to_le_bytes());
+        self.code.push(value);
+    }
+
+    /// cmp byte [rbx], 0
+    fn cmp_cell_zero(&mut self) {
+        self.code.extend_from_slice(&[0x80, 0x3B, 0x00]);
+    }
+
+    /// Emits a jump with the given opcode and a rel32 to patch later.
+    ///
+    /// # Returns
+    ///
+    /// The index of the rel32 in the code.
+    fn jump_rel32(&mut self, opcode: &[u8]) -> usize {
+        self.code.extend_from_slice(opcode);
+        self.code.extend_from_slice(&[0; 4]);
+        self.code.len() - 4
+    }
+
+    /// Points the rel32 at `position` to the code at `target`.
+    fn patch_jump(&mut self, position: usize, target: usize) {
+        // Jumps are relative to the end of the instruction
+        let rel = target as i64 - (position + 4) as i64;
+        self.code[position..position + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+    }
+
+    /// mov ebx, imm32 (zero extended into rbx)
+    fn mov_rbx_address(&mut self, symbol: Symbol, addend: usize) {
+        self.code.push(0xBB);
+        self.address(symbol, addend);
+    }
+
+    /// mov esi, imm32 (zero extended into rsi)
+    fn mov_rsi_address(&mut self, symbol: Symbol, addend: usize) {
+        self.code.push(0xBE);
+        self.address(symbol, addend);
+    }
+
+    /// mov rsi, rbx
+    fn mov_rsi_rbx(&mut self) {
+        self.code.extend_from_slice(&[0x48, 0x89, 0xDE]);
+    }
+
+    /// Leaves room for an absolute address and records it to be filled in.
+    fn address(&mut self, symbol: Symbol, addend: usize) {
+        self.fixups.push(Fixup {
+            position: self.code.len(),
+            symbol,
+            addend,
+        });
+        self.code.extend_from_slice(&[0; 4]);
+    }
+
+    /// Template for a syscall taking its buffer from rsi
+    fn sys_call(&mut self, sys_call: i64, file_descriptor: FileDescriptor, len: usize) {
+        // mov eax, imm32
+        self.code.push(0xB8);
+        self.code.extend_from_slice(&(sys_call as u32).to_le_bytes());
+        // mov edi, imm32
+        self.code.push(0xBF);
+        self.code.extend_from_slice(&(file_descriptor as u32).to_le_bytes());
+        // mov edx, imm32
+        self.code.push(0xBA);
+        self.code.extend_from_slice(&(len as u32).to_le_bytes());
+        // syscall
+        self.code.extend_from_slice(&[0x0F, 0x05]);
+    }
+
+    /// Exits the program with status 0 or the current cell
+    fn exit(&mut self, exit_code: ExitCode) {
+        // mov eax, 60
+        self.code.push(0xB8);
+        self.code.extend_from_slice(&(X86_64_LINUX.sys_calls.exit as u32).to_le_bytes());
+        match exit_code {
+            // xor edi, edi
+            ExitCode::Zero => self.code.extend_from_slice(&[0x31, 0xFF]),
+            // movzx edi, byte [rbx]
+            ExitCode::Cell => self.code.extend_from_slice(&[0x0F, 0xB6, 0x3B]),
+        }
+        // syscall
+        self.code.extend_from_slice(&[0x0F, 0x05]);
+    }
+
+    // ----------------- [ INSTRUCTIONS END ] ----------------- \\
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm_generator::TEST_OPTIONS;
+    use crate::optimizer::PassManager;
+    use crate::parser::Parser;
+
+    /// Gives an encoder without any code
+    fn empty() -> X86_64Encoder {
+        X86_64Encoder {
+            environment: Environment::Executable(TEST_OPTIONS),
+            code: vec![],
+            fixups: vec![],
+            exits: vec![],
+            routines: vec![],
+            routine_refs: vec![],
+            used_stdin: false,
+            precomputed_output: vec![],
+        }
+    }
+
+    #[test]
+    fn encodes_pointer_moves() {
+        let mut encoder = empty();
+        encoder.move_ptr(3);
+        encoder.move_ptr(-2);
+        assert_eq!(
+            encoder.code(),
+            [0x48, 0x81, 0xC3, 3, 0, 0, 0, 0x48, 0x81, 0xC3, 0xFE, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn encodes_cell_changes() {
+        let mut encoder = empty();
+        encoder.add(258, -1);
+        encoder.add(-1, 3);
+        encoder.mov_cell(1, 7);
+        assert_eq!(
+            encoder.code(),
+            [
+                0x80, 0x83, 0xFF, 0xFF, 0xFF, 0xFF, 2,
+                0x80, 0x83, 3, 0, 0, 0, 0xFF,
+                0xC6, 0x83, 1, 0, 0, 0, 7,
+            ]
+        );
+    }
+
+    #[test]
+    fn patches_jumps_relative_to_their_end() {
+        let mut encoder = empty();
+        let forward = encoder.jump_rel32(&[0xE9]);
+        encoder.cmp_cell_zero();
+        encoder.patch_jump(forward, encoder.code.len());
+        let backward = encoder.jump_rel32(&[0x0F, 0x85]);
+        encoder.patch_jump(backward, 0);
+        assert_eq!(
+            encoder.code(),
+            [
+                0xE9, 3, 0, 0, 0,
+                0x80, 0x3B, 0x00,
+                0x0F, 0x85, 0xF2, 0xFF, 0xFF, 0xFF,
+            ]
+        );
+    }
+
+    #[test]
+    fn jumps_over_and_back_into_loops() {
+        let mut parser = Parser::new(String::from("[-]"), PassManager::with_level(0));
+        parser.generate_syntax_tree();
+        let encoder = X86_64Encoder::encode(parser.get_ast().unwrap(), Environment::Executable(TEST_OPTIONS));
+
+        assert_eq!(
+            encoder.code(),
+            [
+                0xBB, 0, 0, 0, 0,
+                0x80, 0x3B, 0x00,
+                0x0F, 0x84, 16, 0, 0, 0,
+                0x80, 0x83, 0, 0, 0, 0, 0xFF,
+                0x80, 0x3B, 0x00,
+                0x0F, 0x85, 0xF0, 0xFF, 0xFF, 0xFF,
+                0xB8, 60, 0, 0, 0,
+                0x31, 0xFF,
+                0x0F, 0x05,
+            ]
+        );
+        assert_eq!(encoder.fixups().len(), 1);
+        assert_eq!(encoder.fixups()[0].position, 1);
+        assert_eq!(encoder.fixups()[0].symbol, Symbol::Tape);
+    }
+}
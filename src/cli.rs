@@ -0,0 +1,379 @@
+use std::env;
+
+use colored::Colorize;
+
+use crate::asm_generator::{CodegenOptions, ExitCode, TapeDump, Target};
+use crate::ir_dump::IrDump;
+use crate::jit::JitProgram;
+use crate::optimizer::{Pass, PassManager, MAX_OPT_LEVEL};
+
+/// Program read when no input file is given
+const DEFAULT_INPUT_PATH: &str = "resources/program.bfk";
+/// File written when no output file is given
+const DEFAULT_OUTPUT_PATH: &str = "resources/program.asm";
+/// Output path standing for stdout
+const STDOUT_PATH: &str = "-";
+/// Optimization level used when no `-O` flag is given
+const DEFAULT_OPT_LEVEL: usize = 2;
+
+/// Ways to run a program in memory instead of writing it to a file.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Engine {
+    /// x86-64 machine code called in the compiler process
+    Jit,
+    /// Bytecode running on a portable virtual machine
+    Vm,
+}
+
+impl Engine {
+    /// Every engine that can be picked
+    pub const ALL: [Engine; 2] = [Engine::Jit, Engine::Vm];
+
+    /// Gives the name used for the engine on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::Jit => "jit",
+            Engine::Vm => "vm",
+        }
+    }
+
+    /// Finds the engine with the given command line name.
+    pub fn from_name(name: &str) -> Option<Engine> {
+        Self::ALL.into_iter().find(|engine| engine.name() == name)
+    }
+
+    /// Gives the fastest engine the machine the compiler runs on supports.
+    pub fn host() -> Engine {
+        if JitProgram::is_supported() {
+            Engine::Jit
+        } else {
+            Engine::Vm
+        }
+    }
+}
+
+/// Options the compiler was started with.
+#[derive(Debug)]
+pub struct Options {
+    /// Path of the Brain FK program
+    pub input_path: String,
+    /// Path of the assembly file to generate, `-` for stdout
+    pub output_path: String,
+    /// Architecture to generate the assembly for
+    pub target: Target,
+    /// Level picked with `-O`
+    pub opt_level: usize,
+    /// Passes picked with `--pass`, replacing the ones of the level
+    pub passes: Option<Vec<Pass>>,
+    /// Passes removed with `--disable-pass`
+    pub disabled_passes: Vec<Pass>,
+    /// Flag indicating whether to repeat the passes until nothing changes
+    pub fixed_point: bool,
+    /// Flag indicating whether to print statistics for every pass
+    pub print_stats: bool,
+    /// Dumps of the AST to print around every pass
+    pub ir_dump: IrDump,
+    /// Engine to run the program with in memory instead of writing a file
+    pub run: Option<Engine>,
+    /// What the program exits with
+    pub exit_code: ExitCode,
+    /// When the program dumps its tape to stderr
+    pub tape_dump: TapeDump,
+    /// Flag indicating whether to put debug information for the source in the assembly
+    pub debug_info: bool,
+    /// Flag indicating whether to annotate the generated code with the source
+    pub annotate: bool,
+}
+
+impl Options {
+    /// Parses the command line arguments, exiting with a usage message when they are invalid.
+    ///
+    /// # Returns
+    ///
+    /// The `Options` given on the command line.
+    pub fn from_args() -> Self {
+        let args: Vec<String> = env::args().collect();
+        Self::parse(&args)
+    }
+
+    /// Parses arguments given the way they are on the command line.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments, starting with the name of the program.
+    ///
+    /// # Returns
+    ///
+    /// The `Options` given by the arguments.
+    fn parse(args: &[String]) -> Self {
+        let mut options = Options {
+            input_path: String::from(DEFAULT_INPUT_PATH),
+            output_path: String::from(DEFAULT_OUTPUT_PATH),
+            // Only used when no target is given on a machine without one, which is
+            // an error unless the program runs in memory
+            target: Target::host().unwrap_or(Target::X86_64Linux),
+            opt_level: DEFAULT_OPT_LEVEL,
+            passes: None,
+            disabled_passes: vec![],
+            fixed_point: false,
+            print_stats: false,
+            ir_dump: IrDump::default(),
+            run: None,
+            exit_code: ExitCode::Zero,
+            tape_dump: TapeDump::Off,
+            debug_info: false,
+            annotate: false,
+        };
+        let mut positional: Vec<&String> = vec![];
+        let mut target_given = false;
+        let mut output_path: Option<&String> = None;
+
+        let mut flags = args[1..].iter();
+        while let Some(arg) = flags.next() {
+            if arg == "-o" {
+                output_path = match flags.next() {
+                    Some(path) => Some(path),
+                    None => Self::usage_err(&args[0], "Missing the output file after '-o'"),
+                };
+            } else if let Some(level) = arg.strip_prefix("-O") {
+                options.opt_level = match level.parse::<usize>() {
+                    Ok(level) if level <= MAX_OPT_LEVEL => level,
+                    _ => Self::usage_err(&args[0], &format!("Unknown optimization level '{}'", arg)),
+                };
+            } else if let Some(names) = arg.strip_prefix("--pass=") {
+                options.passes = Some(Self::parse_passes(&args[0], names));
+            } else if let Some(names) = arg.strip_prefix("--disable-pass=") {
+                options.disabled_passes.extend(Self::parse_passes(&args[0], names));
+            } else if let Some(name) = arg.strip_prefix("--target=") {
+                options.target = match Target::from_name(name) {
+                    Some(target) => target,
+                    None => Self::usage_err(&args[0], &format!("Unknown target '{}'", name)),
+                };
+                target_given = true;
+            } else if arg == "-g" {
+                options.debug_info = true;
+            } else if arg == "--annotate" {
+                options.annotate = true;
+            } else if arg == "--fixed-point" {
+                options.fixed_point = true;
+            } else if arg == "--stats" {
+                options.print_stats = true;
+            } else if arg == "--print-before-each-pass" {
+                options.ir_dump.before_each_pass = true;
+            } else if arg == "--print-after-each-pass" {
+                options.ir_dump.after_each_pass = true;
+            } else if arg == "--print-pass-diff" {
+                options.ir_dump.diff_each_pass = true;
+            } else if arg == "--run" {
+                options.run = Some(Engine::host());
+            } else if let Some(name) = arg.strip_prefix("--run=") {
+                options.run = match Engine::from_name(name) {
+                    Some(engine) => Some(engine),
+                    None => Self::usage_err(&args[0], &format!("Unknown engine '{}'", name)),
+                };
+            } else if let Some(name) = arg.strip_prefix("--exit-code=") {
+                options.exit_code = match ExitCode::from_name(name) {
+                    Some(exit_code) => exit_code,
+                    None => Self::usage_err(&args[0], &format!("Unknown exit code '{}'", name)),
+                };
+            } else if arg == "--dump-tape" {
+                options.tape_dump = TapeDump::Exit;
+            } else if let Some(name) = arg.strip_prefix("--dump-tape=") {
+                options.tape_dump = match TapeDump::from_name(name) {
+                    Some(tape_dump) => tape_dump,
+                    None => Self::usage_err(&args[0], &format!("Unknown tape dump '{}'", name)),
+                };
+            } else if arg.starts_with('-') && arg != STDOUT_PATH {
+                Self::usage_err(&args[0], &format!("Unknown flag '{}'", arg));
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        match positional.as_slice() {
+            [] => {}
+            [input] => options.input_path = input.to_string(),
+            [_, _] if output_path.is_some() => {
+                Self::usage_err(&args[0], "The output file was given twice")
+            }
+            [input, output] => {
+                options.input_path = input.to_string();
+                options.output_path = output.to_string();
+            }
+            _ => Self::usage_err(&args[0], "Too many files were given"),
+        }
+        if let Some(path) = output_path {
+            options.output_path = path.to_string();
+        }
+        if options.tape_dump == TapeDump::Signal
+            && (options.run.is_some() || !options.target.handles_signals())
+        {
+            Self::usage_err(&args[0], "Dumping the tape on a signal needs an x86 Linux target");
+        }
+        if options.debug_info && (options.run.is_some() || !options.target.has_debug_info()) {
+            Self::usage_err(&args[0], "Debug information needs the i386-linux or x86_64-linux target");
+        }
+
+        if !target_given && options.run.is_none() && Target::host().is_none() {
+            Self::usage_err(
+                &args[0],
+                "There is no default target for this machine, pick one with '--target'",
+            );
+        }
+
+        options
+    }
+
+    /// Whether the output goes to stdout instead of a file.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.output_path == STDOUT_PATH
+    }
+
+    /// Gives the runtime behaviour picked for the generated program.
+    pub fn codegen_options(&self) -> CodegenOptions {
+        CodegenOptions {
+            exit_code: self.exit_code,
+            tape_dump: self.tape_dump,
+            debug_info: self.debug_info,
+        }
+    }
+
+    /// Builds the pass manager described by the optimization flags.
+    ///
+    /// # Returns
+    ///
+    /// The `PassManager` to optimize the program with.
+    pub fn pass_manager(&self) -> PassManager {
+        let level = PassManager::with_level(self.opt_level);
+        let mut pass_manager = match &self.passes {
+            Some(passes) => PassManager::new(passes.clone(), self.fixed_point),
+            None if self.fixed_point => PassManager::new(level.pipeline().to_vec(), true),
+            None => level,
+        };
+
+        for &pass in &self.disabled_passes {
+            pass_manager.disable(pass);
+        }
+        pass_manager.set_ir_dump(self.ir_dump);
+        pass_manager
+    }
+
+    /// Parses a comma separated list of pass names.
+    fn parse_passes(program_name: &str, names: &str) -> Vec<Pass> {
+        names
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(|name| match Pass::from_name(name) {
+                Some(pass) => pass,
+                None => Self::usage_err(program_name, &format!("Unknown pass '{}'", name)),
+            })
+            .collect()
+    }
+
+    /// Prints what went wrong with the arguments along with the usage and exits.
+    fn usage_err(program_name: &str, message: &str) -> ! {
+        let passes: Vec<&str> = Pass::ALL.iter().map(|pass| pass.name()).collect();
+        let targets: Vec<&str> = Target::ALL.iter().map(|target| target.name()).collect();
+        let engines: Vec<&str> = Engine::ALL.iter().map(|engine| engine.name()).collect();
+        let exit_codes: Vec<&str> = ExitCode::ALL.iter().map(|exit_code| exit_code.name()).collect();
+        let tape_dumps: Vec<&str> = TapeDump::ALL.iter().map(|tape_dump| tape_dump.name()).collect();
+
+        eprintln!("{}: {}", "Error".red(), message);
+        eprintln!(
+            r#"
+Usage: {} [input_file] [output_file] [options]
+
+Options:
+    -o <output_file>        File to write, `-` for stdout (default {})
+    --target=<target>       Architecture to generate assembly for (default {})
+    -O<level>               Optimization level from 0 to {} (default {})
+    --pass=<p1,p2,..>       Run exactly these passes in order
+    --disable-pass=<p1,..>  Skip these passes
+    --fixed-point           Repeat the passes until they stop changing the program
+    --stats                 Print statistics for every pass
+    --print-before-each-pass
+                            Print the program before every pass
+    --print-after-each-pass Print the program after every pass
+    --print-pass-diff       Print what every pass changed in the program
+    --run[=<engine>]        Run the program in memory instead (default {})
+    --exit-code=<code>      What the program exits with, one of {} (default {})
+    --dump-tape[=<when>]    Write the tape to stderr, on one of {} (default {})
+    -g                      Add debug information so gdb can step through the source
+    --annotate              Put the source line above the code generated for it
+
+Targets: {} (llvm needs LLVM 15 or later)
+Engines: {}
+Passes: {}"#,
+            program_name,
+            DEFAULT_OUTPUT_PATH,
+            Target::host().map_or("none", |target| target.name()),
+            MAX_OPT_LEVEL,
+            DEFAULT_OPT_LEVEL,
+            Engine::host().name(),
+            exit_codes.join(", "),
+            ExitCode::Zero.name(),
+            tape_dumps.join(", "),
+            TapeDump::Exit.name(),
+            targets.join(", "),
+            engines.join(", "),
+            passes.join(", ")
+        );
+        std::process::exit(404);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the flags as if they followed the name of the program.
+    fn parse(flags: &[&str]) -> Options {
+        let args: Vec<String> = ["BrainFkCompiler"]
+            .iter()
+            .chain(flags)
+            .map(|arg| arg.to_string())
+            .collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn disable_pass_removes_passes_from_the_level() {
+        let options = parse(&["-O3", "--disable-pass=partial-eval,dead-loops"]);
+        assert_eq!(options.pass_manager().pipeline(), [Pass::Combine, Pass::Offsets]);
+    }
+
+    #[test]
+    fn disable_pass_removes_passes_picked_with_pass() {
+        let options = parse(&["--pass=offsets,combine", "--disable-pass=combine"]);
+        assert_eq!(options.pass_manager().pipeline(), [Pass::Offsets]);
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn defaults_to_the_host_target() {
+        assert_eq!(Some(parse(&[]).target), Target::host());
+        assert_eq!(parse(&["--target=i386-linux"]).target, Target::I386Linux);
+    }
+
+    #[test]
+    fn run_picks_the_engine() {
+        assert_eq!(parse(&[]).run, None);
+        assert_eq!(parse(&["--run"]).run, Some(Engine::host()));
+        assert_eq!(parse(&["--run=vm"]).run, Some(Engine::Vm));
+        assert_eq!(parse(&["--run=jit"]).run, Some(Engine::Jit));
+    }
+
+    #[test]
+    fn exit_code_defaults_to_zero() {
+        assert_eq!(parse(&[]).exit_code, ExitCode::Zero);
+        assert_eq!(parse(&["--exit-code=cell"]).exit_code, ExitCode::Cell);
+        assert_eq!(parse(&["--exit-code=zero"]).exit_code, ExitCode::Zero);
+    }
+
+    #[test]
+    fn fixed_point_keeps_the_passes_of_the_level() {
+        let options = parse(&["-O1", "--fixed-point"]);
+        assert!(options.fixed_point);
+        assert_eq!(options.pass_manager().pipeline(), [Pass::DeadLoops, Pass::Combine]);
+    }
+}
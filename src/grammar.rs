@@ -48,19 +48,51 @@
 /// ```
 /// #[derive(Debug)]
 /// pub enum Expression {
-///     Loop(Vec<Expression>),
+///     Loop(Vec<Expression>, Span),
 ///     Operator(Box<Operators>),
+///     Snapshot(Box<Snapshot>),
 /// }
 /// ```
 ///
 /// # Operator
-/// Struct representing operators with type and count.
+/// Struct representing operators with type, count and the offset from the cell pointer
+/// of the cell they act on.
 ///
 /// ```
 /// #[derive(Debug)]
 /// pub struct Operator {
 ///     pub _type_name: Token,
 ///     pub _count: u32,
+///     pub _offset: isize,
+///     pub _span: Span,
+/// }
+/// ```
+///
+/// # Snapshot
+/// Struct representing the state of the machine after a part of the program
+/// was evaluated at compile time.
+///
+/// ```
+/// #[derive(Debug)]
+/// pub struct Snapshot {
+///     pub _tape: Vec<u8>,
+///     pub _cell_ptr: usize,
+///     pub _output: Vec<u8>,
+///     pub _span: Span,
+/// }
+/// ```
+///
+/// # Span
+/// Struct representing the range of the source a part of the program comes from,
+/// loops spanning from their `[` to their `]`.
+///
+/// ```
+/// #[derive(Debug, Copy, Clone)]
+/// pub struct Span {
+///     pub _line: usize,
+///     pub _column: usize,
+///     pub _end_line: usize,
+///     pub _end_column: usize,
 /// }
 /// ```
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -75,14 +107,79 @@ pub enum Token {
     LoopEnd = ']' as isize,
 }
 
-#[derive(Debug)]
+/// Number of cells on the tape
+pub const TAPE_LEN: usize = 256;
+
+/// Printed by every target before it reads a character of input
+pub static PROMPT: &str = "Enter a character: ";
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
-    Loop(Vec<Expression>),
+    Loop(Vec<Expression>, Span),
     Operator(Box<Operator>),
+    Snapshot(Box<Snapshot>),
+}
+
+impl Expression {
+    /// Gives the range of the source the expression comes from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Loop(_, span) => *span,
+            Expression::Operator(op) => op.span,
+            Expression::Snapshot(snapshot) => snapshot.span,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Operator {
     pub type_name: Token,
     pub count: usize,
+    /// Offset of the cell operated on relative to the cell pointer
+    pub offset: isize,
+    /// Source of the tokens grouped into the operator
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// Cells of the tape up to the last non zero one
+    pub tape: Vec<u8>,
+    /// Position of the cell pointer
+    pub cell_ptr: usize,
+    /// Everything printed while getting to this state
+    pub output: Vec<u8>,
+    /// Source of the expressions evaluated to get to this state
+    pub span: Span,
+}
+
+/// Range of the source, lines counting from 1 and columns from 0 with the end column
+/// being one past the last character
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// Gives the span of the character at `column` on `line`.
+    pub fn char(line: usize, column: usize) -> Self {
+        Span {
+            line,
+            column,
+            end_line: line,
+            end_column: column + 1,
+        }
+    }
+
+    /// Gives the span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Self {
+        Span {
+            end_line: other.end_line,
+            end_column: other.end_column,
+            ..self
+        }
+    }
 }
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use crate::grammar::{Expression, Snapshot, Span, Token, TAPE_LEN};
+
+/// Reasons for the interpreter to stop before the end of the program.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Halt {
+    /// The program asked for input and the interpreter was given none
+    NeedsInput,
+    /// The step budget was used up
+    OutOfSteps,
+    /// The cell pointer left the tape
+    OutOfBounds,
+}
+
+/// Tree walking interpreter implementing the reference semantics of the language.
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    /// Cells of the program
+    tape: Vec<u8>,
+    /// Index of the current cell
+    cell_ptr: usize,
+    /// Number of instructions that may still be executed
+    steps_left: usize,
+    /// Bytes left for `,` to read, if the interpreter was given input
+    input: Option<VecDeque<u8>>,
+}
+
+impl Interpreter {
+    /// Creates an interpreter with a zeroed tape.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_steps` - The number of instructions it may execute before halting.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Interpreter`.
+    pub fn new(max_steps: usize) -> Self {
+        Interpreter {
+            tape: vec![0; TAPE_LEN],
+            cell_ptr: 0,
+            steps_left: max_steps,
+            input: None,
+        }
+    }
+
+    /// Gives the interpreter input for `,` to read, leaving the cell as it is once the
+    /// input runs out like the other engines do. Without input `,` halts it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The bytes to read.
+    ///
+    /// # Returns
+    ///
+    /// The `Interpreter` reading from `input`.
+    #[cfg(test)]
+    pub fn with_input(mut self, input: &[u8]) -> Self {
+        self.input = Some(input.iter().copied().collect());
+        self
+    }
+
+    /// Runs the expressions, appending everything printed to `output`.
+    ///
+    /// # Arguments
+    ///
+    /// * `expressions` - The expressions to execute.
+    /// * `output` - The buffer receiving the bytes printed.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if every expression was executed, otherwise the reason it stopped. The
+    /// state is left where the interpreter stopped.
+    pub fn run(&mut self, expressions: &[Expression], output: &mut Vec<u8>) -> Result<(), Halt> {
+        for expression in expressions {
+            self.step()?;
+            match expression {
+                Expression::Loop(_loop, _) => {
+                    while self.tape[self.cell_ptr] != 0 {
+                        self.run(_loop, output)?;
+                        self.step()?;
+                    }
+                }
+                Expression::Operator(op) => {
+                    let cell = self.cell_at(op.offset)?;
+                    match op.type_name {
+                        Token::MoveForward => self.cell_ptr = self.cell_at(op.count as isize)?,
+                        Token::MoveBack => self.cell_ptr = self.cell_at(-(op.count as isize))?,
+                        Token::Add => self.tape[cell] = self.tape[cell].wrapping_add(op.count as u8),
+                        Token::Sub => self.tape[cell] = self.tape[cell].wrapping_sub(op.count as u8),
+                        Token::StdOut => output.extend(std::iter::repeat_n(self.tape[cell], op.count)),
+                        Token::StdIn => {
+                            let input = self.input.as_mut().ok_or(Halt::NeedsInput)?;
+                            for _ in 0..op.count {
+                                if let Some(byte) = input.pop_front() {
+                                    self.tape[cell] = byte;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Expression::Snapshot(snapshot) => {
+                    self.tape.fill(0);
+                    self.tape[..snapshot.tape.len()].copy_from_slice(&snapshot.tape);
+                    self.cell_ptr = snapshot.cell_ptr;
+                    output.extend_from_slice(&snapshot.output);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Captures the current state of the machine.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - Everything printed to get to this state.
+    /// * `span` - Source of the expressions run to get to this state.
+    ///
+    /// # Returns
+    ///
+    /// The `Snapshot` of the tape and the cell pointer.
+    pub fn snapshot(&self, output: Vec<u8>, span: Span) -> Snapshot {
+        let used_len = self.tape.iter().rposition(|&cell| cell != 0).map_or(0, |idx| idx + 1);
+
+        Snapshot {
+            tape: self.tape[..used_len].to_vec(),
+            cell_ptr: self.cell_ptr,
+            output,
+            span,
+        }
+    }
+
+    /// Gives the whole tape and the cell pointer.
+    #[cfg(test)]
+    pub fn tape(&self) -> (&[u8], usize) {
+        (&self.tape, self.cell_ptr)
+    }
+
+    /// Gets the number of instructions that may still be executed.
+    pub fn steps_left(&self) -> usize {
+        self.steps_left
+    }
+
+    /// Uses up one step of the budget.
+    fn step(&mut self) -> Result<(), Halt> {
+        match self.steps_left.checked_sub(1) {
+            Some(steps_left) => {
+                self.steps_left = steps_left;
+                Ok(())
+            }
+            None => Err(Halt::OutOfSteps),
+        }
+    }
+
+    /// Gets the index of the cell `offset` cells away from the cell pointer.
+    fn cell_at(&self, offset: isize) -> Result<usize, Halt> {
+        self.cell_ptr
+            .checked_add_signed(offset)
+            .filter(|&cell| cell < TAPE_LEN)
+            .ok_or(Halt::OutOfBounds)
+    }
+}
+
+/// Programs along with their input, covering I/O, nested loops and moves folded into
+/// offsets, to check the other engines against the interpreter
+#[cfg(test)]
+pub static TEST_PROGRAMS: [(&str, &[u8]); 7] = [
+    (
+        "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.",
+        b"",
+    ),
+    (",[.[-],]", b"echo"),
+    (",>,<[->+<]>.", b"\x05\x07"),
+    (",+.,,.", b"a"),
+    ("+++[>++[>+++[>+<-]<-]<-]>>>.", b""),
+    (">>+++<<->>[-<+>>++<]<[->>>+<<<]>>>>-.<<.", b""),
+    ("+++++[->++>>+++<<<]>[->+<]>>[-]<.>>.", b""),
+];
+
+/// Removes the prompts printed before every read, which the interpreter leaves out.
+#[cfg(test)]
+pub fn without_prompts(output: &[u8], prompt: &str) -> Vec<u8> {
+    let prompt = prompt.as_bytes();
+    let mut stripped = vec![];
+    let mut idx = 0;
+    while idx < output.len() {
+        if output[idx..].starts_with(prompt) {
+            idx += prompt.len();
+        } else {
+            stripped.push(output[idx]);
+            idx += 1;
+        }
+    }
+    stripped
+}
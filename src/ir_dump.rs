@@ -0,0 +1,235 @@
+use colored::Colorize;
+
+use crate::grammar::{Expression, Operator, Token};
+
+/// Number of spaces each loop indents its body by
+const INDENT_WIDTH: usize = 4;
+/// Most entries in the table of common lines built by [diff], bounding its memory use
+/// when most of a large program changes
+const MAX_DIFF_TABLE_LEN: usize = 1 << 20;
+
+/// Which dumps of the abstract syntax tree (AST) to print around optimization passes.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct IrDump {
+    /// Print the AST before every pass
+    pub before_each_pass: bool,
+    /// Print the AST after every pass
+    pub after_each_pass: bool,
+    /// Print what every pass changed in the AST
+    pub diff_each_pass: bool,
+}
+
+impl IrDump {
+    /// Checks if any dump was asked for.
+    pub fn is_enabled(&self) -> bool {
+        self.before_each_pass || self.after_each_pass || self.diff_each_pass
+    }
+}
+
+/// Converts the AST into a readable listing with one instruction per line.
+///
+/// # Arguments
+///
+/// * `ast` - The abstract syntax tree (AST) to print.
+///
+/// # Returns
+///
+/// The listing of the AST, loop bodies indented under their loop.
+pub fn dump_ast(ast: &[Expression]) -> String {
+    let mut listing = String::new();
+    dump_expressions(ast, 0, &mut listing);
+    listing
+}
+
+/// Appends the listing of the expressions at the given loop depth.
+fn dump_expressions(expressions: &[Expression], depth: usize, listing: &mut String) {
+    let indent = " ".repeat(depth * INDENT_WIDTH);
+
+    for expression in expressions {
+        match expression {
+            Expression::Loop(_loop, _) if _loop.is_empty() => {
+                listing.push_str(&format!("{}loop {{}}\n", indent));
+            }
+            Expression::Loop(_loop, _) => {
+                listing.push_str(&format!("{}loop {{\n", indent));
+                dump_expressions(_loop, depth + 1, listing);
+                listing.push_str(&format!("{}}}\n", indent));
+            }
+            Expression::Operator(op) => {
+                listing.push_str(&format!("{}{}\n", indent, dump_operator(op)));
+            }
+            Expression::Snapshot(snapshot) => {
+                listing.push_str(&format!(
+                    "{}snapshot ptr={} tape={:?} output=\"{}\"\n",
+                    indent,
+                    snapshot.cell_ptr,
+                    snapshot.tape,
+                    snapshot.output.escape_ascii()
+                ));
+            }
+        }
+    }
+}
+
+/// Gives the line for a single operator, Eg = 'add 3 @ p+1', 'move -2'
+fn dump_operator(op: &Operator) -> String {
+    let cell = match op.offset {
+        0 => String::from("p"),
+        offset => format!("p{:+}", offset),
+    };
+
+    match op.type_name {
+        Token::MoveForward => format!("move +{}", op.count),
+        Token::MoveBack => format!("move -{}", op.count),
+        Token::Add => format!("add {} @ {}", op.count, cell),
+        Token::Sub => format!("sub {} @ {}", op.count, cell),
+        Token::StdOut | Token::StdIn => {
+            let name = if op.type_name == Token::StdOut {
+                "output"
+            } else {
+                "input"
+            };
+            match op.count {
+                1 => format!("{} @ {}", name, cell),
+                count => format!("{} @ {} x{}", name, cell, count),
+            }
+        }
+        token => format!("{:?}", token),
+    }
+}
+
+/// Builds a line by line diff of two listings.
+///
+/// # Arguments
+///
+/// * `before` - The listing before the change.
+/// * `after` - The listing after the change.
+///
+/// # Returns
+///
+/// Every line of both listings prefixed by `-` when removed, `+` when added and
+/// a space when kept.
+pub fn diff(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Lines shared at the ends don't take part in the search for common lines
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut listing = String::new();
+    old[..prefix]
+        .iter()
+        .for_each(|line| listing.push_str(&format!("  {}\n", line)));
+
+    if (old_mid.len() + 1).saturating_mul(new_mid.len() + 1) > MAX_DIFF_TABLE_LEN {
+        // Too much changed to search for the lines in common, replace all of them
+        old_mid.iter().for_each(|line| listing.push_str(&removed_line(line)));
+        new_mid.iter().for_each(|line| listing.push_str(&added_line(line)));
+    } else {
+        diff_lines(old_mid, new_mid, &mut listing);
+    }
+
+    old[old.len() - suffix..]
+        .iter()
+        .for_each(|line| listing.push_str(&format!("  {}\n", line)));
+    listing
+}
+
+/// Appends the diff of two listings with nothing in common at their ends, keeping the
+/// longest common subsequence of their lines.
+fn diff_lines(old: &[&str], new: &[&str], listing: &mut String) {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            listing.push_str(&format!("  {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            listing.push_str(&removed_line(old[i]));
+            i += 1;
+        } else {
+            listing.push_str(&added_line(new[j]));
+            j += 1;
+        }
+    }
+}
+
+/// Gives the line of a diff for a line that was removed.
+fn removed_line(line: &str) -> String {
+    format!("{}\n", format!("- {}", line).red())
+}
+
+/// Gives the line of a diff for a line that was added.
+fn added_line(line: &str) -> String {
+    format!("{}\n", format!("+ {}", line).green())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::PassManager;
+    use crate::parser::Parser;
+
+    /// Builds the diff without colors so it can be compared.
+    fn plain_diff(before: &str, after: &str) -> String {
+        colored::control::set_override(false);
+        diff(before, after)
+    }
+
+    #[test]
+    fn diff_keeps_common_lines() {
+        assert_eq!(plain_diff("a\nb\nc\n", "a\nx\nc\n"), "  a\n- b\n+ x\n  c\n");
+        assert_eq!(
+            plain_diff("a\nb\nc\nd\ne\n", "a\nc\nd\nf\ne\n"),
+            "  a\n- b\n  c\n  d\n+ f\n  e\n"
+        );
+        assert_eq!(plain_diff("a\nb\n", "a\nb\n"), "  a\n  b\n");
+    }
+
+    #[test]
+    fn diff_replaces_large_changes_whole() {
+        let lines = |prefix: &str| -> String {
+            (0..2000).map(|idx| format!("{}{}\n", prefix, idx)).collect()
+        };
+        let before = format!("start\n{}end\n", lines("old "));
+        let after = format!("start\n{}end\n", lines("new "));
+
+        let listing = plain_diff(&before, &after);
+        let listing: Vec<&str> = listing.lines().collect();
+        assert_eq!(listing.len(), 4002);
+        assert_eq!(listing[0], "  start");
+        assert!(listing[1..2001].iter().all(|line| line.starts_with("- old ")));
+        assert!(listing[2001..4001].iter().all(|line| line.starts_with("+ new ")));
+        assert_eq!(listing[4001], "  end");
+    }
+
+    #[test]
+    fn dumps_the_ast() {
+        let mut parser = Parser::new(String::from("+>[-<.>]"), PassManager::with_level(0));
+        parser.generate_syntax_tree();
+        assert_eq!(
+            dump_ast(parser.get_ast().unwrap()),
+            "add 1 @ p\nmove +1\nloop {\n    sub 1 @ p\n    move -1\n    output @ p\n    move +1\n}\n"
+        );
+    }
+}
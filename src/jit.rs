@@ -0,0 +1,263 @@
+use std::ffi::c_void;
+use std::io;
+use std::io::{Read, Write};
+use std::ptr;
+
+use crate::asm_generator::x86_64_encoder::{Environment, X86_64Encoder};
+use crate::grammar::{Expression, PROMPT, TAPE_LEN};
+use crate::tape::Tape;
+
+// ------------------- [ MMAP ] ------------------- \\
+// NOTE THE FLAGS ARE THE ONES OF x86-64 LINUX, THE ONLY HOST THE CODE CAN RUN ON
+
+const PROT_READ: i32 = 0x1;
+const PROT_WRITE: i32 = 0x2;
+const PROT_EXEC: i32 = 0x4;
+const MAP_PRIVATE: i32 = 0x02;
+const MAP_ANONYMOUS: i32 = 0x20;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: i32, flags: i32, fd: i32, offset: i64)
+        -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: i32) -> i32;
+    fn munmap(addr: *mut c_void, len: usize) -> i32;
+}
+
+// ------------------- [ MMAP END ] ------------------- \\
+
+/// Signature of the encoded program, see [Environment::Function]
+type JitFn = unsafe extern "C" fn(
+    tape: *mut u8,
+    context: *mut c_void,
+    read: extern "C" fn(*mut c_void) -> i32,
+    write: extern "C" fn(*mut c_void, u32),
+) -> usize;
+
+/// Where the callbacks of a running program read from and write to
+struct IoContext<'a> {
+    input: &'a mut dyn Read,
+    output: &'a mut dyn Write,
+    /// First error hit by a callback, which stops any further I/O
+    error: Option<io::Error>,
+}
+
+/// A program compiled to machine code in the memory of the compiler.
+///
+/// The code lives in its own executable mapping while the tape is allocated by Rust
+/// for every run, and `.` and `,` call back into Rust so the program can use any
+/// `Read` and `Write`. Unlike the assembly targets the code checks the cell pointer
+/// stays on the tape, as leaving it would corrupt the memory of the compiler.
+pub struct JitProgram {
+    /// Start of the mapping holding the code
+    code: *mut c_void,
+    /// Length of the mapping
+    len: usize,
+}
+
+impl JitProgram {
+    /// Whether the machine code can run on the host.
+    pub fn is_supported() -> bool {
+        cfg!(all(target_arch = "x86_64", target_os = "linux"))
+    }
+
+    /// Encodes the program and maps the machine code as executable.
+    ///
+    /// # Arguments
+    ///
+    /// * `syntax_tree` - The optimized program.
+    ///
+    /// # Returns
+    ///
+    /// The program ready to run, or the error of the failed mapping.
+    pub fn compile(syntax_tree: &[Expression]) -> Result<Self, io::Error> {
+        let encoder = X86_64Encoder::encode(syntax_tree, Environment::Function);
+        let code = encoder.code();
+
+        // The mapping is writable until the code is in and executable after
+        let len = code.len();
+        let mapping = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if mapping == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let program = JitProgram { code: mapping, len };
+
+        unsafe {
+            ptr::copy_nonoverlapping(code.as_ptr(), mapping as *mut u8, len);
+            if mprotect(mapping, len, PROT_READ | PROT_EXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(program)
+    }
+
+    /// Runs the program on a fresh tape.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Where `,` reads from, after printing a prompt to `output`.
+    /// * `output` - Where `.` writes to.
+    ///
+    /// # Returns
+    ///
+    /// The tape at the end of the program, or the first error hit while reading or
+    /// writing or when the cell pointer left the tape.
+    pub fn run(&self, input: &mut impl Read, output: &mut impl Write) -> Result<Tape, io::Error> {
+        let mut tape = [0u8; TAPE_LEN];
+        let mut context = IoContext {
+            input,
+            output,
+            error: None,
+        };
+
+        let cell_ptr = unsafe {
+            let function: JitFn = std::mem::transmute(self.code);
+            function(
+                tape.as_mut_ptr(),
+                &mut context as *mut IoContext as *mut c_void,
+                read_callback,
+                write_callback,
+            )
+        };
+
+        if let Some(error) = context.error {
+            return Err(error);
+        }
+        context.output.flush()?;
+        if cell_ptr < TAPE_LEN {
+            Ok(Tape {
+                cells: tape.to_vec(),
+                cell_ptr,
+            })
+        } else {
+            Err(io::Error::other("The cell pointer left the tape"))
+        }
+    }
+}
+
+impl Drop for JitProgram {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.code, self.len);
+        }
+    }
+}
+
+/// Prompts for a byte, giving -1 on end of file or on an error.
+extern "C" fn read_callback(context: *mut c_void) -> i32 {
+    let context = unsafe { &mut *(context as *mut IoContext) };
+    if context.error.is_some() {
+        return -1;
+    }
+
+    let mut byte = [0u8];
+    let result = context
+        .output
+        .write_all(PROMPT.as_bytes())
+        .and_then(|_| context.output.flush())
+        .and_then(|_| context.input.read(&mut byte));
+    match result {
+        Ok(1) => byte[0] as i32,
+        Ok(_) => -1,
+        Err(error) => {
+            context.error = Some(error);
+            -1
+        }
+    }
+}
+
+/// Prints a byte unless an error was already hit.
+extern "C" fn write_callback(context: *mut c_void, byte: u32) {
+    let context = unsafe { &mut *(context as *mut IoContext) };
+    if context.error.is_some() {
+        return;
+    }
+
+    if let Err(error) = context.output.write_all(&[byte as u8]) {
+        context.error = Some(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{without_prompts, Interpreter, TEST_PROGRAMS};
+    use crate::optimizer::{PassManager, MAX_OPT_LEVEL};
+    use crate::parser::Parser;
+
+    /// Parses a program and optimizes it at the given level.
+    fn parse(program: &str, opt_level: usize) -> Vec<Expression> {
+        let mut parser = Parser::new(program.to_string(), PassManager::with_level(opt_level));
+        parser.generate_syntax_tree();
+        parser.get_ast().unwrap().clone()
+    }
+
+    #[test]
+    fn runs_programs_like_the_interpreter() {
+        for (program, input) in TEST_PROGRAMS {
+            let mut interpreter = Interpreter::new(usize::MAX).with_input(input);
+            let mut expected_output = vec![];
+            interpreter.run(&parse(program, 0), &mut expected_output).unwrap();
+            let (expected_tape, expected_cell_ptr) = interpreter.tape();
+
+            for opt_level in 0..=MAX_OPT_LEVEL {
+                let mut output = vec![];
+                let tape = JitProgram::compile(&parse(program, opt_level))
+                    .unwrap()
+                    .run(&mut &input[..], &mut output)
+                    .unwrap();
+
+                assert_eq!(
+                    without_prompts(&output, PROMPT),
+                    expected_output,
+                    "Output of {} at -O{}",
+                    program,
+                    opt_level
+                );
+                assert_eq!(
+                    tape.cells[..],
+                    expected_tape[..],
+                    "Tape of {} at -O{}",
+                    program,
+                    opt_level
+                );
+                assert_eq!(
+                    tape.cell_ptr, expected_cell_ptr,
+                    "Cell pointer of {} at -O{}",
+                    program, opt_level
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stops_when_leaving_the_tape() {
+        // Off the end, off the start and through an offset ahead of the pointer
+        for program in ["+[>+]", "+>>,[<<]", "+[>+>+<]"] {
+            for opt_level in 0..=MAX_OPT_LEVEL {
+                let jit = JitProgram::compile(&parse(program, opt_level)).unwrap();
+                let error = jit.run(&mut &b"a"[..], &mut vec![]).expect_err(program);
+                assert_eq!(error.to_string(), "The cell pointer left the tape");
+            }
+        }
+    }
+
+    #[test]
+    fn gives_back_the_current_cell() {
+        for (program, cell) in [("+++>++<-", 2), ("+++>+", 1), (",[>+<-]>+", 98)] {
+            for opt_level in 0..=MAX_OPT_LEVEL {
+                let jit = JitProgram::compile(&parse(program, opt_level)).unwrap();
+                assert_eq!(jit.run(&mut &b"a"[..], &mut vec![]).unwrap().current_cell(), cell, "{}", program);
+            }
+        }
+    }
+}
@@ -1,12 +1,12 @@
 use colored::Colorize;
 
-use crate::grammar::Token;
+use crate::grammar::{Span, Token};
 
 /// Struct representing a lexer for a custom language.
 #[derive(Debug)]
 pub struct Lexer {
-    // Vector to store tokens
-    tokens_: Vec<Token>,
+    // Vector to store tokens along with where they are in the program
+    tokens_: Vec<(Token, Span)>,
     // // Line number in the program
     line_num_: usize,
     // // Character index in the current line
@@ -47,38 +47,39 @@ impl Lexer {
     /// # Arguments
     ///
     /// * `program` - The program string to be tokenized.
-    fn tokenize(&mut self, program: &String) {
+    fn tokenize(&mut self, program: &str) {
         // Iterate through characters in the program
         for (line_num, line) in program.lines().enumerate() {
             self.line_num_ = line_num + 1;
             for (char_index, curr_char) in line.chars().enumerate() {
                 self.line_idx_ = char_index;
+                let span = Span::char(self.line_num_, char_index);
                 match curr_char {
                     '>' => {
                         self.ptr_sim_ += 1;
-                        self.tokens_.push(Token::MoveForward);
+                        self.tokens_.push((Token::MoveForward, span));
                     }
                     '<' => {
                         self.ptr_sim_ -= 1;
                         if self.ptr_sim_ < 0 {
                             self.throw_run_err(line, char_index, "Index runs out of bounds");
                         }
-                        self.tokens_.push(Token::MoveBack);
+                        self.tokens_.push((Token::MoveBack, span));
                     }
-                    '+' => self.tokens_.push(Token::Add),
-                    '-' => self.tokens_.push(Token::Sub),
-                    '.' => self.tokens_.push(Token::StdOut),
-                    ',' => self.tokens_.push(Token::StdIn),
+                    '+' => self.tokens_.push((Token::Add, span)),
+                    '-' => self.tokens_.push((Token::Sub, span)),
+                    '.' => self.tokens_.push((Token::StdOut, span)),
+                    ',' => self.tokens_.push((Token::StdIn, span)),
                     '[' => {
                         self.brace_stack_ += 1;
-                        self.tokens_.push(Token::LoopStart);
+                        self.tokens_.push((Token::LoopStart, span));
                     }
                     ']' => {
                         self.brace_stack_ -= 1;
                         if self.brace_stack_ < 0 {
                             self.throw_run_err(line, char_index, "Not enough matches for ']'");
                         }
-                        self.tokens_.push(Token::LoopEnd);
+                        self.tokens_.push((Token::LoopEnd, span));
                     }
                     _ => {
                         if curr_char.is_whitespace() {
@@ -120,22 +121,12 @@ impl Lexer {
     ///
     /// # Returns
     ///
-    /// A vector containing the tokens that were previously stored in the Lexer instance.
-    pub fn move_tokens(&mut self) -> Vec<Token> {
+    /// A vector containing the tokens that were previously stored in the Lexer instance,
+    /// each with its span in the program.
+    pub fn move_tokens(&mut self) -> Vec<(Token, Span)> {
         std::mem::take(&mut self.tokens_)
     }
 
-    /// Returns an iterator over references to the tokens stored in the Lexer instance.
-    ///
-    /// This method allows iterating over the tokens without consuming the Lexer instance.
-    ///
-    /// # Returns
-    ///
-    /// An iterator yielding references to the tokens stored in the Lexer instance.
-    pub fn tokens_iter(&self) -> impl Iterator<Item=&Token> {
-        self.tokens_.iter()
-    }
-
 
     /// Function to handle and print runtime errors.
     ///
@@ -1,50 +1,144 @@
 use std::fs::File;
-use std::io::Read;
+use std::io;
+use std::io::{BufWriter, Read, Write};
 
 use colored::Colorize;
 
-use crate::asm_generator::AsmContext;
+use crate::asm_generator::{elf_writer, AsmContext, CodegenOptions, ExitCode, TapeDump};
+use crate::cli::{Engine, Options};
+use crate::grammar::Expression;
+use crate::jit::JitProgram;
 use crate::parser::Parser;
+use crate::vm::Vm;
 
 mod asm_generator;
+mod cli;
 mod grammar;
+mod interpreter;
+mod ir_dump;
+mod jit;
 mod lexer;
+mod optimizer;
 mod parser;
-
-/// Represents a Brainfuck program with its content and optimization flag.
-struct Program {
-    content: String,
-    should_optimise: bool,
-}
+mod tape;
+mod vm;
 
 /// Main entry point of the program. Reads a Brainfuck program from a file, parses it,
-/// optimizes it, and executes it using an interpreter.
+/// optimizes it, and compiles it to assembly.
 fn main() {
+    let options = Options::from_args();
+
     // Read the program from file
-    let Program {
-        content,
-        should_optimise,
-    } = read_file();
+    let content = read_file(&options.input_path);
 
     // Init a parser that takes the program and converts it to a token stream
-    let mut parser = Parser::new(content, should_optimise);
+    let mut parser = Parser::new(content.clone(), options.pass_manager());
 
     // Generates an abstract syntax tree for the program
     parser.generate_syntax_tree();
 
-    // Create the asm and generate the x86 representation of the Brain FK program
-    let mut asm_context = create_asm_context(&mut parser);
+    if options.print_stats {
+        // Kept out of stdout, which may be carrying the generated code
+        let _ = parser
+            .get_pass_manager()
+            .print_stats(&mut io::stderr(), parser.get_num_of_instr());
+    }
+
+    let syntax_tree = get_syntax_tree(&parser);
+
+    if let Some(engine) = options.run {
+        // Run the Brain FK program in this process instead of writing it out
+        run_program(syntax_tree, engine, options.codegen_options());
+        return;
+    }
 
-    asm_context.generate_asm();
+    if options.writes_to_stdout() {
+        // Nothing else may go to stdout so the output can be piped, Eg = into `as`
+        write_to_stdout(syntax_tree, &options, &content);
+        return;
+    }
 
-    println!("\n");
+    if options.target.is_executable() {
+        // Encode the Brain FK program straight into an executable
+        elf_writer::write_executable(syntax_tree, &options.output_path, options.codegen_options());
+    } else {
+        let asm_file = match File::create(&options.output_path) {
+            Ok(file) => file,
+            Err(error) => {
+                eprintln!("{:?}", error);
+                std::process::exit(404);
+            }
+        };
+
+        // Create the asm and generate the x86 representation of the Brain FK program
+        let mut asm_context =
+            new_asm_context(syntax_tree, BufWriter::new(asm_file), &options, &content);
+
+        match asm_context.generate_asm() {
+            Ok(_) => {
+                eprintln!("{}", "The ASM code was successfully generated".green());
+            }
+            Err(error) => {
+                eprintln!("{}", "Could not generate the ASM file".red());
+                eprintln!("{}", error)
+            }
+        };
+    }
+
+    eprintln!("\n");
 }
 
-fn create_asm_context(parser: &mut Parser) -> AsmContext {
+/// Creates the context generating the code for the target picked on the command line.
+///
+/// # Arguments
+///
+/// * `syntax_tree` - The optimized program.
+/// * `out` - Where the code gets written.
+/// * `options` - The options the compiler was started with.
+/// * `source` - The Brain FK program, to annotate the code with.
+fn new_asm_context<'a, W: Write>(
+    syntax_tree: &'a [Expression],
+    out: W,
+    options: &'a Options,
+    source: &'a str,
+) -> AsmContext<'a, W> {
+    let mut asm_context = AsmContext::new(
+        syntax_tree,
+        out,
+        options.target,
+        options.opt_level > 0,
+        options.codegen_options(),
+    );
+    if options.debug_info {
+        asm_context = asm_context.with_debug_info(&options.input_path);
+    }
+    if options.annotate {
+        asm_context = asm_context.with_annotations(source);
+    }
+    asm_context
+}
+
+/// Writes the code or the executable for the target to stdout.
+fn write_to_stdout(syntax_tree: &[Expression], options: &Options, source: &str) {
+    let stdout = &mut BufWriter::new(io::stdout().lock());
+
+    let result = if options.target.is_executable() {
+        stdout
+            .write_all(&elf_writer::encode_executable(syntax_tree, options.codegen_options()))
+            .and_then(|_| stdout.flush())
+    } else {
+        new_asm_context(syntax_tree, stdout, options, source).generate_asm()
+    };
+    if let Err(error) = result {
+        eprintln!("{}", "Could not write the output".red());
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn get_syntax_tree(parser: &Parser) -> &[Expression] {
     match parser.get_ast() {
-        Some(syntax_tree) => {
-            return AsmContext::new(syntax_tree, "resources/program.asm");
-        }
+        Some(syntax_tree) => syntax_tree,
         None => {
             eprintln!("{}", "Tree has not been generated yet".red());
             std::process::exit(255);
@@ -52,23 +146,41 @@ fn create_asm_context(parser: &mut Parser) -> AsmContext {
     }
 }
 
-/// Reads a Brainfuck program from a file and returns a `Program` struct containing
-/// the program content and optimization flag.
-fn read_file() -> Program {
-    // let args: Vec<String> = env::args().collect();
-    //
-    // if args.len() < 2 {
-    //     eprintln!("Usage: {} <filename> <flag[-0]>", args[0]);
-    //     std::process::exit(404);
-    // }
-    //
-    // let file_path = &args[1];
-    // let optimisation = args.len() > 2;
-
-    let file_path = String::from("resources/program.bfk");
-    let optimisation = true;
-
-    let mut file = match File::open(&file_path) {
+/// Runs the program in memory on stdin and stdout with the given engine, dumping the
+/// tape and exiting with the current cell when asked to.
+fn run_program(syntax_tree: &[Expression], engine: Engine, options: CodegenOptions) {
+    let input = &mut io::stdin().lock();
+    let output = &mut io::BufWriter::new(io::stdout().lock());
+
+    let result = match engine {
+        Engine::Jit => {
+            if !JitProgram::is_supported() {
+                eprintln!("{}", "The JIT needs an x86-64 Linux host, use --run=vm".red());
+                std::process::exit(1);
+            }
+            JitProgram::compile(syntax_tree).and_then(|program| program.run(input, output))
+        }
+        Engine::Vm => Vm::new(syntax_tree).run(input, output),
+    };
+    let tape = match result {
+        Ok(tape) => tape,
+        Err(error) => {
+            eprintln!("{}", "Could not run the program".red());
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    if options.tape_dump != TapeDump::Off {
+        eprint!("{}", tape.dump());
+    }
+    if options.exit_code == ExitCode::Cell {
+        std::process::exit(tape.current_cell() as i32);
+    }
+}
+
+/// Reads a Brainfuck program from a file.
+fn read_file(file_path: &str) -> String {
+    let mut file = match File::open(file_path) {
         Ok(file) => file,
         Err(why) => panic!("couldn't open {}: {}", file_path, why),
     };
@@ -79,8 +191,5 @@ fn read_file() -> Program {
         Err(why) => panic!("couldn't read {}: {}", file_path, why),
     }
 
-    Program {
-        content: prog,
-        should_optimise: optimisation,
-    }
+    prog
 }
@@ -0,0 +1,372 @@
+use std::io;
+use std::io::Write;
+
+use colored::Colorize;
+
+use crate::grammar::Expression;
+use crate::ir_dump::{diff, dump_ast, IrDump};
+use crate::optimizer::partial_evaluation::MAX_EVALUATION_STEPS;
+use crate::parser::Parser;
+
+mod combine;
+mod dead_loops;
+mod offsets;
+mod partial_evaluation;
+
+/// Highest optimization level accepted by `-O`
+pub const MAX_OPT_LEVEL: usize = 3;
+
+/// Most times a pipeline is repeated when running to a fixed point
+const MAX_FIXED_POINT_ITERATIONS: usize = 16;
+
+/// Optimization passes that can be run over the abstract syntax tree (AST).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pass {
+    /// Removes loops starting on a cell known to be zero
+    DeadLoops,
+    /// Groups consecutive operators of the same type
+    Combine,
+    /// Evaluates the input free start of the program at compile time
+    PartialEvaluation,
+    /// Folds pointer moves into the offsets of cell modifications
+    Offsets,
+}
+
+impl Pass {
+    /// Every pass in the order they run in by default
+    pub const ALL: [Pass; 4] = [
+        Pass::DeadLoops,
+        Pass::Combine,
+        Pass::PartialEvaluation,
+        Pass::Offsets,
+    ];
+
+    /// Gives the name used for the pass on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::DeadLoops => "dead-loops",
+            Pass::Combine => "combine",
+            Pass::PartialEvaluation => "partial-eval",
+            Pass::Offsets => "offsets",
+        }
+    }
+
+    /// Finds the pass with the given command line name.
+    pub fn from_name(name: &str) -> Option<Pass> {
+        Self::ALL.into_iter().find(|pass| pass.name() == name)
+    }
+
+    /// Runs the pass over the AST.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - A mutable reference to the AST.
+    /// * `evaluation_steps_left` - The steps partial evaluation may still take.
+    ///
+    /// # Returns
+    ///
+    /// The number of loops removed or replaced by the pass.
+    fn run(&self, ast: &mut Vec<Expression>, evaluation_steps_left: &mut usize) -> usize {
+        match self {
+            Pass::DeadLoops => dead_loops::run(ast),
+            Pass::Combine => combine::run(ast),
+            Pass::PartialEvaluation => partial_evaluation::run(ast, evaluation_steps_left),
+            Pass::Offsets => offsets::run(ast),
+        }
+    }
+}
+
+/// Statistics collected for a pass over every time it ran.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PassStats {
+    /// Number of times the pass ran
+    pub runs: usize,
+    /// Number of times the pass changed the AST
+    pub changes: usize,
+    /// Number of instructions removed
+    pub nodes_removed: usize,
+    /// Number of loops removed or replaced
+    pub loops_rewritten: usize,
+}
+
+/// Runs a pipeline of optimization passes and keeps statistics on them.
+#[derive(Debug)]
+pub struct PassManager {
+    /// Passes to run in order
+    pipeline: Vec<Pass>,
+    /// Most times the pipeline is repeated, stopping early once nothing changes
+    max_iterations: usize,
+    /// Statistics of every pass in the pipeline
+    stats: Vec<(Pass, PassStats)>,
+    /// Number of instructions before any pass ran
+    num_of_instr_before: usize,
+    /// Steps partial evaluation may still take, shared by every iteration so the
+    /// whole pipeline executes at most [MAX_EVALUATION_STEPS] of them
+    evaluation_steps_left: usize,
+    /// Dumps of the AST to print around every pass
+    ir_dump: IrDump,
+}
+
+impl PassManager {
+    /// Constructs a new `PassManager` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `pipeline` - The passes to run in order.
+    /// * `fixed_point` - A boolean indicating whether to repeat the pipeline until
+    ///   it stops changing the AST.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `PassManager`.
+    pub fn new(pipeline: Vec<Pass>, fixed_point: bool) -> Self {
+        PassManager {
+            stats: pipeline.iter().map(|&pass| (pass, PassStats::default())).collect(),
+            pipeline,
+            max_iterations: if fixed_point {
+                MAX_FIXED_POINT_ITERATIONS
+            } else {
+                1
+            },
+            num_of_instr_before: 0,
+            evaluation_steps_left: MAX_EVALUATION_STEPS,
+            ir_dump: IrDump::default(),
+        }
+    }
+
+    /// Constructs the `PassManager` for an `-O` level.
+    ///
+    /// * `0` - No passes.
+    /// * `1` - Dead loop elimination and grouping of operators.
+    /// * `2` - Every pass, once.
+    /// * `3` - Every pass, repeated until nothing changes.
+    pub fn with_level(level: usize) -> Self {
+        match level {
+            0 => Self::new(vec![], false),
+            1 => Self::new(vec![Pass::DeadLoops, Pass::Combine], false),
+            2 => Self::new(Pass::ALL.to_vec(), false),
+            _ => Self::new(Pass::ALL.to_vec(), true),
+        }
+    }
+
+    /// Gets the passes that will run in order.
+    pub fn pipeline(&self) -> &[Pass] {
+        &self.pipeline
+    }
+
+    /// Picks the dumps of the AST to print around every pass.
+    pub fn set_ir_dump(&mut self, ir_dump: IrDump) {
+        self.ir_dump = ir_dump;
+    }
+
+    /// Removes a pass from the pipeline.
+    pub fn disable(&mut self, pass: Pass) {
+        self.pipeline.retain(|&p| p != pass);
+        self.stats.retain(|&(p, _)| p != pass);
+    }
+
+    /// Runs the pipeline over the AST.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - A mutable reference to the AST.
+    pub fn run(&mut self, ast: &mut Vec<Expression>) {
+        self.num_of_instr_before = Parser::count_instructions(Some(ast));
+
+        for iteration in 1..=self.max_iterations {
+            let mut changed = false;
+            for idx in 0..self.pipeline.len() {
+                changed |= self.run_pass(idx, iteration, ast);
+            }
+
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Runs the pass at `idx` in the pipeline and records its statistics.
+    ///
+    /// # Returns
+    ///
+    /// Whether the pass changed the AST.
+    fn run_pass(&mut self, idx: usize, iteration: usize, ast: &mut Vec<Expression>) -> bool {
+        let ir_dump = self.ir_dump;
+        let (pass, stats) = &mut self.stats[idx];
+        let before = ast.clone();
+        let num_of_instr = Parser::count_instructions(Some(ast));
+
+        let listing_before = if ir_dump.is_enabled() {
+            dump_ast(ast)
+        } else {
+            String::new()
+        };
+        if ir_dump.before_each_pass {
+            Self::print_dump("Before", *pass, iteration, &listing_before);
+        }
+
+        stats.loops_rewritten += pass.run(ast, &mut self.evaluation_steps_left);
+        stats.runs += 1;
+        stats.nodes_removed +=
+            num_of_instr.saturating_sub(Parser::count_instructions(Some(ast)));
+
+        let changed = *ast != before;
+        if changed {
+            stats.changes += 1;
+        }
+
+        if ir_dump.after_each_pass {
+            Self::print_dump("After", *pass, iteration, &dump_ast(ast));
+        }
+        if ir_dump.diff_each_pass {
+            let listing = if changed {
+                diff(&listing_before, &dump_ast(ast))
+            } else {
+                String::from("  (no changes)\n")
+            };
+            Self::print_dump("Diff of", *pass, iteration, &listing);
+        }
+
+        changed
+    }
+
+    /// Prints a dump of the AST under a header naming the pass.
+    fn print_dump(when: &str, pass: Pass, iteration: usize, listing: &str) {
+        eprintln!(
+            "{}",
+            format!(
+                "*** IR Dump {} {} (iteration {}) ***",
+                when,
+                pass.name(),
+                iteration
+            )
+            .bold()
+        );
+        eprint!("{}", listing);
+    }
+
+    /// Writes the statistics of every pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Where the statistics go, Eg = stderr so they stay out of piped code.
+    /// * `num_of_instr` - The number of instructions after the pipeline ran.
+    pub fn print_stats(&self, out: &mut dyn Write, num_of_instr: usize) -> io::Result<()> {
+        writeln!(
+            out,
+            "{}",
+            format!(
+                "{:<14}{:>6}{:>9}{:>15}{:>17}",
+                "Pass", "Runs", "Changes", "Nodes removed", "Loops rewritten"
+            )
+            .bold()
+        )?;
+        for (pass, stats) in &self.stats {
+            writeln!(
+                out,
+                "{:<14}{:>6}{:>9}{:>15}{:>17}",
+                pass.name(),
+                stats.runs,
+                stats.changes,
+                stats.nodes_removed,
+                stats.loops_rewritten
+            )?;
+        }
+        writeln!(
+            out,
+            "Instructions: {} -> {}",
+            self.num_of_instr_before, num_of_instr
+        )
+    }
+}
+
+/// Parses a program into an AST without running any pass over it.
+#[cfg(test)]
+pub(crate) fn parse(program: &str) -> Vec<Expression> {
+    let mut parser = Parser::new(program.to_string(), PassManager::with_level(0));
+    parser.generate_syntax_tree();
+    parser.get_ast().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Needs a third iteration, as the dead loop is only found from the snapshot made
+    /// by the second
+    static SLOW_TO_SETTLE: &str = "+[-]>>>,<[-]";
+
+    /// Gives the number of runs of every pass in the pipeline.
+    fn runs(pass_manager: &PassManager) -> Vec<usize> {
+        pass_manager.stats.iter().map(|(_, stats)| stats.runs).collect()
+    }
+
+    #[test]
+    fn runs_the_pipeline_once() {
+        let mut pass_manager = PassManager::with_level(2);
+        let mut ast = parse(SLOW_TO_SETTLE);
+        pass_manager.run(&mut ast);
+        assert_eq!(runs(&pass_manager), [1, 1, 1, 1]);
+        assert_eq!(
+            dump_ast(&ast),
+            "snapshot ptr=3 tape=[] output=\"\"\ninput @ p\nmove -1\nloop {\n    sub 1 @ p\n}\n"
+        );
+    }
+
+    #[test]
+    fn fixed_point_repeats_until_nothing_changes() {
+        let mut pass_manager = PassManager::with_level(3);
+        let mut ast = parse(SLOW_TO_SETTLE);
+        pass_manager.run(&mut ast);
+        assert_eq!(runs(&pass_manager), [3, 3, 3, 3]);
+        assert_eq!(dump_ast(&ast), "snapshot ptr=3 tape=[] output=\"\"\ninput @ p\nmove -1\n");
+    }
+
+    #[test]
+    fn fixed_point_stops_at_the_iteration_cap() {
+        let mut pass_manager = PassManager::with_level(3);
+        pass_manager.max_iterations = 2;
+        let mut ast = parse(SLOW_TO_SETTLE);
+        pass_manager.run(&mut ast);
+        assert_eq!(runs(&pass_manager), [2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn fixed_point_shares_the_evaluation_budget() {
+        let mut pass_manager = PassManager::with_level(3);
+        let mut ast = parse("+[]");
+        pass_manager.run(&mut ast);
+        assert_eq!(pass_manager.evaluation_steps_left, 0);
+        assert_eq!(dump_ast(&ast), "snapshot ptr=0 tape=[1] output=\"\"\nloop {}\n");
+    }
+
+    #[test]
+    fn disabled_passes_do_not_run() {
+        let mut pass_manager = PassManager::with_level(3);
+        pass_manager.disable(Pass::PartialEvaluation);
+        let mut ast = parse("++.");
+        pass_manager.run(&mut ast);
+        assert_eq!(pass_manager.pipeline(), [Pass::DeadLoops, Pass::Combine, Pass::Offsets]);
+        assert!(pass_manager.stats.iter().all(|&(pass, _)| pass != Pass::PartialEvaluation));
+        assert_eq!(dump_ast(&ast), "add 2 @ p\noutput @ p\n");
+    }
+
+    #[test]
+    fn writes_stats() {
+        colored::control::set_override(false);
+        let mut pass_manager = PassManager::with_level(1);
+        let mut ast = parse("[-]+++.");
+        pass_manager.run(&mut ast);
+        let mut out = vec![];
+        pass_manager
+            .print_stats(&mut out, Parser::count_instructions(Some(&ast)))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Pass            Runs  Changes  Nodes removed  Loops rewritten\n\
+             dead-loops         1        1              2                1\n\
+             combine            1        1              2                0\n\
+             Instructions: 6 -> 2\n"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::grammar::{Expression, Operator, Token};
+
+/// Groups consecutive operators of the same type acting on the same cell into one.
+///
+/// # Arguments
+///
+/// * `ast` - A mutable reference to the AST.
+///
+/// # Returns
+///
+/// The number of loops rewritten, always zero as loops are left in place.
+pub fn run(ast: &mut Vec<Expression>) -> usize {
+    let mut prev: Option<&mut Operator> = None;
+
+    // The index of optimized out AST nodes to remove
+    let mut nodes_idx: Vec<usize> = vec![];
+
+    for (idx, expression) in ast.iter_mut().enumerate() {
+        match expression {
+            Expression::Loop(_loop, _) => {
+                // Optimize the expressions contained in the loop
+                run(_loop);
+                prev = None;
+            }
+            Expression::Operator(new_op) => {
+                match &mut prev {
+                    Some(old_op) => {
+                        if new_op.type_name != Token::StdOut && new_op.type_name != Token::StdIn {
+                            // Groups non - Std(in/out) tokens
+                            if old_op.type_name == new_op.type_name
+                                && old_op.offset == new_op.offset
+                            {
+                                old_op.count += new_op.count;
+                                old_op.span = old_op.span.to(new_op.span);
+                                nodes_idx.push(idx);
+                                continue;
+                            }
+                        }
+
+                        // Replace the prev operation if the new one differs or is STD(IN/OUT)
+                        prev = Some(new_op);
+                    }
+                    None => {
+                        prev = Some(new_op);
+                    }
+                }
+            }
+            Expression::Snapshot(_) => {
+                prev = None;
+            }
+        }
+    }
+
+    // Delete all operations optimized out
+    nodes_idx.iter().rev().for_each(|&idx| {
+        ast.remove(idx);
+    });
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_dump::dump_ast;
+    use crate::optimizer::{offsets, parse};
+
+    #[test]
+    fn groups_runs_of_the_same_operator() {
+        let mut ast = parse("+++>>--<[-->]");
+        run(&mut ast);
+        assert_eq!(
+            dump_ast(&ast),
+            "add 3 @ p\nmove +2\nsub 2 @ p\nmove -1\nloop {\n    sub 2 @ p\n    move +1\n}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_io_and_operators_on_other_cells_apart() {
+        let mut ast = parse("..,,");
+        run(&mut ast);
+        assert_eq!(dump_ast(&ast), "output @ p\noutput @ p\ninput @ p\ninput @ p\n");
+
+        // Runs split by the offsets pass act on different cells
+        let mut ast = parse("+>+<+>+");
+        offsets::run(&mut ast);
+        run(&mut ast);
+        assert_eq!(dump_ast(&ast), "add 1 @ p\nadd 1 @ p+1\nadd 1 @ p\nadd 1 @ p+1\nmove +1\n");
+
+        let mut ast = parse("++>+<");
+        offsets::run(&mut ast);
+        run(&mut ast);
+        assert_eq!(dump_ast(&ast), "add 2 @ p\nadd 1 @ p+1\n");
+    }
+}
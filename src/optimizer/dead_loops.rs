@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use colored::Colorize;
+
+use crate::grammar::{Expression, Token};
+
+/// Longest loop source shown when warning about a removed loop
+const MAX_WARNING_SOURCE_LEN: usize = 24;
+
+/// Removes the loops that can never execute because the cell they start on is
+/// known to be zero, warning about each of them.
+///
+/// # Arguments
+///
+/// * `ast` - A mutable reference to the AST.
+///
+/// # Returns
+///
+/// The number of loops removed.
+pub fn run(ast: &mut Vec<Expression>) -> usize {
+    // Every cell starts out as zero
+    eliminate_dead_loops(ast, Some(0))
+}
+
+/// Removes the dead loops of `ast` and the loops nested in it.
+///
+/// # Arguments
+///
+/// * `ast` - A mutable reference to the AST.
+/// * `default_cell` - The value of the cells not yet touched by `ast`, if known.
+///
+/// # Returns
+///
+/// The number of loops removed.
+fn eliminate_dead_loops(ast: &mut Vec<Expression>, mut default_cell: Option<u8>) -> usize {
+    // Known values of the cells touched, keyed by their distance from where `ast` starts
+    let mut cells: HashMap<isize, Option<u8>> = HashMap::new();
+    let mut cell_ptr: isize = 0;
+    let mut num_removed = 0;
+
+    // The index of the dead loops to remove
+    let mut nodes_idx: Vec<usize> = vec![];
+
+    for (idx, expression) in ast.iter_mut().enumerate() {
+        match expression {
+            Expression::Loop(_loop, _) => {
+                if cells.get(&cell_ptr).copied().unwrap_or(default_cell) == Some(0) {
+                    warn_dead_loop(_loop);
+                    nodes_idx.push(idx);
+                    continue;
+                }
+
+                // Nothing is known about the cells a loop body starts with
+                num_removed += eliminate_dead_loops(_loop, None);
+
+                // Only the cell the loop exited on is known afterwards
+                cells.clear();
+                cells.insert(0, Some(0));
+                cell_ptr = 0;
+                default_cell = None;
+            }
+            Expression::Operator(op) => {
+                let cell = cell_ptr + op.offset;
+                let value = cells.get(&cell).copied().unwrap_or(default_cell);
+                match op.type_name {
+                    Token::MoveForward => cell_ptr += op.count as isize,
+                    Token::MoveBack => cell_ptr -= op.count as isize,
+                    Token::Add => {
+                        cells.insert(cell, value.map(|v| v.wrapping_add(op.count as u8)));
+                    }
+                    Token::Sub => {
+                        cells.insert(cell, value.map(|v| v.wrapping_sub(op.count as u8)));
+                    }
+                    Token::StdIn => {
+                        cells.insert(cell, None);
+                    }
+                    _ => {}
+                }
+            }
+            Expression::Snapshot(snapshot) => {
+                // The whole tape is known, keyed by the index of the cells
+                cells.clear();
+                cells.extend(
+                    snapshot.tape.iter().enumerate().map(|(idx, &cell)| (idx as isize, Some(cell))),
+                );
+                cell_ptr = snapshot.cell_ptr as isize;
+                default_cell = Some(0);
+            }
+        }
+    }
+
+    // Delete all loops that never execute
+    nodes_idx.iter().rev().for_each(|&idx| {
+        ast.remove(idx);
+    });
+
+    num_removed + nodes_idx.len()
+}
+
+/// Prints a warning for a loop removed because it can never execute.
+///
+/// # Arguments
+///
+/// * `_loop` - The body of the removed loop.
+fn warn_dead_loop(_loop: &[Expression]) {
+    let mut source = format!("[{}]", to_source(_loop));
+    if source.len() > MAX_WARNING_SOURCE_LEN {
+        source.truncate(MAX_WARNING_SOURCE_LEN - 3);
+        source.push_str("...");
+    }
+
+    eprintln!(
+        "{}: Removed the loop {} as it starts on a cell that is always zero",
+        "Warning".yellow(),
+        source.bold()
+    );
+}
+
+/// Converts expressions back into Brain FK source.
+///
+/// # Arguments
+///
+/// * `expressions` - The expressions to convert.
+///
+/// # Returns
+///
+/// The source code the expressions were parsed from.
+fn to_source(expressions: &[Expression]) -> String {
+    let mut source = String::new();
+    for expression in expressions {
+        match expression {
+            Expression::Loop(_loop, _) => {
+                source.push('[');
+                source.push_str(&to_source(_loop));
+                source.push(']');
+            }
+            Expression::Operator(op) => {
+                let (to_cell, from_cell) = if op.offset < 0 { ('<', '>') } else { ('>', '<') };
+                let distance = op.offset.unsigned_abs();
+                source.extend(std::iter::repeat_n(to_cell, distance));
+                source.extend(std::iter::repeat_n(op.type_name as u8 as char, op.count));
+                source.extend(std::iter::repeat_n(from_cell, distance));
+            }
+            // Evaluated code has no source left
+            Expression::Snapshot(_) => {}
+        }
+    }
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_dump::dump_ast;
+    use crate::optimizer::parse;
+
+    #[test]
+    fn removes_loops_at_the_start() {
+        let mut ast = parse("[-][+]+.");
+        assert_eq!(run(&mut ast), 2);
+        assert_eq!(dump_ast(&ast), "add 1 @ p\noutput @ p\n");
+    }
+
+    #[test]
+    fn removes_loops_right_after_a_loop() {
+        let mut ast = parse("+[-][+>]");
+        assert_eq!(run(&mut ast), 1);
+        assert_eq!(dump_ast(&ast), "add 1 @ p\nloop {\n    sub 1 @ p\n}\n");
+    }
+
+    #[test]
+    fn keeps_loops_on_unknown_cells() {
+        let mut ast = parse(",[-]+[[-]]");
+        assert_eq!(run(&mut ast), 0);
+        assert_eq!(
+            dump_ast(&ast),
+            "input @ p\nloop {\n    sub 1 @ p\n}\nadd 1 @ p\nloop {\n    loop {\n        sub 1 @ p\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn removes_loops_on_cells_known_to_be_zero_again() {
+        let mut ast = parse("+>+<-[>]");
+        assert_eq!(run(&mut ast), 1);
+        assert_eq!(dump_ast(&ast), "add 1 @ p\nmove +1\nadd 1 @ p\nmove -1\nsub 1 @ p\n");
+    }
+}
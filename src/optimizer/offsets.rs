@@ -0,0 +1,122 @@
+use crate::grammar::{Expression, Operator, Span, Token};
+
+/// Folds the pointer moves of every basic block into the offsets of the cell
+/// modifications that follow them, so the cell pointer is only moved once
+/// before loops, I/O and at the end of the block.
+///
+/// # Arguments
+///
+/// * `ast` - A mutable reference to the AST.
+///
+/// # Returns
+///
+/// The number of loops rewritten, always zero as loops are left in place.
+pub fn run(ast: &mut Vec<Expression>) -> usize {
+    let mut expressions: Vec<Expression> = Vec::with_capacity(ast.len());
+
+    // Distance of the virtual cell pointer from the real one, along with the source of
+    // the moves making it up
+    let mut offset: isize = 0;
+    let mut moves: Option<Span> = None;
+
+    for expression in ast.drain(..) {
+        match expression {
+            Expression::Loop(mut _loop, span) => {
+                // Loops check the cell under the real pointer
+                materialize_offset(&mut expressions, &mut offset, &mut moves);
+                run(&mut _loop);
+                expressions.push(Expression::Loop(_loop, span));
+            }
+            Expression::Operator(mut op) => match op.type_name {
+                Token::MoveForward | Token::MoveBack => {
+                    offset += match op.type_name {
+                        Token::MoveForward => op.count as isize,
+                        _ => -(op.count as isize),
+                    };
+                    moves = Some(moves.map_or(op.span, |span| span.to(op.span)));
+                }
+                Token::Add | Token::Sub => {
+                    op.offset += offset;
+                    expressions.push(Expression::Operator(op));
+                }
+                _ => {
+                    // I/O works on the cell under the real pointer
+                    materialize_offset(&mut expressions, &mut offset, &mut moves);
+                    expressions.push(Expression::Operator(op));
+                }
+            },
+            Expression::Snapshot(snapshot) => {
+                // Snapshots place the pointer themselves
+                materialize_offset(&mut expressions, &mut offset, &mut moves);
+                expressions.push(Expression::Snapshot(snapshot));
+            }
+        }
+    }
+
+    // The next block expects the pointer to be where the source left it
+    materialize_offset(&mut expressions, &mut offset, &mut moves);
+
+    *ast = expressions;
+    0
+}
+
+/// Emits a single pointer move covering the pending `offset` and resets it.
+///
+/// # Arguments
+///
+/// * `expressions` - The block being rebuilt.
+/// * `offset` - The pending offset of the virtual cell pointer.
+/// * `moves` - The source of the moves making up the offset, taken along.
+fn materialize_offset(expressions: &mut Vec<Expression>, offset: &mut isize, moves: &mut Option<Span>) {
+    let Some(span) = moves.take().filter(|_| *offset != 0) else {
+        *offset = 0;
+        return;
+    };
+
+    expressions.push(Expression::Operator(Box::new(Operator {
+        type_name: if *offset > 0 {
+            Token::MoveForward
+        } else {
+            Token::MoveBack
+        },
+        count: offset.unsigned_abs(),
+        offset: 0,
+        span,
+    })));
+    *offset = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_dump::dump_ast;
+    use crate::optimizer::parse;
+
+    #[test]
+    fn folds_moves_into_offsets() {
+        let mut ast = parse(">+>-<<+");
+        run(&mut ast);
+        assert_eq!(dump_ast(&ast), "add 1 @ p+1\nsub 1 @ p+2\nadd 1 @ p\n");
+    }
+
+    #[test]
+    fn moves_the_pointer_before_loops_and_io() {
+        let mut ast = parse(">>+.<[-<+>]");
+        run(&mut ast);
+        assert_eq!(
+            dump_ast(&ast),
+            "add 1 @ p+2\nmove +2\noutput @ p\nmove -1\nloop {\n    sub 1 @ p\n    add 1 @ p-1\n}\n"
+        );
+    }
+
+    #[test]
+    fn leaves_the_pointer_where_the_source_does() {
+        let mut ast = parse(">>+<");
+        run(&mut ast);
+        assert_eq!(dump_ast(&ast), "add 1 @ p+2\nmove +1\n");
+
+        let mut ast = parse("><+");
+        run(&mut ast);
+        assert_eq!(dump_ast(&ast), "add 1 @ p\n");
+    }
+}
@@ -0,0 +1,101 @@
+use crate::grammar::Expression;
+use crate::interpreter::Interpreter;
+
+/// Number of instructions that may be executed when evaluating the program at compile time,
+/// shared by every time the pass runs over it
+pub const MAX_EVALUATION_STEPS: usize = 1_000_000;
+
+/// Runs the start of the program up to its first input at compile time and replaces
+/// it with the state of the machine and the output it produced.
+///
+/// Whole top level expressions are evaluated until one needs input, leaves the tape
+/// or uses up the steps left; that expression and the rest of the program are left
+/// to run from the snapshot.
+///
+/// # Arguments
+///
+/// * `ast` - A mutable reference to the AST.
+/// * `steps_left` - The number of instructions that may still be executed, taking off
+///   the ones executed here, so running the pass again only picks up where it stopped.
+///
+/// # Returns
+///
+/// The number of loops evaluated away.
+pub fn run(ast: &mut Vec<Expression>, steps_left: &mut usize) -> usize {
+    let mut interpreter = Interpreter::new(*steps_left);
+    let mut output: Vec<u8> = vec![];
+    let mut num_evaluated = 0;
+
+    for expression in ast.iter() {
+        let checkpoint = (interpreter.clone(), output.len());
+        let halted = interpreter.run(std::slice::from_ref(expression), &mut output).is_err();
+        // The steps of a partly evaluated expression are spent all the same
+        *steps_left = interpreter.steps_left();
+        if halted {
+            // Roll back the partly evaluated expression
+            interpreter = checkpoint.0;
+            output.truncate(checkpoint.1);
+            break;
+        }
+        num_evaluated += 1;
+    }
+
+    if num_evaluated == 0 {
+        return 0;
+    }
+
+    let span = ast[0].span().to(ast[num_evaluated - 1].span());
+    let snapshot = Expression::Snapshot(Box::new(interpreter.snapshot(output, span)));
+    ast.splice(..num_evaluated, [snapshot])
+        .filter(|expression| matches!(expression, Expression::Loop(..)))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_dump::dump_ast;
+    use crate::optimizer::parse;
+
+    #[test]
+    fn evaluates_up_to_the_first_input() {
+        let mut ast = parse("++[>+<-]>.,+");
+        let mut steps_left = MAX_EVALUATION_STEPS;
+        assert_eq!(run(&mut ast, &mut steps_left), 1);
+        assert_eq!(
+            dump_ast(&ast),
+            "snapshot ptr=1 tape=[0, 2] output=\"\\x02\"\ninput @ p\nadd 1 @ p\n"
+        );
+        assert!(steps_left < MAX_EVALUATION_STEPS);
+    }
+
+    #[test]
+    fn stops_before_leaving_the_tape() {
+        let mut ast = parse("+[>+]");
+        let mut steps_left = MAX_EVALUATION_STEPS;
+        assert_eq!(run(&mut ast, &mut steps_left), 0);
+        assert_eq!(
+            dump_ast(&ast),
+            "snapshot ptr=0 tape=[1] output=\"\"\nloop {\n    move +1\n    add 1 @ p\n}\n"
+        );
+    }
+
+    #[test]
+    fn stops_at_the_step_budget() {
+        let mut ast = parse("++.+[]");
+        let mut steps_left = 100;
+        assert_eq!(run(&mut ast, &mut steps_left), 0);
+        assert_eq!(
+            dump_ast(&ast),
+            "snapshot ptr=0 tape=[3] output=\"\\x02\"\nloop {}\n"
+        );
+        assert_eq!(steps_left, 0);
+
+        // Running again with the budget used up leaves the program as it is
+        assert_eq!(run(&mut ast, &mut steps_left), 0);
+        assert_eq!(
+            dump_ast(&ast),
+            "snapshot ptr=0 tape=[3] output=\"\\x02\"\nloop {}\n"
+        );
+    }
+}
@@ -1,16 +1,17 @@
-use crate::grammar::{Expression, Operator, Token};
+use crate::grammar::{Expression, Operator, Span, Token};
 use crate::lexer::Lexer;
+use crate::optimizer::PassManager;
 use colored::Colorize;
 
 /// Struct representing a parser for the custom language.
 #[derive(Debug)]
 pub struct Parser {
-    /// Lexer instance to tokenize the program
-    tokens: Vec<Token>,
+    /// Tokens of the program along with their spans
+    tokens: Vec<(Token, Span)>,
     /// Index to keep track of parsing progress
     parser_index: usize,
-    /// Flag indicating whether to optimize the AST
-    should_optimize: bool,
+    /// Optimization passes to run over the AST
+    pass_manager: PassManager,
     /// Holds the parsed tree
     syntax_tree: Option<Vec<Expression>>,
     /// Number of instructions
@@ -23,16 +24,16 @@ impl Parser {
     /// # Arguments
     ///
     /// * `program` - The program string to be parsed.
-    /// * `optimize` - A boolean indicating whether to optimize the AST.
+    /// * `pass_manager` - The optimization passes to run over the AST.
     ///
     /// # Returns
     ///
     /// A new instance of `Parser`.
-    pub fn new(program: String, optimize: bool) -> Self {
+    pub fn new(program: String, pass_manager: PassManager) -> Self {
         Parser {
             tokens: Lexer::new(program).move_tokens(),
             parser_index: 0,
-            should_optimize: optimize,
+            pass_manager,
             syntax_tree: None,
             num_of_instr: 0,
         }
@@ -45,9 +46,7 @@ impl Parser {
     /// The AST represented as a vector of `Expression`.
     pub fn generate_syntax_tree(&mut self) {
         let mut ast = self.parse_to_ast();
-        if self.should_optimize {
-            Self::optimize_ast(&mut ast);
-        }
+        self.pass_manager.run(&mut ast);
         self.syntax_tree = Some(ast);
         self.num_of_instr = Parser::count_instructions(self.get_ast());
     }
@@ -61,6 +60,15 @@ impl Parser {
         self.num_of_instr
     }
 
+    /// Gets the pass manager that optimized the AST.
+    ///
+    /// # Returns
+    ///
+    /// The `PassManager` holding the statistics of every pass.
+    pub fn get_pass_manager(&self) -> &PassManager {
+        &self.pass_manager
+    }
+
     /// Gets the abstract syntax tree (AST) of the program.
     ///
     /// # Returns
@@ -79,17 +87,24 @@ impl Parser {
         let mut expressions: Vec<Expression> = vec![];
 
         while self.parser_index < self.tokens.len() {
-            let token = self.tokens[self.parser_index];
+            let (token, span) = self.tokens[self.parser_index];
             self.parser_index += 1;
 
             expressions.push(match token {
-                Token::LoopStart => Expression::Loop(self.parse_to_ast()),
+                Token::LoopStart => {
+                    let body = self.parse_to_ast();
+                    // The lexer made sure the loop is closed, by the token just parsed
+                    let (_, end) = self.tokens[self.parser_index - 1];
+                    Expression::Loop(body, span.to(end))
+                }
                 Token::LoopEnd => {
                     return expressions;
                 }
                 _ => Expression::Operator(Box::new(Operator {
                     type_name: token,
                     count: 1,
+                    offset: 0,
+                    span,
                 })),
             });
         }
@@ -97,54 +112,6 @@ impl Parser {
         expressions
     }
 
-    /// Optimizes the abstract syntax tree (AST) by removing redundant operations.
-    ///
-    /// # Arguments
-    ///
-    /// * `ast` - A mutable reference to the AST.
-    fn optimize_ast(ast: &mut Vec<Expression>) {
-        let mut prev: Option<&mut Operator> = None;
-
-        // The index of optimized out AST nodes to remove
-        let mut nodes_idx: Vec<usize> = vec![];
-
-        for (idx, expression) in ast.iter_mut().enumerate() {
-            match expression {
-                Expression::Loop(_loop) => {
-                    // Optimize the expressions contained in the loop
-                    Self::optimize_ast(_loop);
-                    prev = None;
-                }
-                Expression::Operator(new_op) => {
-                    match &mut prev {
-                        Some(old_op) => {
-                            if new_op.type_name != Token::StdOut && new_op.type_name != Token::StdIn
-                            {
-                                // Groups non - Std(in/out) tokens
-                                if old_op.type_name == new_op.type_name {
-                                    old_op.count += 1;
-                                    nodes_idx.push(idx);
-                                    continue;
-                                }
-                            }
-
-                            // Replace the prev operation if the new one differs or is STD(IN/OUT)
-                            prev = Some(new_op);
-                        }
-                        None => {
-                            prev = Some(new_op);
-                        }
-                    }
-                }
-            }
-        }
-
-        // Delete all operations optimized out
-        nodes_idx.iter().rev().for_each(|&idx| {
-            ast.remove(idx);
-        })
-    }
-
     /// Counts the number of instructions in the AST.
     ///
     /// # Arguments
@@ -154,19 +121,22 @@ impl Parser {
     /// # Returns
     ///
     /// The number of instructions.
-    fn count_instructions(ast_tree: Option<&Vec<Expression>>) -> usize {
+    pub fn count_instructions(ast_tree: Option<&Vec<Expression>>) -> usize {
         let mut count: usize = 0;
         match ast_tree {
             Some(tree) => {
                 for node in tree {
                     match node {
-                        Expression::Loop(_loop) => {
+                        Expression::Loop(_loop, _) => {
                             // + 1 is to count the loop itself
                             count += Self::count_instructions(Some(_loop)) + 1;
                         }
                         Expression::Operator(_op) => {
                             count += 1;
                         }
+                        Expression::Snapshot(_) => {
+                            count += 1;
+                        }
                     }
                 }
             }
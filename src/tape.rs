@@ -0,0 +1,96 @@
+use crate::grammar::TAPE_LEN;
+
+/// Text starting a tape dump, followed by the cell pointer
+pub static DUMP_HEADER: &str = "Cell pointer: ";
+/// Digits of the hexadecimal numbers in a tape dump
+pub static HEX_DIGITS: &str = "0123456789abcdef";
+/// Number of cells per line of a tape dump
+pub const CELLS_PER_LINE: usize = 16;
+/// Hex digits of the cell pointer and of the index starting every line of a tape dump
+pub const INDEX_DIGITS: usize = 4;
+/// Hex digits of every cell of a tape dump
+pub const CELL_DIGITS: usize = 2;
+/// Most bytes of a tape dump after the [DUMP_HEADER]: the cell pointer, the start of
+/// every line with its line break and colon, every cell with its space and the final
+/// line break
+pub const MAX_DUMP_LEN: usize = INDEX_DIGITS
+    + TAPE_LEN.div_ceil(CELLS_PER_LINE) * (INDEX_DIGITS + 2)
+    + TAPE_LEN * (CELL_DIGITS + 1)
+    + 1;
+
+/// The tape of a program that finished running.
+#[derive(Debug)]
+pub struct Tape {
+    /// Every cell of the tape
+    pub cells: Vec<u8>,
+    /// Index of the current cell
+    pub cell_ptr: usize,
+}
+
+impl Tape {
+    /// Gives the value of the current cell.
+    pub fn current_cell(&self) -> u8 {
+        self.cells[self.cell_ptr]
+    }
+
+    /// Gives a hex dump of the used part of the tape, the cells up to the last one that
+    /// is not zero or up to the current one, whichever comes last.
+    ///
+    /// Every target writes its dump in this format, with the cell pointer and the index
+    /// starting each line in [INDEX_DIGITS] hex digits and the cells in [CELL_DIGITS].
+    /// Eg = 'Cell pointer: 0001\n0000: 00 2a\n'
+    pub fn dump(&self) -> String {
+        let last_used = self
+            .cells
+            .iter()
+            .rposition(|&cell| cell != 0)
+            .unwrap_or(0)
+            .max(self.cell_ptr);
+
+        let mut dump = format!("{}{:0w$x}", DUMP_HEADER, self.cell_ptr, w = INDEX_DIGITS);
+        for (idx, cell) in self.cells[..=last_used].iter().enumerate() {
+            if idx % CELLS_PER_LINE == 0 {
+                dump.push_str(&format!("\n{:0w$x}:", idx, w = INDEX_DIGITS));
+            }
+            dump.push_str(&format!(" {:0w$x}", cell, w = CELL_DIGITS));
+        }
+        dump.push('\n');
+        dump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gives a tape with the given cells at its start
+    fn tape(start: &[u8], cell_ptr: usize) -> Tape {
+        let mut cells = vec![0; TAPE_LEN];
+        cells[..start.len()].copy_from_slice(start);
+        Tape { cells, cell_ptr }
+    }
+
+    #[test]
+    fn dumps_the_cells_up_to_the_last_used_one() {
+        assert_eq!(tape(&[], 0).dump(), "Cell pointer: 0000\n0000: 00\n");
+        assert_eq!(tape(&[0, 42], 0).dump(), "Cell pointer: 0000\n0000: 00 2a\n");
+        assert_eq!(tape(&[7], 2).dump(), "Cell pointer: 0002\n0000: 07 00 00\n");
+    }
+
+    #[test]
+    fn dumps_sixteen_cells_per_line() {
+        let cells: Vec<u8> = (1..=17).collect();
+        assert_eq!(
+            tape(&cells, 16).dump(),
+            "Cell pointer: 0010\n\
+             0000: 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10\n\
+             0010: 11\n"
+        );
+    }
+
+    #[test]
+    fn fits_the_longest_dump_in_the_buffer() {
+        let dump = tape(&[0xff; TAPE_LEN], TAPE_LEN - 1).dump();
+        assert_eq!(dump.len(), DUMP_HEADER.len() + MAX_DUMP_LEN);
+    }
+}
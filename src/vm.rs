@@ -0,0 +1,189 @@
+use std::io;
+use std::io::{Read, Write};
+
+use crate::grammar::{Expression, PROMPT, TAPE_LEN};
+use crate::tape::Tape;
+use crate::vm::bytecode::{Bytecode, Instr};
+
+pub mod bytecode;
+
+/// Virtual machine running programs compiled to [Bytecode].
+///
+/// Runs anywhere Rust does, with loops reduced to jumps to precomputed targets and
+/// common loops fused into single instructions. Like the assembly targets `,` prompts
+/// the user and leaves the cell untouched on end of file, but the cell pointer leaving
+/// the tape is an error instead of corrupting memory.
+pub struct Vm {
+    /// Instructions and data of the program
+    bytecode: Bytecode,
+}
+
+impl Vm {
+    /// Compiles the program for the VM.
+    ///
+    /// # Arguments
+    ///
+    /// * `syntax_tree` - The optimized program.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Vm`.
+    pub fn new(syntax_tree: &[Expression]) -> Self {
+        Vm {
+            bytecode: Bytecode::compile(syntax_tree),
+        }
+    }
+
+    /// Runs the program on a fresh tape.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Where `,` reads from, after printing a prompt to `output`.
+    /// * `output` - Where `.` writes to.
+    ///
+    /// # Returns
+    ///
+    /// The tape at the end of the program, or the first error hit while reading or
+    /// writing or when the cell pointer left the tape.
+    pub fn run(&self, input: &mut impl Read, output: &mut impl Write) -> Result<Tape, io::Error> {
+        let instrs = &self.bytecode.instrs;
+        let mut tape = [0u8; TAPE_LEN];
+        let mut cell_ptr: usize = 0;
+        let mut pc: usize = 0;
+
+        while let Some(&instr) = instrs.get(pc) {
+            pc += 1;
+            match instr {
+                Instr::Add { amount, offset } => {
+                    let cell = cell_at(&mut tape, cell_ptr, offset)?;
+                    *cell = cell.wrapping_add(amount);
+                }
+                Instr::Move(amount) => cell_ptr = cell_ptr.wrapping_add_signed(amount),
+                Instr::Clear { offset } => *cell_at(&mut tape, cell_ptr, offset)? = 0,
+                Instr::MulAdd { factor, offset } => {
+                    // The loop this comes from does not touch other cells when skipped
+                    let value = *cell_at(&mut tape, cell_ptr, 0)?;
+                    if value != 0 {
+                        let cell = cell_at(&mut tape, cell_ptr, offset)?;
+                        *cell = cell.wrapping_add(value.wrapping_mul(factor));
+                    }
+                }
+                Instr::Set { index, value } => tape[index] = value,
+                Instr::SetPtr(index) => cell_ptr = index,
+                Instr::Output { offset } => {
+                    output.write_all(&[*cell_at(&mut tape, cell_ptr, offset)?])?
+                }
+                Instr::Input { offset } => {
+                    output.write_all(PROMPT.as_bytes())?;
+                    output.flush()?;
+                    let mut byte = [0u8];
+                    if input.read(&mut byte)? == 1 {
+                        *cell_at(&mut tape, cell_ptr, offset)? = byte[0];
+                    }
+                }
+                Instr::Print { start, len } => {
+                    output.write_all(&self.bytecode.data[start..start + len])?
+                }
+                Instr::JumpIfZero(target) => {
+                    if *cell_at(&mut tape, cell_ptr, 0)? == 0 {
+                        pc = target;
+                    }
+                }
+                Instr::JumpIfNotZero(target) => {
+                    if *cell_at(&mut tape, cell_ptr, 0)? != 0 {
+                        pc = target;
+                    }
+                }
+            }
+        }
+
+        output.flush()?;
+        cell_at(&mut tape, cell_ptr, 0)?;
+        Ok(Tape {
+            cells: tape.to_vec(),
+            cell_ptr,
+        })
+    }
+}
+
+/// Gives the cell `offset` cells away from the cell pointer, or an error if it is
+/// not on the tape.
+fn cell_at(tape: &mut [u8; TAPE_LEN], cell_ptr: usize, offset: isize) -> Result<&mut u8, io::Error> {
+    cell_ptr
+        .checked_add_signed(offset)
+        .and_then(|idx| tape.get_mut(idx))
+        .ok_or_else(|| io::Error::other("The cell pointer left the tape"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{without_prompts, Interpreter, TEST_PROGRAMS};
+    use crate::optimizer::{PassManager, MAX_OPT_LEVEL};
+    use crate::parser::Parser;
+
+    /// Parses a program and optimizes it at the given level.
+    fn parse(program: &str, opt_level: usize) -> Vec<Expression> {
+        let mut parser = Parser::new(program.to_string(), PassManager::with_level(opt_level));
+        parser.generate_syntax_tree();
+        parser.get_ast().unwrap().clone()
+    }
+
+    #[test]
+    fn runs_programs_like_the_interpreter() {
+        for (program, input) in TEST_PROGRAMS {
+            let mut interpreter = Interpreter::new(usize::MAX).with_input(input);
+            let mut expected_output = vec![];
+            interpreter.run(&parse(program, 0), &mut expected_output).unwrap();
+            let (expected_tape, expected_cell_ptr) = interpreter.tape();
+
+            for opt_level in 0..=MAX_OPT_LEVEL {
+                let mut output = vec![];
+                let tape = Vm::new(&parse(program, opt_level))
+                    .run(&mut &input[..], &mut output)
+                    .unwrap();
+
+                assert_eq!(
+                    without_prompts(&output, PROMPT),
+                    expected_output,
+                    "Output of {} at -O{}",
+                    program,
+                    opt_level
+                );
+                assert_eq!(
+                    tape.cells[..],
+                    expected_tape[..],
+                    "Tape of {} at -O{}",
+                    program,
+                    opt_level
+                );
+                assert_eq!(
+                    tape.cell_ptr, expected_cell_ptr,
+                    "Cell pointer of {} at -O{}",
+                    program, opt_level
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stops_when_leaving_the_tape() {
+        for program in ["+[>+]", "+>>,[<<]", "+[>+>+<]"] {
+            for opt_level in 0..=MAX_OPT_LEVEL {
+                let vm = Vm::new(&parse(program, opt_level));
+                let error = vm.run(&mut &b"a"[..], &mut vec![]).expect_err(program);
+                assert_eq!(error.to_string(), "The cell pointer left the tape");
+            }
+        }
+    }
+
+    #[test]
+    fn gives_back_the_current_cell() {
+        for (program, cell) in [("+++>++<-", 2), ("+++>+", 1), (",[>+<-]>+", 98)] {
+            for opt_level in 0..=MAX_OPT_LEVEL {
+                let vm = Vm::new(&parse(program, opt_level));
+                assert_eq!(vm.run(&mut &b"a"[..], &mut vec![]).unwrap().current_cell(), cell, "{}", program);
+            }
+        }
+    }
+}
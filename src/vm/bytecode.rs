@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+use crate::grammar::{Expression, Token};
+
+/// An instruction of the VM, working on cells at an offset from the cell pointer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Instr {
+    /// Adds `amount` to the cell at `offset`
+    Add { amount: u8, offset: isize },
+    /// Moves the cell pointer by `amount` cells
+    Move(isize),
+    /// Sets the cell at `offset` to zero, from loops like `[-]`
+    Clear { offset: isize },
+    /// Adds the current cell times `factor` to the cell at `offset`, from loops like `[->++<]`
+    MulAdd { factor: u8, offset: isize },
+    /// Sets the cell at `index` on the tape to `value`
+    Set { index: usize, value: u8 },
+    /// Moves the cell pointer to `index`
+    SetPtr(usize),
+    /// Prints the cell at `offset`
+    Output { offset: isize },
+    /// Reads a byte into the cell at `offset`
+    Input { offset: isize },
+    /// Prints `len` bytes of the data of the program starting at `start`
+    Print { start: usize, len: usize },
+    /// Jumps to the instruction at the index when the current cell is zero
+    JumpIfZero(usize),
+    /// Jumps to the instruction at the index when the current cell is not zero
+    JumpIfNotZero(usize),
+}
+
+/// A program compiled to instructions for the VM.
+#[derive(Debug, Default)]
+pub struct Bytecode {
+    /// Instructions of the program
+    pub instrs: Vec<Instr>,
+    /// Output computed at compile time printed by [Instr::Print]
+    pub data: Vec<u8>,
+}
+
+impl Bytecode {
+    /// Compiles the syntax tree to bytecode.
+    ///
+    /// # Arguments
+    ///
+    /// * `syntax_tree` - The optimized program.
+    ///
+    /// # Returns
+    ///
+    /// The bytecode of the program.
+    pub fn compile(syntax_tree: &[Expression]) -> Self {
+        let mut bytecode = Bytecode::default();
+        bytecode.compile_(syntax_tree);
+        bytecode
+    }
+
+    /// Recursively compiles the given expressions.
+    fn compile_(&mut self, expressions: &[Expression]) {
+        for expr in expressions {
+            match expr {
+                Expression::Loop(_loop, _) => {
+                    if let Some(instrs) = Self::fuse_loop(_loop) {
+                        self.instrs.extend(instrs);
+                        continue;
+                    }
+
+                    let start = self.instrs.len();
+                    // Patched once the end of the loop is known
+                    self.instrs.push(Instr::JumpIfZero(0));
+                    self.compile_(_loop);
+                    self.instrs.push(Instr::JumpIfNotZero(start + 1));
+                    self.instrs[start] = Instr::JumpIfZero(self.instrs.len());
+                }
+                Expression::Operator(_op) => {
+                    let count = _op.count as isize;
+                    self.instrs.push(match _op.type_name {
+                        Token::MoveBack => Instr::Move(-count),
+                        Token::MoveForward => Instr::Move(count),
+                        // Cells wrap around so only the low byte of the count matters
+                        Token::Add => Instr::Add {
+                            amount: count as u8,
+                            offset: _op.offset,
+                        },
+                        Token::Sub => Instr::Add {
+                            amount: count.wrapping_neg() as u8,
+                            offset: _op.offset,
+                        },
+                        Token::StdOut => Instr::Output { offset: _op.offset },
+                        Token::StdIn => Instr::Input { offset: _op.offset },
+                        _ => {
+                            eprintln!(
+                                "Unexpected Token: {:?} when generating bytecode ",
+                                _op.type_name
+                            );
+                            std::process::exit(123);
+                        }
+                    });
+                }
+                Expression::Snapshot(snapshot) => {
+                    self.instrs.push(Instr::SetPtr(0));
+                    snapshot
+                        .tape
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &value)| value != 0)
+                        .for_each(|(index, &value)| self.instrs.push(Instr::Set { index, value }));
+                    self.instrs.push(Instr::SetPtr(snapshot.cell_ptr));
+
+                    if !snapshot.output.is_empty() {
+                        self.instrs.push(Instr::Print {
+                            start: self.data.len(),
+                            len: snapshot.output.len(),
+                        });
+                        self.data.extend_from_slice(&snapshot.output);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Turns a loop that only adds to cells around the current one and steps the
+    /// current cell towards zero one at a time into straight line code.
+    ///
+    /// # Returns
+    ///
+    /// The instructions doing the work of the loop, `None` if it cannot be fused.
+    fn fuse_loop(body: &[Expression]) -> Option<Vec<Instr>> {
+        // Amount added to every cell in one round of the loop
+        let mut deltas: BTreeMap<isize, u8> = BTreeMap::new();
+        let mut ptr: isize = 0;
+
+        for expr in body {
+            let Expression::Operator(op) = expr else {
+                return None;
+            };
+            let count = op.count as isize;
+            match op.type_name {
+                Token::MoveForward => ptr += count,
+                Token::MoveBack => ptr -= count,
+                Token::Add | Token::Sub => {
+                    let amount = match op.type_name {
+                        Token::Add => count as u8,
+                        _ => count.wrapping_neg() as u8,
+                    };
+                    let delta = deltas.entry(ptr + op.offset).or_insert(0);
+                    *delta = delta.wrapping_add(amount);
+                }
+                _ => return None,
+            }
+        }
+
+        // The loop must end where it started and count the current cell down
+        // or up by one, running as many rounds as the cell, or its negation, says
+        if ptr != 0 {
+            return None;
+        }
+        let negate = match deltas.remove(&0) {
+            Some(255) => false,
+            Some(1) => true,
+            _ => return None,
+        };
+
+        let mut instrs: Vec<Instr> = deltas
+            .into_iter()
+            .filter(|(_, delta)| *delta != 0)
+            .map(|(offset, delta)| Instr::MulAdd {
+                factor: if negate { delta.wrapping_neg() } else { delta },
+                offset,
+            })
+            .collect();
+        instrs.push(Instr::Clear { offset: 0 });
+        Some(instrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::PassManager;
+    use crate::parser::Parser;
+
+    /// Compiles a program combined but not evaluated at compile time
+    fn compile(program: &str) -> Vec<Instr> {
+        let mut parser = Parser::new(program.to_string(), PassManager::with_level(0));
+        parser.generate_syntax_tree();
+        let mut ast = parser.get_ast().unwrap().clone();
+        PassManager::new(vec![crate::optimizer::Pass::Combine], false).run(&mut ast);
+        Bytecode::compile(&ast).instrs
+    }
+
+    #[test]
+    fn fuses_clear_and_multiply_loops() {
+        assert_eq!(compile(",[-]"), [Instr::Input { offset: 0 }, Instr::Clear { offset: 0 }]);
+        assert_eq!(
+            compile(",[->++>>-<<<]"),
+            [
+                Instr::Input { offset: 0 },
+                Instr::MulAdd { factor: 2, offset: 1 },
+                Instr::MulAdd { factor: 255, offset: 3 },
+                Instr::Clear { offset: 0 },
+            ]
+        );
+        // Counting up runs as many rounds as the negation of the cell
+        assert_eq!(
+            compile(",[+>+<]"),
+            [
+                Instr::Input { offset: 0 },
+                Instr::MulAdd { factor: 255, offset: 1 },
+                Instr::Clear { offset: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_loops_that_cannot_be_fused() {
+        assert_eq!(
+            compile(",[>]"),
+            [
+                Instr::Input { offset: 0 },
+                Instr::JumpIfZero(4),
+                Instr::Move(1),
+                Instr::JumpIfNotZero(2),
+            ]
+        );
+        assert_eq!(compile(",[--]").len(), 4);
+        assert_eq!(compile(",[->+<.]").len(), 8);
+    }
+}